@@ -0,0 +1,68 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum AckStatus {
+    Received,
+    Accepted,
+    Rejected,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn acknowledge_mail(&mut self, email_id: U128, status: AckStatus) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let receiver = env::predecessor_account_id();
+        require(
+            self.holds_received(&receiver, real_email_id),
+            ContractError::CallerNotReceiver,
+        );
+        let mut email = self.emails.get(&real_email_id).unwrap();
+        email.ack = Some(status);
+        self.emails.insert(&real_email_id, &email);
+        self.internal_touch(&receiver);
+    }
+
+    pub fn get_ack(&self, email_id: U128) -> Option<AckStatus> {
+        self.emails.get(&email_id.0).and_then(|email| email.ack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    use super::AckStatus;
+
+    fn setup_mail() -> (Contract, U128) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = U128(send(&mut contract, &alice(), &bob(), "offer"));
+        (contract, email_id)
+    }
+
+    #[test]
+    fn receiver_acknowledgement_replaces_the_last_one() {
+        let (mut contract, email_id) = setup_mail();
+        assert!(contract.get_ack(email_id).is_none());
+        set_context(&bob(), 1);
+        contract.acknowledge_mail(email_id, AckStatus::Received);
+        assert!(contract.get_ack(email_id) == Some(AckStatus::Received));
+        set_context(&bob(), 1);
+        contract.acknowledge_mail(email_id, AckStatus::Accepted);
+        assert!(contract.get_ack(email_id) == Some(AckStatus::Accepted));
+    }
+
+    #[test]
+    #[should_panic(expected = "E009")]
+    fn only_the_receiver_acknowledges() {
+        let (mut contract, email_id) = setup_mail();
+        set_context(&alice(), 1);
+        contract.acknowledge_mail(email_id, AckStatus::Rejected);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ActivityKind {
+    Sent,
+    Received,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ActivityItem {
+    pub kind: ActivityKind,
+    pub email: EmailView,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_activity(
+        &self,
+        account: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ActivityItem> {
+        let mut activity: Vec<ActivityItem> = Vec::new();
+        if let Some(sender_vec) = self.senders.get(&account) {
+            for index in sender_vec.iter() {
+                if let Some(mail) = self.emails.get(&index) {
+                    activity.push(ActivityItem {
+                        kind: ActivityKind::Sent,
+                        email: EmailView::new(index, mail),
+                    });
+                }
+            }
+        }
+        if let Some(receiver_vec) = self.receivers.get(&account) {
+            for index in receiver_vec.iter() {
+                if let Some(mail) = self.emails.get(&index) {
+                    activity.push(ActivityItem {
+                        kind: ActivityKind::Received,
+                        email: EmailView::new(index, mail),
+                    });
+                }
+            }
+        }
+        activity.sort_by(|a, b| {
+            a.email
+                .email
+                .timestamp
+                .cmp(&b.email.email.timestamp)
+                .then(a.email.email_id.0.cmp(&b.email.email_id.0))
+        });
+        activity
+            .into_iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    use super::ActivityKind;
+    use near_sdk::testing_env;
+
+    fn send_at(contract: &mut Contract, from: &AccountId, to: &AccountId, timestamp: u64) {
+        testing_env!(context(from, 1).block_timestamp(timestamp).build());
+        contract.send_mail(
+            to.clone(),
+            "Hello".to_string(),
+            "hi".to_string(),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn feed_merges_both_sides_by_time() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        send_at(&mut contract, &alice(), &bob(), 20);
+        send_at(&mut contract, &bob(), &alice(), 10);
+        send_at(&mut contract, &alice(), &bob(), 30);
+
+        let feed = contract.get_activity(alice(), 0, 10);
+        let ids: Vec<u128> = feed.iter().map(|item| item.email.email_id.0).collect();
+        assert_eq!(ids, vec![1, 0, 2]);
+        assert!(matches!(feed[0].kind, ActivityKind::Received));
+        assert!(matches!(feed[1].kind, ActivityKind::Sent));
+
+        let page = contract.get_activity(alice(), 1, 1);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].email.email_id, U128(0));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::*;
+use near_sdk::Balance;
+
+pub const STORAGE_PER_ALIAS: Balance = 10;
+pub const MAX_ALIAS_LEN: usize = 64;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn register_alias(&mut self, alias: String) {
+        assert_one_yocto();
+        assert_valid_alias(&alias);
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.accounts.contains_key(&account_id),
+            "Account not registered"
+        );
+        assert!(
+            !self.alias_to_account.contains_key(&alias),
+            "Alias already registered"
+        );
+
+        let storage_cost = STORAGE_PER_ALIAS * self.byte_cost();
+        let mut vaccount = self.accounts.get(&account_id).unwrap();
+        self.internal_charge_entry(
+            &mut vaccount,
+            &account_id,
+            ChargeKind::Alias,
+            alias.clone(),
+            storage_cost,
+        );
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+        self.alias_to_account.insert(&alias, &account_id);
+    }
+
+    #[payable]
+    pub fn send_mail_to_alias(
+        &mut self,
+        alias: String,
+        title: String,
+        content: String,
+        fee: Option<U128>,
+        options: Option<SendOptions>,
+    ) -> U128 {
+        assert_valid_alias(&alias);
+        let receiver = self.alias_to_account.get(&alias).expect("Alias not found");
+        let sender = env::predecessor_account_id();
+        let mut email = Email::new(sender, receiver, title, content, fee);
+        options.unwrap_or_default().apply(&mut email);
+        U128(self.internal_send_mail(email))
+    }
+
+    pub fn get_alias_account(&self, alias: String) -> Option<AccountId> {
+        self.alias_to_account.get(&alias)
+    }
+}
+
+pub(crate) fn assert_valid_alias(alias: &str) {
+    assert!(!alias.is_empty(), "Alias must not be empty");
+    assert!(
+        alias.len() <= MAX_ALIAS_LEN,
+        "Alias must be at most {} bytes",
+        MAX_ALIAS_LEN
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MAX_ALIAS_LEN;
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn setup_alias() -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&bob(), 1);
+        contract.register_alias("support".to_string());
+        contract
+    }
+
+    #[test]
+    fn mail_to_an_alias_reaches_its_account() {
+        let mut contract = setup_alias();
+        assert_eq!(
+            contract.get_alias_account("support".to_string()),
+            Some(bob())
+        );
+
+        set_context(&alice(), 1);
+        let email_id = contract.send_mail_to_alias(
+            "support".to_string(),
+            "Help".to_string(),
+            "please".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(contract.get_email(email_id).unwrap().receiver, bob());
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+    }
+
+    #[test]
+    fn one_account_holds_several_aliases() {
+        let mut contract = setup_alias();
+        set_context(&bob(), 1);
+        contract.register_alias("billing".to_string());
+        assert_eq!(
+            contract.get_alias_account("billing".to_string()),
+            Some(bob())
+        );
+        assert_eq!(
+            contract.get_alias_account("support".to_string()),
+            Some(bob())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Alias already registered")]
+    fn taken_alias_is_rejected() {
+        let mut contract = setup_alias();
+        set_context(&alice(), 1);
+        contract.register_alias("support".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Alias must not be empty")]
+    fn empty_alias_is_rejected() {
+        let mut contract = setup_alias();
+        set_context(&alice(), 1);
+        contract.register_alias(String::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "Alias must be at most 64 bytes")]
+    fn overlong_alias_is_rejected_before_lookup() {
+        let mut contract = setup_alias();
+        set_context(&alice(), 1);
+        contract.send_mail_to_alias(
+            "a".repeat(MAX_ALIAS_LEN + 1),
+            "Hello".to_string(),
+            "hi".to_string(),
+            None,
+            None,
+        );
+    }
+}
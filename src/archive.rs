@@ -0,0 +1,267 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SweepProgress {
+    pub archived: u64,
+    /// Where to continue; `None` once the sweep has reached newer mail.
+    pub next_index: Option<u64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Moves a mail from the inbox to the archive. Labelled and trashed mail
+    /// leave those folders through `move_mail` and `restore_mail` instead.
+    #[payable]
+    pub fn archive_mail(&mut self, email_id: U128) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let receiver = env::predecessor_account_id();
+        assert!(
+            self.is_receiver_of(&receiver, real_email_id),
+            "Caller is not receiver"
+        );
+        self.internal_archive(&receiver, real_email_id);
+        self.internal_touch(&receiver);
+    }
+
+    #[payable]
+    pub fn unarchive_mail(&mut self, email_id: U128) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let receiver = env::predecessor_account_id();
+        assert!(
+            self.is_archived_by(&receiver, real_email_id),
+            "Email not archived"
+        );
+        self.internal_remove_from_archive(&receiver, real_email_id);
+
+        self.internal_add_to_inbox(&receiver, real_email_id);
+        self.internal_touch(&receiver);
+    }
+
+    /// Archives inbox mail older than `older_than_ts`, examining at most `limit`
+    /// inbox positions from `from_index`. The inbox index is in arrival order,
+    /// so the sweep ends at the first mail that is new enough to keep.
+    #[payable]
+    pub fn sweep_to_archive(
+        &mut self,
+        account: AccountId,
+        older_than_ts: u64,
+        from_index: u64,
+        limit: u64,
+    ) -> SweepProgress {
+        assert_one_yocto();
+        self.assert_batch_size(limit);
+        assert!(
+            env::predecessor_account_id() == account,
+            "Only the account can sweep its inbox"
+        );
+        assert!(
+            self.is_mail_index_migrated(),
+            "Mail index migration pending"
+        );
+        let (order, inbox) = match (
+            self.ordered(&self.receiver_order, &account),
+            self.receivers.get(&account),
+        ) {
+            (Some(order), Some(inbox)) => (order, inbox),
+            _ => {
+                return SweepProgress {
+                    archived: 0,
+                    next_index: None,
+                }
+            }
+        };
+
+        let end = order.len().min(from_index.saturating_add(limit));
+        let mut email_ids: Vec<EmailID> = Vec::new();
+        let mut next_index = (end < order.len()).then_some(end);
+        for index in from_index..end {
+            let email_id = match order.get(index).filter(|email_id| inbox.contains(email_id)) {
+                Some(email_id) => email_id,
+                None => continue,
+            };
+            match self.emails.get(&email_id) {
+                Some(email) if email.timestamp < older_than_ts => email_ids.push(email_id),
+                Some(_) => {
+                    next_index = None;
+                    break;
+                }
+                None => {}
+            }
+        }
+        for email_id in email_ids.iter() {
+            self.internal_archive(&account, *email_id);
+        }
+        self.internal_touch(&account);
+        SweepProgress {
+            archived: email_ids.len() as u64,
+            next_index,
+        }
+    }
+
+    #[payable]
+    pub fn set_auto_archive_on_read(&mut self, auto_archive_on_read: bool) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.auto_archive_on_read = auto_archive_on_read;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+    }
+
+    pub fn get_auto_archive_on_read(&self, account: AccountId) -> bool {
+        self.accounts
+            .get(&account)
+            .map(|vaccount| vaccount.auto_archive_on_read)
+            .unwrap_or(false)
+    }
+
+    pub fn get_mail_archived(&self, receiver: AccountId) -> Vec<Email> {
+        let mut email_vec: Vec<Email> = Vec::new();
+        if let Some(archived_vec) = self.archived.get(&receiver) {
+            for index in archived_vec.iter() {
+                if let Some(mail) = self.emails.get(&index) {
+                    email_vec.push(EmailView::new(index, mail).email);
+                }
+            }
+        }
+        email_vec
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_archive(&mut self, receiver: &AccountId, email_id: EmailID) {
+        self.internal_remove_from_inbox(receiver, email_id);
+        let mut archived_vec = self.archived.get(receiver).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountArchived {
+                account_id: receiver.clone(),
+            })
+        });
+        archived_vec.insert(&email_id);
+        self.archived.insert(receiver, &archived_vec);
+    }
+
+    pub(crate) fn internal_remove_from_archive(&mut self, receiver: &AccountId, email_id: EmailID) {
+        if let Some(mut archived_vec) = self.archived.get(receiver) {
+            if archived_vec.remove(&email_id) {
+                if archived_vec.is_empty() {
+                    self.archived.remove(receiver);
+                } else {
+                    self.archived.insert(receiver, &archived_vec);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn is_archived_by(&self, account_id: &AccountId, email_id: EmailID) -> bool {
+        self.archived
+            .get(account_id)
+            .map(|archived_vec| archived_vec.contains(&email_id))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::testing_env;
+
+    fn send_at(contract: &mut Contract, timestamp: u64) -> EmailID {
+        testing_env!(context(&alice(), 1).block_timestamp(timestamp).build());
+        contract
+            .send_mail(bob(), "Hello".to_string(), "hi".to_string(), None, None)
+            .0
+    }
+
+    fn setup_inbox() -> (Contract, Vec<EmailID>) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_ids = (1..=4).map(|t| send_at(&mut contract, t * 100)).collect();
+        set_context(&bob(), 1);
+        (contract, email_ids)
+    }
+
+    #[test]
+    fn sweep_archives_only_mail_older_than_cutoff() {
+        let (mut contract, email_ids) = setup_inbox();
+        let progress = contract.sweep_to_archive(bob(), 300, 0, 10);
+        assert_eq!(progress.archived, 2);
+        assert_eq!(progress.next_index, None);
+        assert!(contract.is_archived_by(&bob(), email_ids[0]));
+        assert!(contract.is_archived_by(&bob(), email_ids[1]));
+        assert!(contract.is_receiver_of(&bob(), email_ids[2]));
+        assert!(contract.is_receiver_of(&bob(), email_ids[3]));
+    }
+
+    #[test]
+    fn sweep_examines_at_most_limit_positions() {
+        let (mut contract, email_ids) = setup_inbox();
+        let first = contract.sweep_to_archive(bob(), u64::MAX, 0, 3);
+        assert_eq!(first.archived, 3);
+        assert_eq!(first.next_index, Some(3));
+        assert!(contract.is_receiver_of(&bob(), email_ids[3]));
+
+        // Positions already archived are tombstones and are skipped on resume.
+        let second = contract.sweep_to_archive(bob(), u64::MAX, 0, 3);
+        assert_eq!(second.archived, 0);
+        assert_eq!(second.next_index, Some(3));
+        let last = contract.sweep_to_archive(bob(), u64::MAX, 3, 3);
+        assert_eq!(last.archived, 1);
+        assert_eq!(last.next_index, None);
+    }
+
+    fn archived_mail() -> (Contract, EmailID) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+        set_context(&bob(), 1);
+        contract.archive_mail(U128(email_id));
+        (contract, email_id)
+    }
+
+    #[test]
+    fn reading_with_auto_archive_moves_mail_to_the_archive() {
+        let (mut contract, _) = setup_inbox();
+        set_context(&bob(), 1);
+        contract.set_auto_archive_on_read(true);
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(0));
+        assert!(!contract.is_receiver_of(&bob(), 0));
+        assert!(contract.is_archived_by(&bob(), 0));
+
+        set_context(&bob(), 1);
+        contract.set_auto_archive_on_read(false);
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(1));
+        assert!(contract.is_receiver_of(&bob(), 1));
+        assert!(!contract.is_archived_by(&bob(), 1));
+    }
+
+    #[test]
+    fn archived_mail_can_still_be_read_replied_to_and_reported() {
+        let (mut contract, email_id) = archived_mail();
+        set_context(&bob(), 1);
+        contract.set_auto_archive_on_read(true);
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(email_id));
+        assert!(contract.emails.get(&email_id).unwrap().read_at.is_some());
+        assert!(contract.is_archived_by(&bob(), email_id));
+
+        set_context(&bob(), 1);
+        contract.reply_mail(U128(email_id), "ok".to_string(), None, false);
+        assert_eq!(contract.get_mail_receive_num(alice()), 1);
+
+        set_context(&bob(), 1);
+        contract.report_spam(U128(email_id));
+        assert!(contract.is_reported(U128(email_id)));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Balance;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageAudit {
+    pub recorded_used: U128,
+    pub recomputed_used: U128,
+    pub drift: U128,
+    /// Per-kind charges as booked in the ledger.
+    pub breakdown: StorageCharges,
+    /// Per-kind charges found by walking what the account actually has stored.
+    pub recomputed: StorageCharges,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Reconciles `used` against what the account has stored: its mail, the
+    /// entries under its own key and the items it is linked to elsewhere.
+    /// Owner-only, so it is a change method rather than a view.
+    #[payable]
+    pub fn audit_storage(&mut self, account: AccountId) -> Option<StorageAudit> {
+        assert_one_yocto();
+        self.assert_owner();
+        let vaccount = self.accounts.get(&account)?;
+        let recomputed = self.internal_recompute_charges(&account, &vaccount);
+        let recomputed_used = recomputed.total();
+        Some(StorageAudit {
+            recorded_used: U128(vaccount.used),
+            recomputed_used: U128(recomputed_used),
+            drift: U128(vaccount.used.abs_diff(recomputed_used)),
+            breakdown: vaccount.charges,
+            recomputed,
+        })
+    }
+}
+
+impl Contract {
+    /// Rebuilds the per-kind charges from stored state. The registration charge
+    /// and reserved slots have no item of their own, so those two are taken from
+    /// the ledger, with the slot count read from the account.
+    fn internal_recompute_charges(
+        &self,
+        account_id: &AccountId,
+        vaccount: &VAccount,
+    ) -> StorageCharges {
+        let mut charges = StorageCharges {
+            account: vaccount.charges.account,
+            reservations: Charge {
+                count: vaccount.reserved_capacity,
+                amount: vaccount.charges.reservations.amount,
+            },
+            ..Default::default()
+        };
+        if let Some(sender_vec) = self.senders.get(account_id) {
+            for email_id in sender_vec.iter() {
+                if let Some(email) = self.emails.get(&email_id) {
+                    if email.storage_payer.is_none() {
+                        tally(&mut charges, ChargeKind::Mail, email.storage_charged.0);
+                    }
+                }
+            }
+        }
+
+        let entry = |kind: ChargeKind, item: String| {
+            self.entry_charges
+                .get(&(kind, account_id.clone(), item))
+                .unwrap_or(0)
+        };
+        if let Some(contacts) = self.contacts.get(account_id) {
+            for contact in contacts.iter() {
+                tally(
+                    &mut charges,
+                    ChargeKind::Contact,
+                    entry(ChargeKind::Contact, contact.to_string()),
+                );
+            }
+        }
+        if let Some(labels) = self.label_names.get(account_id) {
+            for label in labels.iter() {
+                tally(
+                    &mut charges,
+                    ChargeKind::Label,
+                    entry(ChargeKind::Label, label),
+                );
+            }
+        }
+        if let Some(muted) = self.muted_threads.get(account_id) {
+            for root_id in muted.iter() {
+                tally(
+                    &mut charges,
+                    ChargeKind::MutedThread,
+                    entry(ChargeKind::MutedThread, root_id.to_string()),
+                );
+            }
+        }
+        if let Some(blocked) = self.blocklists.get(account_id) {
+            for sender in blocked.iter() {
+                tally(
+                    &mut charges,
+                    ChargeKind::BlockEntry,
+                    entry(ChargeKind::BlockEntry, sender.to_string()),
+                );
+            }
+        }
+        if let Some(allowed) = self.allowlists.get(account_id) {
+            for sender in allowed.iter() {
+                tally(
+                    &mut charges,
+                    ChargeKind::AllowEntry,
+                    entry(ChargeKind::AllowEntry, sender.to_string()),
+                );
+            }
+        }
+        if let Some(overrides) = self.fee_overrides.get(account_id) {
+            for sender in overrides.keys() {
+                tally(
+                    &mut charges,
+                    ChargeKind::FeeOverride,
+                    entry(ChargeKind::FeeOverride, sender.to_string()),
+                );
+            }
+        }
+        for amount in self.scheduled_charges(account_id) {
+            tally(&mut charges, ChargeKind::Scheduled, amount);
+        }
+        if self.pending_broadcasts.contains_key(account_id) {
+            tally(
+                &mut charges,
+                ChargeKind::Broadcast,
+                entry(ChargeKind::Broadcast, account_id.to_string()),
+            );
+        }
+
+        if let Some(links) = self.account_links.get(account_id) {
+            for (kind, item) in links.iter() {
+                let amount = match kind {
+                    ChargeKind::Mail => item
+                        .parse::<EmailID>()
+                        .ok()
+                        .and_then(|email_id| self.emails.get(&email_id))
+                        .map(|email| email.storage_charged.0),
+                    ChargeKind::Request => item
+                        .parse::<RequestID>()
+                        .ok()
+                        .and_then(|request_id| self.send_requests.get(&request_id))
+                        .map(|request| request.storage_charged.0),
+                    ChargeKind::Tombstone => self.tombstone_charge(&item),
+                    _ => self.entry_charges.get(&(kind, account_id.clone(), item)),
+                };
+                if let Some(amount) = amount {
+                    tally(&mut charges, kind, amount);
+                }
+            }
+        }
+        charges
+    }
+}
+
+fn tally(charges: &mut StorageCharges, kind: ChargeKind, amount: Balance) {
+    let charge = charges.get_mut(kind);
+    charge.count += 1;
+    charge.amount.0 += amount;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StorageAudit;
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn audit(contract: &mut Contract, account: AccountId) -> StorageAudit {
+        set_context(&owner(), 1);
+        contract.audit_storage(account).unwrap()
+    }
+
+    #[test]
+    fn audit_balances_after_every_kind_of_charge() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        send(&mut contract, &alice(), &bob(), "hi");
+        set_context(&alice(), 1);
+        contract.add_contact(bob());
+        set_context(&alice(), 1);
+        contract.register_alias("al".to_string());
+        set_context(&alice(), 1);
+        contract.create_label("work".to_string());
+        set_context(&alice(), 1);
+        contract.subscribe(bob());
+        set_context(&alice(), 1);
+        contract.request_to_send(bob(), "Hey".to_string(), "can I write?".to_string());
+
+        let report = audit(&mut contract, alice());
+        assert_eq!(report.drift, U128(0));
+        assert_eq!(report.breakdown.mail.count, 1);
+        assert_eq!(report.breakdown.aliases.count, 1);
+        assert_eq!(report.breakdown.requests.count, 1);
+        assert_eq!(report.recomputed.mail.count, 1);
+        assert_eq!(report.recomputed.contacts.count, 1);
+        assert_eq!(report.recomputed.labels.count, 1);
+        assert_eq!(report.recomputed.subscriptions.count, 1);
+        assert_eq!(report.recomputed.requests.count, 1);
+    }
+
+    #[test]
+    fn audit_finds_mail_the_sender_hid_but_still_pays_for() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+        set_context(&alice(), 1);
+        contract.hide_mail(U128(email_id));
+
+        let report = audit(&mut contract, alice());
+        assert_eq!(report.drift, U128(0));
+        assert_eq!(report.recomputed.mail.count, 1);
+
+        // Once the receiver drops it too, the charge and the link both go.
+        set_context(&bob(), 1);
+        contract.hide_mail(U128(email_id));
+        let report = audit(&mut contract, alice());
+        assert_eq!(report.drift, U128(0));
+        assert_eq!(report.recomputed.mail.count, 0);
+        assert!(contract.account_links.get(&alice()).is_none());
+    }
+
+    #[test]
+    fn audit_reports_charges_left_behind_by_lost_state() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+        set_context(&alice(), 1);
+        contract.add_contact(bob());
+        let mail_charge = contract.emails.get(&email_id).unwrap().storage_charged.0;
+        let contact_charge = contract
+            .entry_charges
+            .get(&(ChargeKind::Contact, alice(), bob().to_string()))
+            .unwrap();
+
+        // Drop the stored items without releasing what they were charged.
+        contract.emails.remove(&email_id);
+        let mut contacts = contract.contacts.get(&alice()).unwrap();
+        contacts.remove(&bob());
+        contract.contacts.insert(&alice(), &contacts);
+
+        let report = audit(&mut contract, alice());
+        assert_eq!(report.drift, U128(mail_charge + contact_charge));
+        assert_eq!(report.breakdown.mail.count, 1);
+        assert_eq!(report.recomputed.mail.count, 0);
+        assert_eq!(report.recomputed.contacts.count, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller is not owner")]
+    fn audit_is_owner_only() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        set_context(&alice(), 1);
+        contract.audit_storage(alice());
+    }
+
+    #[test]
+    fn unregistered_accounts_read_as_empty() {
+        let mut contract = setup();
+        set_context(&owner(), 1);
+        assert!(contract.audit_storage(carol()).is_none());
+
+        assert!(contract.get_mail_receive(carol()).is_empty());
+        assert!(contract.get_mail_send(carol()).is_empty());
+        assert_eq!(contract.get_mail_receive_num(carol()), 0);
+        assert!(contract
+            .get_mail_receive_paginated(carol(), 0, 10)
+            .is_empty());
+        assert!(contract
+            .get_mail_receive_sorted(carol(), None, 0, 10)
+            .is_empty());
+        assert_eq!(contract.get_unread_count(carol()), 0);
+        assert!(contract.get_unread_by_sender(carol()).is_empty());
+        assert!(contract.storage_balance_of(carol()).is_none());
+        assert_eq!(contract.get_fees_earned(carol()), U128(0));
+    }
+}
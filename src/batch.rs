@@ -0,0 +1,113 @@
+use crate::*;
+
+pub const DEFAULT_MAX_BATCH_SIZE: u64 = 100;
+
+impl Contract {
+    pub(crate) fn assert_batch_size(&self, size: u64) {
+        require(size <= self.max_batch_size, ContractError::BatchTooLarge);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::ImportEntry;
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn assert_rejects_batch(contract: &mut Contract, call: impl FnOnce(&mut Contract)) {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| call(contract)));
+        let message = result
+            .err()
+            .and_then(|payload| payload.downcast::<String>().ok())
+            .expect("oversized batch was accepted");
+        assert!(message.starts_with("E023"), "{}", message);
+    }
+
+    #[test]
+    fn every_bulk_method_respects_the_cap() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_max_batch_size(1);
+        let email_ids = vec![U128(0), U128(1)];
+
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&alice(), 2);
+            contract.send_mail_multi(
+                vec![bob(), owner()],
+                "Hello".to_string(),
+                "content".to_string(),
+                None,
+                None,
+            );
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&bob(), 1);
+            contract.claim_fees(email_ids.clone());
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&bob(), 1);
+            contract.report_spam_many(email_ids.clone());
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&owner(), 1);
+            let entries = (0..2)
+                .map(|_| ImportEntry {
+                    sender: alice(),
+                    receiver: bob(),
+                    email: Email::new(alice(), bob(), "Hi".to_string(), "old".to_string(), None),
+                })
+                .collect();
+            contract.import_emails(entries);
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&bob(), 1);
+            contract.sweep_to_archive(bob(), 0, 0, 2);
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&bob(), 1);
+            contract.restore_all(2);
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&bob(), 1);
+            contract.empty_trash(2);
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&bob(), 1);
+            contract.apply_auto_purge(bob(), None, 2);
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&bob(), 1);
+            contract.apply_retention(bob(), None, 2);
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&bob(), 1);
+            contract.purge_expired(bob(), None, 2);
+        });
+        assert_rejects_batch(&mut contract, |contract| {
+            set_context(&bob(), 1);
+            contract.migrate_mail_index(2);
+        });
+    }
+
+    #[test]
+    fn batch_at_the_cap_is_accepted() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_max_batch_size(1);
+
+        set_context(&alice(), 1);
+        let email_ids = contract.send_mail_multi(
+            vec![bob()],
+            "Hello".to_string(),
+            "content".to_string(),
+            None,
+            None,
+        );
+        set_context(&bob(), 1);
+        assert!(contract.report_spam_many(email_ids).is_empty());
+    }
+}
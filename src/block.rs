@@ -0,0 +1,444 @@
+use crate::*;
+use near_sdk::Balance;
+
+pub const STORAGE_PER_LIST_ENTRY: Balance = 10;
+/// How many recent allowed senders an allowlist-only account remembers.
+pub const RECENT_ALLOWED_CAP: usize = 4;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn block_sender(&mut self, sender: AccountId) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        assert!(
+            !self.is_blocked_by(&account_id, &sender),
+            "Sender already blocked"
+        );
+        self.internal_block(&account_id, &sender);
+    }
+
+    #[payable]
+    pub fn unblock_sender(&mut self, sender: AccountId) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut blocklist = self
+            .blocklists
+            .get(&account_id)
+            .expect("Sender not blocked");
+        assert!(blocklist.remove(&sender), "Sender not blocked");
+        self.blocklists.insert(&account_id, &blocklist);
+        self.internal_remove_blocked_by(&sender, &account_id);
+        self.internal_refund_list_entry(&account_id, ChargeKind::BlockEntry, &sender);
+    }
+
+    #[payable]
+    pub fn allow_sender(&mut self, sender: AccountId) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut allowlist = self.allowlists.get(&account_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountAllowlist {
+                account_id: account_id.clone(),
+            })
+        });
+        assert!(!allowlist.contains(&sender), "Sender already allowed");
+        self.internal_charge_list_entry(&account_id, ChargeKind::AllowEntry, &sender);
+        allowlist.insert(&sender);
+        self.allowlists.insert(&account_id, &allowlist);
+    }
+
+    #[payable]
+    pub fn disallow_sender(&mut self, sender: AccountId) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut allowlist = self
+            .allowlists
+            .get(&account_id)
+            .expect("Sender not allowed");
+        assert!(allowlist.remove(&sender), "Sender not allowed");
+        self.allowlists.insert(&account_id, &allowlist);
+        self.internal_forget_allowed(&account_id, &sender);
+        self.internal_refund_list_entry(&account_id, ChargeKind::AllowEntry, &sender);
+    }
+
+    #[payable]
+    pub fn set_allowlist_only(&mut self, enabled: bool) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.allowlist_only = enabled;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+        if !enabled {
+            self.recent_allowed.remove(&account_id);
+        }
+    }
+
+    #[payable]
+    pub fn freeze_mailbox(&mut self) {
+        assert_one_yocto();
+        self.internal_set_frozen(true);
+    }
+
+    #[payable]
+    pub fn unfreeze_mailbox(&mut self) {
+        assert_one_yocto();
+        self.internal_set_frozen(false);
+    }
+
+    pub fn is_frozen(&self, account: AccountId) -> bool {
+        self.accounts
+            .get(&account)
+            .map(|vaccount| vaccount.frozen)
+            .unwrap_or(false)
+    }
+
+    pub fn get_allowlist_only(&self, account: AccountId) -> bool {
+        self.accounts
+            .get(&account)
+            .map(|vaccount| vaccount.allowlist_only)
+            .unwrap_or(false)
+    }
+
+    pub fn get_blocklist(&self, account: AccountId, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.blocklists
+            .get(&account)
+            .map(|blocklist| paginate_set(&blocklist, from_index, limit))
+            .unwrap_or_default()
+    }
+
+    pub fn get_blocked_by(
+        &self,
+        account: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<AccountId> {
+        self.blocked_by
+            .get(&account)
+            .map(|blockers| paginate_set(&blockers, from_index, limit))
+            .unwrap_or_default()
+    }
+
+    pub fn get_allowlist(&self, account: AccountId, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.allowlists
+            .get(&account)
+            .map(|allowlist| paginate_set(&allowlist, from_index, limit))
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    pub(crate) fn is_blocked_by(&self, receiver: &AccountId, sender: &AccountId) -> bool {
+        self.blocklists
+            .get(receiver)
+            .map(|blocklist| blocklist.contains(sender))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn is_allowed_by(&self, receiver: &AccountId, sender: &AccountId) -> bool {
+        self.allowlists
+            .get(receiver)
+            .map(|allowlist| allowlist.contains(sender))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn internal_block(&mut self, account_id: &AccountId, sender: &AccountId) {
+        let mut blocklist = self.blocklists.get(account_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountBlocklist {
+                account_id: account_id.clone(),
+            })
+        });
+        self.internal_charge_list_entry(account_id, ChargeKind::BlockEntry, sender);
+        blocklist.insert(sender);
+        self.blocklists.insert(account_id, &blocklist);
+        let mut blockers = self.blocked_by.get(sender).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountBlockedBy {
+                account_id: sender.clone(),
+            })
+        });
+        blockers.insert(account_id);
+        self.blocked_by.insert(sender, &blockers);
+        emit_event(
+            "sender_blocked",
+            json!({
+                "account_id": account_id,
+                "sender": sender,
+            }),
+        );
+    }
+
+    pub(crate) fn internal_remove_blocked_by(
+        &mut self,
+        sender: &AccountId,
+        account_id: &AccountId,
+    ) {
+        if let Some(mut blockers) = self.blocked_by.get(sender) {
+            blockers.remove(account_id);
+            if blockers.is_empty() {
+                self.blocked_by.remove(sender);
+            } else {
+                self.blocked_by.insert(sender, &blockers);
+            }
+        }
+    }
+
+    pub(crate) fn assert_accepts_sender(&self, receiver: &AccountId, sender: &AccountId) {
+        if let Err(error) = self.check_accepts_sender(receiver, sender) {
+            error.panic();
+        }
+    }
+
+    pub(crate) fn check_accepts_sender(
+        &self,
+        receiver: &AccountId,
+        sender: &AccountId,
+    ) -> Result<(), ContractError> {
+        ensure(
+            !self.is_frozen(receiver.clone()),
+            ContractError::MailboxFrozen,
+        )?;
+        ensure(
+            !self.is_blocked_by(receiver, sender),
+            ContractError::SenderBlocked,
+        )?;
+        ensure(
+            !self.get_allowlist_only(receiver.clone()) || self.is_allowed_sender(receiver, sender),
+            ContractError::SenderNotAllowed,
+        )
+    }
+
+    /// Allowlist membership, answered from the receiver's recent senders when
+    /// the sender is one of them so repeat senders skip the allowlist itself.
+    pub(crate) fn is_allowed_sender(&self, receiver: &AccountId, sender: &AccountId) -> bool {
+        self.recent_allowed
+            .get(receiver)
+            .is_some_and(|recent| recent.contains(sender))
+            || self.is_allowed_by(receiver, sender)
+    }
+
+    /// Moves `sender` to the front of the receiver's recent senders, keeping
+    /// at most `RECENT_ALLOWED_CAP` of them.
+    pub(crate) fn internal_note_allowed(&mut self, receiver: &AccountId, sender: &AccountId) {
+        let mut recent = self.recent_allowed.get(receiver).unwrap_or_default();
+        if recent.first() == Some(sender) {
+            return;
+        }
+        recent.retain(|cached| cached != sender);
+        recent.insert(0, sender.clone());
+        recent.truncate(RECENT_ALLOWED_CAP);
+        self.recent_allowed.insert(receiver, &recent);
+    }
+
+    /// Drops `sender` from the receiver's recent senders, so removing it from
+    /// the allowlist takes effect on the next send.
+    pub(crate) fn internal_forget_allowed(&mut self, receiver: &AccountId, sender: &AccountId) {
+        if let Some(mut recent) = self.recent_allowed.get(receiver) {
+            recent.retain(|cached| cached != sender);
+            if recent.is_empty() {
+                self.recent_allowed.remove(receiver);
+            } else {
+                self.recent_allowed.insert(receiver, &recent);
+            }
+        }
+    }
+
+    fn internal_set_frozen(&mut self, frozen: bool) {
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.frozen = frozen;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+        emit_event(
+            if frozen {
+                "mailbox_frozen"
+            } else {
+                "mailbox_unfrozen"
+            },
+            json!({ "account_id": account_id }),
+        );
+    }
+
+    pub(crate) fn internal_charge_list_entry(
+        &mut self,
+        account_id: &AccountId,
+        kind: ChargeKind,
+        entry: &AccountId,
+    ) {
+        let mut vaccount = self
+            .accounts
+            .get(account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        let storage_cost = STORAGE_PER_LIST_ENTRY * self.byte_cost();
+        self.internal_charge_entry(
+            &mut vaccount,
+            account_id,
+            kind,
+            entry.to_string(),
+            storage_cost,
+        );
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(account_id, &vaccount);
+    }
+
+    pub(crate) fn internal_refund_list_entry(
+        &mut self,
+        account_id: &AccountId,
+        kind: ChargeKind,
+        entry: &AccountId,
+    ) {
+        if let Some(mut vaccount) = self.accounts.get(account_id) {
+            self.internal_release_entry(&mut vaccount, account_id, kind, entry.to_string());
+            vaccount.last_active = env::block_timestamp();
+            self.accounts.insert(account_id, &vaccount);
+        }
+    }
+}
+
+fn paginate_set(set: &UnorderedSet<AccountId>, from_index: u64, limit: u64) -> Vec<AccountId> {
+    set.as_vector()
+        .iter()
+        .skip(from_index as usize)
+        .take(limit as usize)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RECENT_ALLOWED_CAP;
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::test_utils::accounts;
+
+    fn setup_allowlist(senders: &[AccountId]) -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &bob());
+        set_context(&bob(), 1);
+        contract.set_allowlist_only(true);
+        for sender in senders {
+            register(&mut contract, sender);
+            set_context(&bob(), 1);
+            contract.allow_sender(sender.clone());
+        }
+        contract
+    }
+
+    fn recent(contract: &Contract) -> Vec<AccountId> {
+        contract.recent_allowed.get(&bob()).unwrap_or_default()
+    }
+
+    #[test]
+    fn delivered_senders_are_cached_most_recent_first_up_to_the_cap() {
+        let senders: Vec<AccountId> = [0, 1, 3, 4, 5].into_iter().map(accounts).collect();
+        let mut contract = setup_allowlist(&senders);
+        for sender in senders.iter() {
+            send(&mut contract, sender, &bob(), "hi");
+        }
+        send(&mut contract, &senders[2], &bob(), "again");
+
+        let cached = recent(&contract);
+        assert_eq!(cached.len(), RECENT_ALLOWED_CAP);
+        assert_eq!(cached[0], senders[2]);
+        assert!(!cached.contains(&senders[0]));
+        assert!(contract.is_allowed_sender(&bob(), &senders[0]));
+    }
+
+    #[test]
+    #[should_panic(expected = "E015")]
+    fn cached_sender_is_rejected_once_disallowed() {
+        let mut contract = setup_allowlist(&[alice()]);
+        send(&mut contract, &alice(), &bob(), "hi");
+        assert_eq!(recent(&contract), vec![alice()]);
+
+        set_context(&bob(), 1);
+        contract.disallow_sender(alice());
+        assert!(recent(&contract).is_empty());
+        send(&mut contract, &alice(), &bob(), "hi again");
+    }
+
+    #[test]
+    fn leaving_allowlist_only_clears_the_cache() {
+        let mut contract = setup_allowlist(&[alice()]);
+        send(&mut contract, &alice(), &bob(), "hi");
+        set_context(&bob(), 1);
+        contract.set_allowlist_only(false);
+        assert!(contract.recent_allowed.get(&bob()).is_none());
+    }
+
+    #[test]
+    fn block_and_allow_lists_page_in_insertion_order() {
+        let mut contract = setup();
+        register(&mut contract, &bob());
+        for sender in [alice(), carol(), owner()] {
+            set_context(&bob(), 1);
+            contract.block_sender(sender.clone());
+            set_context(&bob(), 1);
+            contract.allow_sender(sender);
+        }
+
+        assert_eq!(contract.get_blocklist(bob(), 0, 2), vec![alice(), carol()]);
+        assert_eq!(contract.get_blocklist(bob(), 2, 5), vec![owner()]);
+        assert!(contract.get_blocklist(bob(), 3, 5).is_empty());
+        assert_eq!(contract.get_allowlist(bob(), 1, 1), vec![carol()]);
+        assert!(contract.get_allowlist(alice(), 0, 5).is_empty());
+    }
+
+    #[test]
+    fn blocked_by_lists_every_blocker_until_unblocked() {
+        let mut contract = setup();
+        for blocker in [bob(), carol()] {
+            register(&mut contract, &blocker);
+            set_context(&blocker, 1);
+            contract.block_sender(alice());
+        }
+        assert_eq!(
+            contract.get_blocked_by(alice(), 0, 10),
+            vec![bob(), carol()]
+        );
+        assert_eq!(contract.get_blocked_by(alice(), 1, 10), vec![carol()]);
+
+        set_context(&bob(), 1);
+        contract.unblock_sender(alice());
+        assert_eq!(contract.get_blocked_by(alice(), 0, 10), vec![carol()]);
+        assert!(contract.get_blocked_by(bob(), 0, 10).is_empty());
+    }
+
+    fn setup_frozen() -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&bob(), 1);
+        contract.freeze_mailbox();
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "E022")]
+    fn frozen_mailbox_rejects_new_mail() {
+        let mut contract = setup_frozen();
+        assert!(contract.is_frozen(bob()));
+        send(&mut contract, &alice(), &bob(), "hi");
+    }
+
+    #[test]
+    fn unfrozen_mailbox_receives_again() {
+        let mut contract = setup_frozen();
+        // A frozen receiver can still send.
+        send(&mut contract, &bob(), &alice(), "out");
+        set_context(&bob(), 1);
+        contract.unfreeze_mailbox();
+        assert!(!contract.is_frozen(bob()));
+        assert_eq!(
+            events("mailbox_unfrozen"),
+            vec![json!({ "account_id": bob() })]
+        );
+
+        send(&mut contract, &alice(), &bob(), "hi");
+        assert_eq!(contract.get_mail_receive(bob()).len(), 1);
+    }
+}
@@ -0,0 +1,314 @@
+use crate::*;
+use near_sdk::Balance;
+
+pub const STORAGE_PER_SUBSCRIPTION: Balance = 10;
+pub const DEFAULT_MAX_RECIPIENTS: u64 = 50;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn subscribe(&mut self, publisher: AccountId) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.accounts.contains_key(&account_id),
+            "Account not registered"
+        );
+
+        let mut subscriber_set = self.subscribers.get(&publisher).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::PublisherSubscribers {
+                account_id: publisher.clone(),
+            })
+        });
+        assert!(!subscriber_set.contains(&account_id), "Already subscribed");
+
+        let storage_cost = STORAGE_PER_SUBSCRIPTION * self.byte_cost();
+        let mut vaccount = self.accounts.get(&account_id).unwrap();
+        self.internal_charge_entry(
+            &mut vaccount,
+            &account_id,
+            ChargeKind::Subscription,
+            publisher.to_string(),
+            storage_cost,
+        );
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+
+        subscriber_set.insert(&account_id);
+        self.subscribers.insert(&publisher, &subscriber_set);
+    }
+
+    #[payable]
+    pub fn unsubscribe(&mut self, publisher: AccountId) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut subscriber_set = self.subscribers.get(&publisher).expect("Not subscribed");
+        assert!(subscriber_set.remove(&account_id), "Not subscribed");
+        self.subscribers.insert(&publisher, &subscriber_set);
+
+        if let Some(mut vaccount) = self.accounts.get(&account_id) {
+            self.internal_release_entry(
+                &mut vaccount,
+                &account_id,
+                ChargeKind::Subscription,
+                publisher.to_string(),
+            );
+            vaccount.last_active = env::block_timestamp();
+            self.accounts.insert(&account_id, &vaccount);
+        }
+    }
+
+    pub fn get_subscribers(&self, publisher: AccountId) -> Vec<AccountId> {
+        if let Some(subscriber_set) = self.subscribers.get(&publisher) {
+            return subscriber_set.to_vec();
+        }
+        Vec::new()
+    }
+
+    pub fn get_subscriber_num(&self, publisher: AccountId) -> u64 {
+        if let Some(subscriber_set) = self.subscribers.get(&publisher) {
+            return subscriber_set.len();
+        }
+        0
+    }
+
+    /// Starts fanning a message out to the caller's subscribers. Delivers the
+    /// first page of up to `max_recipients` and keeps the rest for
+    /// `continue_broadcast`, so no subscriber count can make it run out of gas.
+    /// Returns whether every subscriber has been reached.
+    #[payable]
+    pub fn broadcast(&mut self, title: String, content: String) -> bool {
+        assert_one_yocto();
+        let title = sanitize_title(&title);
+        let content = sanitize_content(&content);
+        self.assert_not_empty(&title, &content);
+        check_lengths(&title, &content).unwrap_or_else(|error| error.panic());
+        let sender = env::predecessor_account_id();
+        require(
+            self.accounts.contains_key(&sender),
+            ContractError::AccountNotRegistered,
+        );
+        require(
+            !self.is_spam_suspended(&sender),
+            ContractError::AccountSuspended,
+        );
+        self.assert_cooldown_elapsed(&sender);
+        assert!(
+            !self.pending_broadcasts.contains_key(&sender),
+            "Broadcast in progress"
+        );
+
+        let pending = PendingBroadcast {
+            title,
+            content,
+            timestamp: env::block_timestamp(),
+            next_index: 0,
+        };
+        let record_cost = self.mail_storage_cost(pending.len());
+        let mut vaccount = self.accounts.get(&sender).unwrap();
+        self.internal_charge_entry(
+            &mut vaccount,
+            &sender,
+            ChargeKind::Broadcast,
+            sender.to_string(),
+            record_cost,
+        );
+        self.accounts.insert(&sender, &vaccount);
+        self.internal_broadcast_page(&sender, pending, self.max_recipients)
+    }
+
+    /// Delivers the next `limit` subscribers of the caller's broadcast.
+    #[payable]
+    pub fn continue_broadcast(&mut self, limit: u64) -> bool {
+        assert_one_yocto();
+        self.assert_recipient_count(limit);
+        let sender = env::predecessor_account_id();
+        let pending = self
+            .pending_broadcasts
+            .get(&sender)
+            .expect("No broadcast in progress");
+        self.internal_broadcast_page(&sender, pending, limit)
+    }
+
+    pub fn is_broadcast_pending(&self, publisher: AccountId) -> bool {
+        self.pending_broadcasts.contains_key(&publisher)
+    }
+}
+
+/// A broadcast still being fanned out. Subscribers are walked by position in
+/// the subscriber set, so someone who leaves mid-broadcast can shift a later
+/// subscriber into the delivered range.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PendingBroadcast {
+    pub title: String,
+    pub content: String,
+    pub timestamp: u64,
+    pub next_index: u64,
+}
+
+impl PendingBroadcast {
+    fn len(&self) -> u64 {
+        (self.title.len() + self.content.len()) as u64
+    }
+}
+
+impl Contract {
+    fn internal_broadcast_page(
+        &mut self,
+        sender: &AccountId,
+        mut pending: PendingBroadcast,
+        limit: u64,
+    ) -> bool {
+        let subscriber_set = self.subscribers.get(sender);
+        let total = subscriber_set.as_ref().map_or(0, |set| set.len());
+        let end = total.min(pending.next_index.saturating_add(limit));
+        let subscribers: Vec<AccountId> = match subscriber_set {
+            Some(subscriber_set) => (pending.next_index..end)
+                .filter_map(|index| subscriber_set.as_vector().get(index))
+                .filter(|subscriber| !self.is_frozen(subscriber.clone()))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let mail_cost = self.mail_storage_cost(pending.len());
+        let storage_cost = mail_cost * subscribers.len() as Balance;
+        let mut vaccount = self.accounts.get(sender).unwrap();
+        require(
+            vaccount.deposit - vaccount.used >= storage_cost,
+            ContractError::NotDepositEnough,
+        );
+        vaccount.charge_many(ChargeKind::Mail, subscribers.len() as u64, storage_cost);
+        vaccount.sent_count += subscribers.len() as u64;
+        vaccount.last_sent_at = env::block_timestamp();
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(sender, &vaccount);
+
+        for subscriber in subscribers {
+            let mut email = Email::new(
+                sender.clone(),
+                subscriber.clone(),
+                pending.title.clone(),
+                pending.content.clone(),
+                None,
+            );
+            email.timestamp = pending.timestamp;
+            email.storage_charged = U128(mail_cost);
+            let email_id = self.internal_insert_email(&email);
+            self.internal_notify_new_mail(email_id, sender, &subscriber);
+        }
+
+        pending.next_index = end;
+        let done = end >= total;
+        if done {
+            self.pending_broadcasts.remove(sender);
+            let mut vaccount = self.accounts.get(sender).unwrap();
+            self.internal_release_entry(
+                &mut vaccount,
+                sender,
+                ChargeKind::Broadcast,
+                sender.to_string(),
+            );
+            self.accounts.insert(sender, &vaccount);
+        } else {
+            self.pending_broadcasts.insert(sender, &pending);
+        }
+        done
+    }
+
+    pub(crate) fn assert_recipient_count(&self, count: u64) {
+        assert!(count <= self.max_recipients, "Too many recipients");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn setup_subscribers() -> Contract {
+        let mut contract = setup();
+        for account_id in [alice(), bob(), carol()] {
+            register(&mut contract, &account_id);
+        }
+        for subscriber in [bob(), carol()] {
+            set_context(&subscriber, 1);
+            contract.subscribe(alice());
+        }
+        contract
+    }
+
+    #[test]
+    fn broadcast_reaches_every_subscriber() {
+        let mut contract = setup_subscribers();
+        set_context(&alice(), 1);
+        contract.broadcast("News".to_string(), "hello all".to_string());
+
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+        assert_eq!(contract.get_mail_receive_num(carol()), 1);
+        assert_eq!(contract.get_mail_send_num(alice()), 2);
+    }
+
+    #[test]
+    fn broadcast_pages_past_max_recipients() {
+        let mut contract = setup_subscribers();
+        set_context(&owner(), 1);
+        contract.set_max_recipients(1);
+        let before = contract.accounts.get(&alice()).unwrap().used;
+
+        set_context(&alice(), 1);
+        assert!(!contract.broadcast("News".to_string(), "hello all".to_string()));
+        assert!(contract.is_broadcast_pending(alice()));
+        assert_eq!(contract.get_mail_send_num(alice()), 1);
+
+        set_context(&alice(), 1);
+        assert!(contract.continue_broadcast(1));
+        assert!(!contract.is_broadcast_pending(alice()));
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+        assert_eq!(contract.get_mail_receive_num(carol()), 1);
+        let vaccount = contract.accounts.get(&alice()).unwrap();
+        assert_eq!(vaccount.charges.broadcasts.count, 0);
+        assert_eq!(vaccount.used - before, 2 * contract.mail_storage_cost(13));
+    }
+
+    #[test]
+    #[should_panic(expected = "Broadcast in progress")]
+    fn one_broadcast_at_a_time() {
+        let mut contract = setup_subscribers();
+        set_context(&owner(), 1);
+        contract.set_max_recipients(1);
+        set_context(&alice(), 1);
+        contract.broadcast("News".to_string(), "first".to_string());
+        set_context(&alice(), 1);
+        contract.broadcast("News".to_string(), "second".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "E012")]
+    fn broadcast_enforces_content_cap() {
+        let mut contract = setup_subscribers();
+        set_context(&alice(), 1);
+        contract.broadcast("News".to_string(), "x".repeat(MAX_CONTENT_LEN + 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E026")]
+    fn broadcast_enforces_title_cap() {
+        let mut contract = setup_subscribers();
+        set_context(&alice(), 1);
+        contract.broadcast("x".repeat(MAX_TITLE_LEN + 1), "hello".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "E026")]
+    fn send_enforces_title_cap() {
+        let mut contract = setup_subscribers();
+        set_context(&alice(), 1);
+        contract.send_mail(
+            bob(),
+            "x".repeat(MAX_TITLE_LEN + 1),
+            "hello".to_string(),
+            None,
+            None,
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+pub type RequestID = u128;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SendRequest {
+    pub sender: AccountId,
+    pub receiver: AccountId,
+    pub title: String,
+    pub content: String,
+    pub created_at: u64,
+    pub storage_charged: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SendRequestView {
+    pub request_id: U128,
+    #[serde(flatten)]
+    pub request: SendRequest,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn request_to_send(&mut self, receiver: AccountId, title: String, content: String) -> U128 {
+        assert_one_yocto();
+        let sender = env::predecessor_account_id();
+        let title = sanitize_title(&title);
+        let content = sanitize_content(&content);
+        self.assert_not_empty(&title, &content);
+        check_lengths(&title, &content).unwrap_or_else(|error| error.panic());
+        self.assert_accepts_sender(&receiver, &sender);
+
+        let mut vaccount = self
+            .accounts
+            .get(&sender)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        let storage_cost = self.mail_storage_cost((title.len() + content.len()) as u64);
+        require(
+            vaccount.deposit - vaccount.used >= storage_cost,
+            ContractError::NotDepositEnough,
+        );
+        vaccount.charge(ChargeKind::Request, storage_cost);
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&sender, &vaccount);
+
+        let request_id = self.send_request_count;
+        self.send_request_count += 1;
+        let request = SendRequest {
+            sender: sender.clone(),
+            receiver: receiver.clone(),
+            title,
+            content,
+            created_at: env::block_timestamp(),
+            storage_charged: U128(storage_cost),
+        };
+        self.send_requests.insert(&request_id, &request);
+        self.internal_link(&sender, ChargeKind::Request, request_id.to_string());
+        let mut incoming = self.incoming_requests.get(&receiver).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountIncomingRequests {
+                account_id: receiver.clone(),
+            })
+        });
+        incoming.insert(&request_id);
+        self.incoming_requests.insert(&receiver, &incoming);
+        emit_event(
+            "send_requested",
+            json!({
+                "request_id": U128(request_id),
+                "sender": sender,
+                "receiver": receiver,
+            }),
+        );
+        U128(request_id)
+    }
+
+    #[payable]
+    pub fn accept_incoming(&mut self, request_id: U128) -> U128 {
+        assert_one_yocto();
+        let receiver = env::predecessor_account_id();
+        let request = self.internal_take_request(&receiver, request_id.0);
+        let mut email = Email::new(
+            request.sender.clone(),
+            receiver.clone(),
+            request.title,
+            request.content,
+            None,
+        );
+        email.storage_charged = request.storage_charged;
+        if let Some(mut vaccount) = self.accounts.get(&request.sender) {
+            vaccount.release(ChargeKind::Request, request.storage_charged.0);
+            vaccount.charge(ChargeKind::Mail, request.storage_charged.0);
+            self.accounts.insert(&request.sender, &vaccount);
+        }
+        let email_id = self.internal_insert_email(&email);
+        self.internal_notify_new_mail(email_id, &request.sender, &receiver);
+        self.internal_touch(&receiver);
+        U128(email_id)
+    }
+
+    #[payable]
+    pub fn decline_incoming(&mut self, request_id: U128) {
+        assert_one_yocto();
+        let receiver = env::predecessor_account_id();
+        let request = self.internal_take_request(&receiver, request_id.0);
+        self.internal_refund_request(&request);
+        self.internal_touch(&receiver);
+    }
+
+    pub fn get_incoming_requests(
+        &self,
+        account: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<SendRequestView> {
+        self.incoming_requests
+            .get(&account)
+            .map(|incoming| incoming.to_vec())
+            .unwrap_or_default()
+            .into_iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|request_id| {
+                self.send_requests.get(&request_id).map(|mut request| {
+                    request.content = String::new();
+                    SendRequestView {
+                        request_id: U128(request_id),
+                        request,
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    fn internal_take_request(
+        &mut self,
+        receiver: &AccountId,
+        request_id: RequestID,
+    ) -> SendRequest {
+        let mut incoming = self
+            .incoming_requests
+            .get(receiver)
+            .expect("Request not found");
+        assert!(incoming.remove(&request_id), "Request not found");
+        self.incoming_requests.insert(receiver, &incoming);
+        self.internal_remove_request(request_id).unwrap()
+    }
+
+    pub(crate) fn internal_remove_request(&mut self, request_id: RequestID) -> Option<SendRequest> {
+        let request = self.send_requests.remove(&request_id)?;
+        self.internal_unlink(&request.sender, ChargeKind::Request, request_id.to_string());
+        Some(request)
+    }
+
+    pub(crate) fn internal_refund_request(&mut self, request: &SendRequest) {
+        if let Some(mut vaccount) = self.accounts.get(&request.sender) {
+            vaccount.release(ChargeKind::Request, request.storage_charged.0);
+            self.accounts.insert(&request.sender, &vaccount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn setup_request() -> (Contract, U128, Balance) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        register(&mut contract, &carol());
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        set_context(&alice(), 1);
+        let request_id = contract.request_to_send(bob(), "Hello".to_string(), "may I?".to_string());
+        (contract, request_id, before)
+    }
+
+    #[test]
+    fn accepted_request_becomes_mail_charged_to_the_sender() {
+        let (mut contract, request_id, before) = setup_request();
+        let pending = contract.get_incoming_requests(bob(), 0, 10);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].request.content, "");
+        assert!(contract.get_mail_receive(bob()).is_empty());
+        let charged = contract.accounts.get(&alice()).unwrap().used - before;
+        assert!(charged > 0);
+
+        set_context(&bob(), 1);
+        let email_id = contract.accept_incoming(request_id);
+        assert!(contract.get_incoming_requests(bob(), 0, 10).is_empty());
+        let email = contract.get_email_private(email_id).unwrap();
+        assert_eq!(email.sender, alice());
+        assert_eq!(email.content, "may I?");
+        let vaccount = contract.accounts.get(&alice()).unwrap();
+        assert_eq!(vaccount.used, before + charged);
+        assert_eq!(vaccount.charges.requests.amount, U128(0));
+    }
+
+    #[test]
+    fn declined_request_refunds_the_sender() {
+        let (mut contract, request_id, before) = setup_request();
+        set_context(&bob(), 1);
+        contract.decline_incoming(request_id);
+        assert!(contract.get_incoming_requests(bob(), 0, 10).is_empty());
+        assert!(contract.get_mail_receive(bob()).is_empty());
+        assert_eq!(contract.accounts.get(&alice()).unwrap().used, before);
+    }
+
+    #[test]
+    #[should_panic(expected = "Request not found")]
+    fn only_the_receiver_answers_a_request() {
+        let (mut contract, request_id, _) = setup_request();
+        set_context(&carol(), 1);
+        contract.accept_incoming(request_id);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::*;
+use near_sdk::serde_json::json;
+use near_sdk::Balance;
+
+pub const STORAGE_PER_CONTACT: Balance = 10;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn add_contact(&mut self, contact: AccountId) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.accounts.contains_key(&account_id),
+            "Account not registered"
+        );
+
+        let mut contact_set = self.contacts.get(&account_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountContacts {
+                account_id: account_id.clone(),
+            })
+        });
+        assert!(!contact_set.contains(&contact), "Contact already added");
+
+        let storage_cost = STORAGE_PER_CONTACT * self.byte_cost();
+        let mut vaccount = self.accounts.get(&account_id).unwrap();
+        self.internal_charge_entry(
+            &mut vaccount,
+            &account_id,
+            ChargeKind::Contact,
+            contact.to_string(),
+            storage_cost,
+        );
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+
+        contact_set.insert(&contact);
+        self.contacts.insert(&account_id, &contact_set);
+
+        if self
+            .get_notification_prefs(contact.clone())
+            .on_contact_request
+        {
+            emit_event(
+                "contact_request",
+                json!({
+                    "account_id": account_id,
+                    "contact": contact,
+                }),
+            );
+        }
+    }
+
+    #[payable]
+    pub fn remove_contact(&mut self, contact: AccountId) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut contact_set = self.contacts.get(&account_id).expect("Contact not found");
+        assert!(contact_set.remove(&contact), "Contact not found");
+        self.contacts.insert(&account_id, &contact_set);
+
+        if let Some(mut vaccount) = self.accounts.get(&account_id) {
+            self.internal_release_entry(
+                &mut vaccount,
+                &account_id,
+                ChargeKind::Contact,
+                contact.to_string(),
+            );
+            vaccount.last_active = env::block_timestamp();
+            self.accounts.insert(&account_id, &vaccount);
+        }
+    }
+
+    pub fn get_contacts(&self, account: AccountId) -> Vec<AccountId> {
+        if let Some(contact_set) = self.contacts.get(&account) {
+            return contact_set.to_vec();
+        }
+        Vec::new()
+    }
+
+    pub fn is_contact(&self, account: &AccountId, contact: &AccountId) -> bool {
+        self.contacts
+            .get(account)
+            .map(|contact_set| contact_set.contains(contact))
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,248 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Relationship {
+    pub mutual_contacts: bool,
+    pub a_blocks_b: bool,
+    pub b_blocks_a: bool,
+    pub message_count: u64,
+    pub last_ts: Option<u64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_conversation(
+        &self,
+        a: AccountId,
+        b: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<EmailView> {
+        match self.conversations.get(&pair_key(&a, &b)) {
+            Some(conversation) => self.page_index(&conversation, from_index, limit),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_directed_mail(
+        &self,
+        sender: AccountId,
+        receiver: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<EmailView> {
+        match self.directed_index.get(&directed_key(&sender, &receiver)) {
+            Some(directed) => self.page_index(&directed, from_index, limit),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_relationship(&self, a: AccountId, b: AccountId) -> Relationship {
+        let conversation = self.conversations.get(&pair_key(&a, &b));
+        let last_ts = conversation.as_ref().and_then(|conversation| {
+            (0..conversation.len())
+                .rev()
+                .filter_map(|index| conversation.get(index))
+                .find_map(|email_id| self.emails.get(&email_id))
+                .map(|email| email.timestamp)
+        });
+        Relationship {
+            mutual_contacts: self.is_contact(&a, &b) && self.is_contact(&b, &a),
+            a_blocks_b: self.is_blocked_by(&a, &b),
+            b_blocks_a: self.is_blocked_by(&b, &a),
+            message_count: self.conversation_counts.get(&pair_key(&a, &b)).unwrap_or(0),
+            last_ts,
+        }
+    }
+
+    pub fn get_contact_count(&self, account: AccountId) -> u64 {
+        self.conversation_partners
+            .get(&account)
+            .map(|partners| partners.len())
+            .unwrap_or(0)
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_index_conversation(
+        &mut self,
+        sender: &AccountId,
+        receiver: &AccountId,
+        email_id: EmailID,
+    ) {
+        let key = pair_key(sender, receiver);
+        let mut conversation = self.conversations.get(&key).unwrap_or_else(|| {
+            Vector::new(StorageKeys::Conversation {
+                pair_key: key.clone(),
+            })
+        });
+        conversation.push(&email_id);
+        self.conversations.insert(&key, &conversation);
+        let count = self.conversation_counts.get(&key).unwrap_or(0);
+        self.conversation_counts.insert(&key, &(count + 1));
+
+        let key = directed_key(sender, receiver);
+        let mut directed = self.directed_index.get(&key).unwrap_or_else(|| {
+            Vector::new(StorageKeys::DirectedMail {
+                directed_key: key.clone(),
+            })
+        });
+        directed.push(&email_id);
+        self.directed_index.insert(&key, &directed);
+
+        self.internal_add_partner(sender, receiver);
+        self.internal_add_partner(receiver, sender);
+    }
+
+    /// The conversation vectors are append-only, so live mail is counted apart.
+    pub(crate) fn internal_uncount_conversation(
+        &mut self,
+        sender: &AccountId,
+        receiver: &AccountId,
+    ) {
+        let key = pair_key(sender, receiver);
+        match self.conversation_counts.get(&key).unwrap_or(0) {
+            0 | 1 => {
+                self.conversation_counts.remove(&key);
+            }
+            count => {
+                self.conversation_counts.insert(&key, &(count - 1));
+            }
+        }
+    }
+
+    fn internal_add_partner(&mut self, account_id: &AccountId, partner: &AccountId) {
+        let mut partners = self
+            .conversation_partners
+            .get(account_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(StorageKeys::AccountPartners {
+                    account_id: account_id.clone(),
+                })
+            });
+        if partners.insert(partner) {
+            self.conversation_partners.insert(account_id, &partners);
+        }
+    }
+
+    fn page_index(&self, index: &Vector<EmailID>, from_index: u64, limit: u64) -> Vec<EmailView> {
+        index
+            .iter()
+            .skip(from_index as usize)
+            .filter_map(|email_id| {
+                self.emails
+                    .get(&email_id)
+                    .map(|email| EmailView::new(email_id, email))
+            })
+            .take(limit as usize)
+            .collect()
+    }
+}
+
+pub(crate) fn directed_key(sender: &AccountId, receiver: &AccountId) -> String {
+    format!("{}>{}", sender, receiver)
+}
+
+pub(crate) fn pair_key(a: &AccountId, b: &AccountId) -> String {
+    if a <= b {
+        format!("{}:{}", a, b)
+    } else {
+        format!("{}:{}", b, a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pair_key;
+    use crate::test_utils::*;
+    use crate::*;
+
+    #[test]
+    fn relationship_counts_only_live_mail() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let first = send(&mut contract, &alice(), &bob(), "one");
+        send(&mut contract, &bob(), &alice(), "two");
+        assert_eq!(contract.get_relationship(alice(), bob()).message_count, 2);
+
+        set_context(&alice(), 1);
+        contract.delete_mail(U128(first));
+        assert_eq!(contract.get_relationship(bob(), alice()).message_count, 1);
+    }
+
+    #[test]
+    fn pair_key_ignores_argument_order() {
+        assert_eq!(pair_key(&alice(), &bob()), pair_key(&bob(), &alice()));
+        assert_ne!(pair_key(&alice(), &bob()), pair_key(&alice(), &carol()));
+    }
+
+    #[test]
+    fn conversation_holds_both_directions_in_send_order() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        register(&mut contract, &carol());
+        send(&mut contract, &alice(), &bob(), "one");
+        send(&mut contract, &carol(), &bob(), "aside");
+        send(&mut contract, &bob(), &alice(), "two");
+
+        let ids = |views: Vec<EmailView>| -> Vec<u128> {
+            views.iter().map(|view| view.email_id.0).collect()
+        };
+        assert_eq!(
+            ids(contract.get_conversation(alice(), bob(), 0, 10)),
+            vec![0, 2]
+        );
+        assert_eq!(
+            ids(contract.get_conversation(bob(), alice(), 1, 10)),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn directed_mail_holds_one_direction_only() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        send(&mut contract, &alice(), &bob(), "one");
+        send(&mut contract, &bob(), &alice(), "back");
+        send(&mut contract, &alice(), &bob(), "two");
+
+        let ids = |views: Vec<EmailView>| -> Vec<u128> {
+            views.iter().map(|view| view.email_id.0).collect()
+        };
+        assert_eq!(
+            ids(contract.get_directed_mail(alice(), bob(), 0, 10)),
+            vec![0, 2]
+        );
+        assert_eq!(
+            ids(contract.get_directed_mail(bob(), alice(), 0, 10)),
+            vec![1]
+        );
+        assert_eq!(
+            ids(contract.get_directed_mail(alice(), bob(), 1, 1)),
+            vec![2]
+        );
+        assert!(contract
+            .get_directed_mail(alice(), carol(), 0, 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn partners_are_counted_once_per_account() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        register(&mut contract, &carol());
+        send(&mut contract, &alice(), &bob(), "one");
+        send(&mut contract, &bob(), &alice(), "two");
+        send(&mut contract, &carol(), &alice(), "three");
+
+        assert_eq!(contract.get_contact_count(alice()), 2);
+        assert_eq!(contract.get_contact_count(bob()), 1);
+        assert_eq!(contract.get_contact_count(owner()), 0);
+    }
+}
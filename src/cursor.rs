@@ -0,0 +1,96 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn advance_cursor(&mut self, other: AccountId, up_to: U128) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        require(
+            self.accounts.contains_key(&account_id),
+            ContractError::AccountNotRegistered,
+        );
+        let key = (account_id.clone(), other);
+        if let Some(current) = self.conversation_cursor.get(&key) {
+            assert!(up_to.0 > current, "Cursor can only move forward");
+        }
+        self.conversation_cursor.insert(&key, &up_to.0);
+        self.internal_touch(&account_id);
+    }
+
+    pub fn get_conversation_cursor(&self, account: AccountId, other: AccountId) -> Option<U128> {
+        self.conversation_cursor.get(&(account, other)).map(U128)
+    }
+
+    pub fn get_unread_count(&self, receiver: AccountId) -> u64 {
+        let mut unread = 0;
+        if let Some(receiver_vec) = self.receivers.get(&receiver) {
+            for index in receiver_vec.iter() {
+                if let Some(mail) = self.emails.get(&index) {
+                    if !self.is_read_by(&receiver, index, &mail)
+                        && !self.is_thread_muted_by(&receiver, index, &mail)
+                    {
+                        unread += 1;
+                    }
+                }
+            }
+        }
+        unread
+    }
+}
+
+impl Contract {
+    pub(crate) fn is_read_by(&self, reader: &AccountId, email_id: EmailID, email: &Email) -> bool {
+        if email.read_at.is_some() {
+            return true;
+        }
+        self.conversation_cursor
+            .get(&(reader.clone(), email.sender.clone()))
+            .map(|cursor| email_id <= cursor)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn setup_conversation() -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        register(&mut contract, &carol());
+        for content in ["one", "two", "three"] {
+            send(&mut contract, &alice(), &bob(), content);
+        }
+        send(&mut contract, &carol(), &bob(), "other");
+        contract
+    }
+
+    #[test]
+    fn cursor_marks_a_conversation_read_up_to_an_id() {
+        let mut contract = setup_conversation();
+        assert_eq!(contract.get_unread_count(bob()), 4);
+        set_context(&bob(), 1);
+        contract.advance_cursor(alice(), U128(1));
+
+        assert_eq!(
+            contract.get_conversation_cursor(bob(), alice()),
+            Some(U128(1))
+        );
+        assert_eq!(contract.get_conversation_cursor(bob(), carol()), None);
+        // The cursor settles only alice's mail; carol's id 3 stays unread.
+        assert_eq!(contract.get_unread_count(bob()), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cursor can only move forward")]
+    fn cursor_never_moves_back() {
+        let mut contract = setup_conversation();
+        set_context(&bob(), 1);
+        contract.advance_cursor(alice(), U128(2));
+        set_context(&bob(), 1);
+        contract.advance_cursor(alice(), U128(1));
+    }
+}
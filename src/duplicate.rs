@@ -0,0 +1,121 @@
+use crate::*;
+
+pub const MAX_RECENT_MAIL_HASHES: usize = 50;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn set_reject_duplicates(&mut self, reject_duplicates: bool) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.reject_duplicates = reject_duplicates;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+        if !reject_duplicates {
+            self.recent_mail_hashes.remove(&account_id);
+        }
+    }
+
+    pub fn get_reject_duplicates(&self, account: AccountId) -> bool {
+        self.accounts
+            .get(&account)
+            .map(|vaccount| vaccount.reject_duplicates)
+            .unwrap_or(false)
+    }
+}
+
+impl Contract {
+    pub(crate) fn check_not_duplicate(
+        &self,
+        email: &Email,
+    ) -> Result<Option<Vec<u8>>, ContractError> {
+        if !self.get_reject_duplicates(email.receiver.clone()) {
+            return Ok(None);
+        }
+
+        let mail_hash = mail_hash(email);
+        let recent = self
+            .recent_mail_hashes
+            .get(&email.receiver)
+            .unwrap_or_default();
+        for (hash, email_id) in recent.iter() {
+            if *hash != mail_hash || !self.is_receiver_of(&email.receiver, *email_id) {
+                continue;
+            }
+            if let Some(existing) = self.emails.get(email_id) {
+                ensure(
+                    self.is_read_by(&email.receiver, *email_id, &existing),
+                    ContractError::DuplicateMessage,
+                )?;
+            }
+        }
+        Ok(Some(mail_hash))
+    }
+
+    pub(crate) fn internal_record_mail_hash(
+        &mut self,
+        receiver: &AccountId,
+        mail_hash: Vec<u8>,
+        email_id: EmailID,
+    ) {
+        let mut recent = self.recent_mail_hashes.get(receiver).unwrap_or_default();
+        if recent.len() >= MAX_RECENT_MAIL_HASHES {
+            recent.remove(0);
+        }
+        recent.push((mail_hash, email_id));
+        self.recent_mail_hashes.insert(receiver, &recent);
+    }
+}
+
+pub(crate) fn mail_hash(email: &Email) -> Vec<u8> {
+    let mut data = email.sender.as_bytes().to_vec();
+    data.push(0);
+    data.extend(email.title.as_bytes());
+    data.push(0);
+    data.extend(email.content.as_bytes());
+    env::sha256(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn setup_dedup(reject: bool) -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&bob(), 1);
+        contract.set_reject_duplicates(reject);
+        send(&mut contract, &alice(), &bob(), "hi");
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "E024")]
+    fn unread_duplicate_is_rejected() {
+        let mut contract = setup_dedup(true);
+        send(&mut contract, &alice(), &bob(), "hi");
+    }
+
+    #[test]
+    fn duplicate_is_accepted_once_the_original_is_read() {
+        let mut contract = setup_dedup(true);
+        send(&mut contract, &alice(), &bob(), "different");
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(0));
+        send(&mut contract, &alice(), &bob(), "hi");
+        assert_eq!(contract.get_mail_receive_num(bob()), 3);
+    }
+
+    #[test]
+    fn duplicates_are_allowed_without_the_opt_in() {
+        let mut contract = setup_dedup(false);
+        send(&mut contract, &alice(), &bob(), "hi");
+        assert_eq!(contract.get_mail_receive_num(bob()), 2);
+    }
+}
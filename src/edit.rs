@@ -0,0 +1,275 @@
+use crate::*;
+
+pub const DEFAULT_EDIT_WINDOW: u64 = 15 * 60_000_000_000;
+
+#[near_bindgen]
+impl Contract {
+    /// Replaces an unread mail's title and content. When the new content raises
+    /// the fee or spam bond owed, the difference must be attached on top of the
+    /// usual yocto and is escrowed with the original amounts.
+    #[payable]
+    pub fn edit_mail(&mut self, email_id: U128, title: String, content: String) {
+        let real_email_id: EmailID = email_id.0;
+        let sender = env::predecessor_account_id();
+        require(
+            self.is_sender_of(&sender, real_email_id),
+            ContractError::CallerNotSender,
+        );
+        let mut email = self.emails.get(&real_email_id).unwrap();
+        assert!(email.read_at.is_none(), "Email already read");
+        require(
+            env::block_timestamp() < email.timestamp.saturating_add(self.edit_window_ns),
+            ContractError::EditWindowClosed,
+        );
+
+        let title = sanitize_title(&title);
+        let content = sanitize_content(&content);
+        self.assert_not_empty(&title, &content);
+        check_lengths(&title, &content).unwrap_or_else(|error| error.panic());
+
+        let payer = email
+            .storage_payer
+            .clone()
+            .unwrap_or_else(|| sender.clone());
+        let old_len = (email.title.len() + email.content.len()) as u64;
+        let new_len = (title.len() + content.len()) as u64;
+        require(
+            payer == sender || new_len <= old_len,
+            ContractError::ContentExceedsStorage,
+        );
+
+        if payer == env::current_account_id() {
+            // Imported mail was never paid for by its sender, so it owes no fee or
+            // bond and the contract keeps carrying its (smaller) storage.
+            assert_one_yocto();
+        } else {
+            self.internal_settle_edit(real_email_id, &mut email, content.len() as u64);
+            self.internal_release_mail_storage(&email);
+            let mut vaccount = self.accounts.get(&payer).unwrap();
+            // Only the text changes size, so the indexes keep what the send
+            // measured for them.
+            let byte_cost = self.byte_cost();
+            let storage_cost = (email.storage_charged.0 + byte_cost * Balance::from(new_len))
+                .saturating_sub(byte_cost * Balance::from(old_len));
+            require(
+                vaccount.deposit - vaccount.used >= storage_cost,
+                ContractError::NotDepositEnough,
+            );
+            vaccount.charge(ChargeKind::Mail, storage_cost);
+            self.accounts.insert(&payer, &vaccount);
+            email.storage_charged = U128(storage_cost);
+        }
+
+        email.title = title;
+        email.content = content;
+        self.emails.insert(&real_email_id, &email);
+        self.internal_touch(&sender);
+        emit_event(
+            "mail_edited",
+            json!({
+                "email_id": email_id,
+                "sender": sender,
+                "receiver": email.receiver,
+            }),
+        );
+    }
+
+    #[payable]
+    pub fn set_mail_visibility(&mut self, email_id: U128, visible: bool) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let sender = env::predecessor_account_id();
+        require(
+            self.is_sender_of(&sender, real_email_id),
+            ContractError::CallerNotSender,
+        );
+        let mut email = self.emails.get(&real_email_id).unwrap();
+        email.retracted = !visible;
+        self.emails.insert(&real_email_id, &email);
+        self.internal_touch(&sender);
+    }
+}
+
+impl Contract {
+    /// Collects whatever the edited content adds to the fee and spam bond owed,
+    /// requiring exactly that plus one yocto to be attached.
+    fn internal_settle_edit(&mut self, email_id: EmailID, email: &mut Email, content_len: u64) {
+        let sender = email.sender.clone();
+        let receiver = email.receiver.clone();
+        let escrowed = self.fee_escrows.get(&email_id);
+        let paid_fee = escrowed.as_ref().map(|escrow| escrow.amount).unwrap_or(0);
+        let fee_due = self
+            .payable_fee(&sender, &receiver, content_len)
+            .unwrap_or(0)
+            .saturating_sub(paid_fee);
+        let posted = self.spam_bonds.get(&email_id);
+        let posted_bond = posted.as_ref().map(|bond| bond.amount).unwrap_or(0);
+        let bond_due = self
+            .required_spam_bond(&sender, &receiver)
+            .saturating_sub(posted_bond);
+        require(
+            env::attached_deposit() == fee_due + bond_due + 1,
+            ContractError::AttachedDepositMismatch,
+        );
+
+        if fee_due > 0 {
+            let escrow = match escrowed {
+                Some(mut escrow) => {
+                    escrow.amount += fee_due;
+                    escrow
+                }
+                None => FeeEscrow {
+                    sender: sender.clone(),
+                    receiver,
+                    amount: fee_due,
+                    escrowed_at: env::block_timestamp(),
+                },
+            };
+            email.fee = Some(FeeKind::Near(U128(escrow.amount)));
+            self.fee_escrows.insert(&email_id, &escrow);
+            self.total_escrowed += fee_due;
+        }
+        if bond_due > 0 {
+            let bond = match posted {
+                Some(mut bond) => {
+                    bond.amount += bond_due;
+                    bond
+                }
+                None => SpamBond {
+                    sender,
+                    amount: bond_due,
+                    posted_at: env::block_timestamp(),
+                },
+            };
+            self.spam_bonds.insert(&email_id, &bond);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::ImportEntry;
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn setup_mail(per_byte_fee: Balance) -> (Contract, EmailID) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+        set_context(&owner(), 1);
+        contract.set_per_byte_fee(U128(per_byte_fee));
+        (contract, email_id)
+    }
+
+    #[test]
+    #[should_panic(expected = "E007")]
+    fn edit_raising_fee_requires_difference() {
+        let (mut contract, email_id) = setup_mail(10);
+        set_context(&alice(), 1);
+        contract.edit_mail(
+            U128(email_id),
+            "Hello".to_string(),
+            "much longer".to_string(),
+        );
+    }
+
+    #[test]
+    fn edit_escrows_fee_difference() {
+        let (mut contract, email_id) = setup_mail(10);
+        let due = contract.required_fee(&alice(), &bob(), 11);
+        set_context(&alice(), due + 1);
+        contract.edit_mail(
+            U128(email_id),
+            "Hello".to_string(),
+            "much longer".to_string(),
+        );
+
+        let email = contract.emails.get(&email_id).unwrap();
+        assert_eq!(email.content, "much longer");
+        assert_eq!(email.fee, Some(FeeKind::Near(U128(due))));
+        assert_eq!(contract.fee_escrows.get(&email_id).unwrap().amount, due);
+    }
+
+    #[test]
+    fn edit_recharges_only_the_change_in_text() {
+        let (mut contract, email_id) = setup_mail(0);
+        let charged = contract.emails.get(&email_id).unwrap().storage_charged.0;
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        set_context(&alice(), 1);
+        contract.edit_mail(U128(email_id), "Hello".to_string(), "hello".to_string());
+
+        let extra = 3 * contract.byte_cost();
+        let email = contract.emails.get(&email_id).unwrap();
+        assert_eq!(email.storage_charged.0, charged + extra);
+        assert_eq!(
+            contract.accounts.get(&alice()).unwrap().used,
+            before + extra
+        );
+    }
+
+    #[test]
+    fn edit_window_saturates() {
+        let (mut contract, email_id) = setup_mail(0);
+        set_context(&owner(), 1);
+        contract.set_edit_window_ns(u64::MAX);
+        set_context(&alice(), 1);
+        contract.edit_mail(U128(email_id), "Hello".to_string(), "h".to_string());
+        assert_eq!(contract.emails.get(&email_id).unwrap().content, "h");
+    }
+
+    #[test]
+    fn edit_imported_mail_is_contract_paid() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email = Email::new(
+            alice(),
+            bob(),
+            "Old".to_string(),
+            "migrated".to_string(),
+            None,
+        );
+        set_context(&owner(), 1);
+        contract.import_emails(vec![ImportEntry {
+            sender: alice(),
+            receiver: bob(),
+            email,
+        }]);
+        let before = contract.accounts.get(&alice()).unwrap().used;
+
+        set_context(&alice(), 1);
+        contract.edit_mail(U128(0), "Old".to_string(), "moved".to_string());
+
+        assert_eq!(contract.emails.get(&0).unwrap().content, "moved");
+        assert_eq!(contract.accounts.get(&alice()).unwrap().used, before);
+    }
+
+    #[test]
+    fn retraction_hides_body_until_restored() {
+        let (mut contract, email_id) = setup_mail(0);
+        set_context(&alice(), 1);
+        contract.set_mail_visibility(U128(email_id), false);
+
+        set_context(&bob(), 0);
+        let email = contract.get_email_private(U128(email_id)).unwrap();
+        assert_eq!(email.content, RETRACTED_CONTENT_PLACEHOLDER);
+        set_context(&alice(), 0);
+        let email = contract.get_email_private(U128(email_id)).unwrap();
+        assert_eq!(email.content, "hi");
+
+        set_context(&alice(), 1);
+        contract.set_mail_visibility(U128(email_id), true);
+        set_context(&bob(), 0);
+        let email = contract.get_email_private(U128(email_id)).unwrap();
+        assert_eq!(email.content, "hi");
+    }
+
+    #[test]
+    #[should_panic(expected = "E008")]
+    fn only_sender_toggles_visibility() {
+        let (mut contract, email_id) = setup_mail(0);
+        set_context(&bob(), 1);
+        contract.set_mail_visibility(U128(email_id), false);
+    }
+}
@@ -1,12 +1,477 @@
+use crate::ack::AckStatus;
+use crate::EmailID;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, AccountId};
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Email {
+    pub sender: AccountId,
+    pub receiver: AccountId,
     pub title: String,
     pub content: String,
     pub timestamp: u64,
-    pub fee: Option<U128>,
+    pub fee: Option<FeeKind>,
+    pub read_at: Option<u64>,
+    pub content_type: String,
+    pub parent_id: Option<U128>,
+    pub bid: Option<U128>,
+    pub storage_payer: Option<AccountId>,
+    pub ack: Option<AckStatus>,
+    pub thread_root: Option<U128>,
+    pub batch_id: Option<U128>,
+    pub locked_until: Option<u64>,
+    pub thread_depth: u32,
+    pub retracted: bool,
+    pub expires_at: Option<u64>,
+    pub grace_period_ns: u64,
+    pub encoding: Option<String>,
+    pub storage_charged: U128,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+/// A file kept off-chain and referenced by its content id.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Attachment {
+    pub cid: String,
+    pub name: String,
+    pub size: u64,
+}
+
+/// What a mail's fee is paid in. Sends take their fee in attached NEAR and
+/// record `Near`; `Ft` records a fee paid in the fungible token `contract`,
+/// whose `ft_metadata` gives the decimals to render `amount` with.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum FeeKind {
+    Near(U128),
+    Ft { contract: AccountId, amount: U128 },
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EmailView {
+    pub email_id: U128,
+    #[serde(flatten)]
+    pub email: Email,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EmailMeta {
+    pub sender: AccountId,
+    pub receiver: AccountId,
+    pub title: String,
+    pub timestamp: u64,
+    pub fee: Option<FeeKind>,
+    pub read: bool,
+    pub size_bytes: u64,
+}
+
+impl From<Email> for EmailMeta {
+    fn from(email: Email) -> Self {
+        Self {
+            size_bytes: email.content.len() as u64,
+            read: email.read_at.is_some(),
+            sender: email.sender,
+            receiver: email.receiver,
+            title: email.title,
+            timestamp: email.timestamp,
+            fee: email.fee,
+        }
+    }
+}
+
+impl Email {
+    pub fn new(
+        sender: AccountId,
+        receiver: AccountId,
+        title: String,
+        content: String,
+        fee: Option<U128>,
+    ) -> Self {
+        Self {
+            sender,
+            receiver,
+            title,
+            content,
+            timestamp: env::block_timestamp(),
+            fee: fee.map(FeeKind::Near),
+            read_at: None,
+            content_type: DEFAULT_CONTENT_TYPE.to_string(),
+            parent_id: None,
+            bid: None,
+            storage_payer: None,
+            ack: None,
+            thread_root: None,
+            batch_id: None,
+            locked_until: None,
+            thread_depth: 0,
+            retracted: false,
+            expires_at: None,
+            grace_period_ns: 0,
+            encoding: None,
+            storage_charged: U128(0),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// The bytes this mail stores for its title, content and attachments.
+    pub fn stored_len(&self) -> u64 {
+        (self.title.len() + self.content.len()) as u64 + attachments_bytes(&self.attachments)
+    }
+
+    /// The NEAR this mail's fee escrows; a token fee escrows none.
+    pub fn near_fee(&self) -> u128 {
+        match &self.fee {
+            Some(FeeKind::Near(amount)) => amount.0,
+            _ => 0,
+        }
+    }
+}
+
+pub const DEFAULT_CONTENT_TYPE: &str = "text/plain";
+pub const CONTENT_TYPES: [&str; 3] = ["text/plain", "text/markdown", "application/json"];
+pub const ENCODINGS: [&str; 3] = ["utf8", "base64", "hex"];
+pub const MAX_CONTENT_LEN: usize = 4096;
+pub const MAX_TITLE_LEN: usize = 256;
+pub const DEFAULT_MAX_ATTACHMENT_BYTES: u64 = 1024;
+pub const LOCKED_CONTENT_PLACEHOLDER: &str = "[locked]";
+pub const RETRACTED_CONTENT_PLACEHOLDER: &str = "[retracted]";
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SendOptions {
+    pub content_type: Option<String>,
+    pub bid: Option<U128>,
+    pub locked_until: Option<u64>,
+    pub expires_at: Option<u64>,
+    pub grace_period_ns: Option<u64>,
+    pub encoding: Option<String>,
+    pub attachments: Option<Vec<Attachment>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SendResult {
+    pub email_id: U128,
+    pub storage_used: U128,
+    pub storage_remaining: U128,
+}
+
+impl SendOptions {
+    pub(crate) fn apply(self, email: &mut Email) {
+        email.content_type = resolve_content_type(self.content_type);
+        email.bid = self.bid;
+        email.locked_until = self.locked_until;
+        email.expires_at = self.expires_at;
+        email.grace_period_ns = self.grace_period_ns.unwrap_or(0);
+        email.encoding = self.encoding.map(resolve_encoding);
+        email.attachments = self.attachments.unwrap_or_default();
+    }
+}
+
+/// The serialized size of `attachments`, summed over each attachment record.
+pub(crate) fn attachments_bytes(attachments: &[Attachment]) -> u64 {
+    attachments
+        .iter()
+        .map(|attachment| attachment.try_to_vec().unwrap().len() as u64)
+        .sum()
+}
+
+pub(crate) fn resolve_content_type(content_type: Option<String>) -> String {
+    let content_type = content_type.unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string());
+    assert!(
+        CONTENT_TYPES.contains(&content_type.as_str()),
+        "Unsupported content type"
+    );
+    content_type
+}
+
+pub(crate) fn resolve_encoding(encoding: String) -> String {
+    assert!(
+        ENCODINGS.contains(&encoding.as_str()),
+        "Unsupported encoding"
+    );
+    encoding
+}
+
+impl EmailView {
+    /// Public view of a mail. Views take no caller and anyone can query any
+    /// account, so the body never leaves through a view; parties read it with
+    /// `get_email_private`.
+    pub(crate) fn new(email_id: EmailID, mut email: Email) -> Self {
+        email.content = String::new();
+        Self {
+            email_id: U128(email_id),
+            email,
+        }
+    }
+}
+
+/// The body as `caller` may see it: retracted mail only to its sender, and
+/// time-locked mail to no one until it unlocks.
+pub(crate) fn visible_content(email: &Email, caller: &AccountId) -> String {
+    if email.retracted && caller != &email.sender {
+        RETRACTED_CONTENT_PLACEHOLDER.to_string()
+    } else if email
+        .locked_until
+        .is_some_and(|locked_until| env::block_timestamp() < locked_until)
+    {
+        LOCKED_CONTENT_PLACEHOLDER.to_string()
+    } else {
+        email.content.clone()
+    }
+}
+
+pub(crate) fn sanitize_title(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+pub(crate) fn sanitize_content(content: &str) -> String {
+    content
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::testing_env;
+
+    fn send_locked(contract: &mut Contract, locked_until: u64) -> EmailID {
+        set_context(&alice(), 1);
+        let options = SendOptions {
+            locked_until: Some(locked_until),
+            ..Default::default()
+        };
+        contract
+            .send_mail(
+                bob(),
+                "Hello".to_string(),
+                "secret".to_string(),
+                None,
+                Some(options),
+            )
+            .0
+    }
+
+    fn setup_pair() -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        contract
+    }
+
+    #[test]
+    fn views_never_return_bodies() {
+        let mut contract = setup_pair();
+        let email_id = send(&mut contract, &alice(), &bob(), "TOPSECRET");
+        set_context(&carol(), 0);
+
+        let contents: Vec<String> = [
+            contract.get_mail_receive_paginated(bob(), 0, 10),
+            contract.get_mail_receive_after(bob(), None, 10),
+            contract.get_mail_receive_sorted(bob(), None, 0, 10),
+            contract.get_conversation(alice(), bob(), 0, 10),
+            contract.get_directed_mail(alice(), bob(), 0, 10),
+            contract.search_sent_titles(alice(), "hello".to_string(), 10),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|view| view.email.content)
+        .chain(
+            contract
+                .get_mail_receive(bob())
+                .into_iter()
+                .map(|email| email.content),
+        )
+        .chain(
+            contract
+                .get_email(U128(email_id))
+                .map(|email| email.content),
+        )
+        .collect();
+        assert_eq!(contents.len(), 8);
+        assert!(contents.iter().all(String::is_empty));
+    }
+
+    #[test]
+    fn private_read_hides_locked_content_until_unlock() {
+        let mut contract = setup_pair();
+        let email_id = send_locked(&mut contract, 1_000);
+
+        set_context(&bob(), 0);
+        let email = contract.get_email_private(U128(email_id)).unwrap();
+        assert_eq!(email.content, LOCKED_CONTENT_PLACEHOLDER);
+
+        testing_env!(context(&bob(), 0).block_timestamp(2_000).build());
+        let email = contract.get_email_private(U128(email_id)).unwrap();
+        assert_eq!(email.content, "secret");
+    }
+
+    #[test]
+    fn private_read_hides_retracted_content_from_receiver() {
+        let mut contract = setup_pair();
+        let email_id = send(&mut contract, &alice(), &bob(), "oops");
+        set_context(&alice(), 1);
+        contract.set_mail_visibility(U128(email_id), false);
+
+        set_context(&bob(), 0);
+        let email = contract.get_email_private(U128(email_id)).unwrap();
+        assert_eq!(email.content, RETRACTED_CONTENT_PLACEHOLDER);
+        set_context(&alice(), 0);
+        let email = contract.get_email_private(U128(email_id)).unwrap();
+        assert_eq!(email.content, "oops");
+    }
+
+    #[test]
+    fn reply_quotes_visible_parent_content() {
+        let mut contract = setup_pair();
+        let email_id = send_locked(&mut contract, 1_000);
+
+        set_context(&bob(), 1);
+        let reply_id = contract.reply_mail(U128(email_id), "ok".to_string(), None, true);
+
+        let reply = contract.emails.get(&reply_id.0).unwrap();
+        assert!(reply.content.contains(LOCKED_CONTENT_PLACEHOLDER));
+        assert!(!reply.content.contains("secret"));
+    }
+
+    fn send_typed(contract: &mut Contract, content_type: Option<&str>) -> EmailID {
+        set_context(&alice(), 1);
+        let options = SendOptions {
+            content_type: content_type.map(str::to_string),
+            ..Default::default()
+        };
+        contract
+            .send_mail(
+                bob(),
+                "Hello".to_string(),
+                "# hi".to_string(),
+                None,
+                Some(options),
+            )
+            .0
+    }
+
+    #[test]
+    fn content_type_is_stored_and_defaults_to_plain_text() {
+        let mut contract = setup_pair();
+        let plain = send_typed(&mut contract, None);
+        assert_eq!(
+            contract.get_email(U128(plain)).unwrap().content_type,
+            DEFAULT_CONTENT_TYPE
+        );
+
+        let markdown = send_typed(&mut contract, Some("text/markdown"));
+        assert_eq!(
+            contract.get_email(U128(markdown)).unwrap().content_type,
+            "text/markdown"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported content type")]
+    fn unknown_content_type_is_rejected() {
+        let mut contract = setup_pair();
+        send_typed(&mut contract, Some("text/html"));
+    }
+
+    #[test]
+    fn titles_lose_control_characters_and_padding() {
+        assert_eq!(sanitize_title("  Hi\nthere\u{7}  "), "Hithere");
+        assert_eq!(sanitize_content(" a\n\tb\r "), "a\n\tb");
+
+        let mut contract = setup_pair();
+        set_context(&alice(), 1);
+        let email_id = contract.send_mail(
+            bob(),
+            " **Urgent**\r\n".to_string(),
+            "body".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(contract.get_email(email_id).unwrap().title, "**Urgent**");
+    }
+
+    fn send_encoded(contract: &mut Contract, encoding: Option<&str>, content: &str) -> EmailID {
+        set_context(&alice(), 1);
+        let options = SendOptions {
+            encoding: encoding.map(str::to_string),
+            ..Default::default()
+        };
+        contract
+            .send_mail(
+                bob(),
+                "Hello".to_string(),
+                content.to_string(),
+                None,
+                Some(options),
+            )
+            .0
+    }
+
+    #[test]
+    fn encoded_content_round_trips_with_its_tag() {
+        let mut contract = setup_pair();
+        let encoded = send_encoded(&mut contract, Some("base64"), "aGVsbG8=");
+        let untagged = send_encoded(&mut contract, None, "hello");
+
+        set_context(&bob(), 0);
+        let email = contract.get_email_private(U128(encoded)).unwrap();
+        assert_eq!(email.content, "aGVsbG8=");
+        assert_eq!(email.encoding, Some("base64".to_string()));
+        assert_eq!(contract.get_email(U128(untagged)).unwrap().encoding, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported encoding")]
+    fn unknown_encoding_is_rejected() {
+        let mut contract = setup_pair();
+        send_encoded(&mut contract, Some("rot13"), "uryyb");
+    }
+
+    #[test]
+    fn meta_describes_a_mail_without_its_body() {
+        let mut contract = setup_pair();
+        testing_env!(context(&alice(), 1).block_timestamp(42).build());
+        let email_id = contract
+            .send_mail(
+                bob(),
+                "Hello".to_string(),
+                "TOPSECRET".to_string(),
+                None,
+                None,
+            )
+            .0;
+
+        let meta = contract.get_email_meta(U128(email_id)).unwrap();
+        assert_eq!(meta.sender, alice());
+        assert_eq!(meta.receiver, bob());
+        assert_eq!(meta.title, "Hello");
+        assert_eq!(meta.timestamp, 42);
+        assert_eq!(meta.size_bytes, "TOPSECRET".len() as u64);
+        assert!(!meta.read);
+        let json = near_sdk::serde_json::to_string(&meta).unwrap();
+        assert!(!json.contains("content") && !json.contains("TOPSECRET"));
+
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(email_id));
+        assert!(contract.get_email_meta(U128(email_id)).unwrap().read);
+        assert!(contract.get_email_meta(U128(email_id + 1)).is_none());
+    }
 }
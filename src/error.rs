@@ -0,0 +1,189 @@
+use crate::*;
+
+pub enum ContractError {
+    AccountNotRegistered,
+    NotDepositEnough,
+    FeeMustBeNone,
+    FeeBelowMinimum,
+    AccountSuspended,
+    SendCooldownActive,
+    AttachedDepositMismatch,
+    CallerNotSender,
+    CallerNotReceiver,
+    FeeNotEscrowed,
+    FeeClaimWindowOpen,
+    ContentTooLong,
+    ContentExceedsStorage,
+    SenderBlocked,
+    SenderNotAllowed,
+    EmptyMail,
+    PaidMailRejected,
+    ThreadTooDeep,
+    LabelLimitReached,
+    EditWindowClosed,
+    ScheduledLimitReached,
+    MailboxFrozen,
+    BatchTooLarge,
+    DuplicateMessage,
+    ReceiverNotVerified,
+    TitleTooLong,
+    AttachmentsTooLarge(u64),
+}
+
+impl ContractError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ContractError::AccountNotRegistered => "E001",
+            ContractError::NotDepositEnough => "E002",
+            ContractError::FeeMustBeNone => "E003",
+            ContractError::FeeBelowMinimum => "E004",
+            ContractError::AccountSuspended => "E005",
+            ContractError::SendCooldownActive => "E006",
+            ContractError::AttachedDepositMismatch => "E007",
+            ContractError::CallerNotSender => "E008",
+            ContractError::CallerNotReceiver => "E009",
+            ContractError::FeeNotEscrowed => "E010",
+            ContractError::FeeClaimWindowOpen => "E011",
+            ContractError::ContentTooLong => "E012",
+            ContractError::ContentExceedsStorage => "E013",
+            ContractError::SenderBlocked => "E014",
+            ContractError::SenderNotAllowed => "E015",
+            ContractError::EmptyMail => "E016",
+            ContractError::PaidMailRejected => "E017",
+            ContractError::ThreadTooDeep => "E018",
+            ContractError::LabelLimitReached => "E019",
+            ContractError::EditWindowClosed => "E020",
+            ContractError::ScheduledLimitReached => "E021",
+            ContractError::MailboxFrozen => "E022",
+            ContractError::BatchTooLarge => "E023",
+            ContractError::DuplicateMessage => "E024",
+            ContractError::ReceiverNotVerified => "E025",
+            ContractError::TitleTooLong => "E026",
+            ContractError::AttachmentsTooLarge(_) => "E027",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ContractError::AccountNotRegistered => "Account not registered",
+            ContractError::NotDepositEnough => "Not deposit enough",
+            ContractError::FeeMustBeNone => "Fee must be none",
+            ContractError::FeeBelowMinimum => "Fee below minimum",
+            ContractError::AccountSuspended => "Account suspended for spam",
+            ContractError::SendCooldownActive => "Send cooldown active",
+            ContractError::AttachedDepositMismatch => {
+                "Requires attached deposit of 1 yoctoNEAR plus spam bond, bid and fee"
+            }
+            ContractError::CallerNotSender => "Caller is not sender",
+            ContractError::CallerNotReceiver => "Caller is not receiver",
+            ContractError::FeeNotEscrowed => "No escrowed fee for email",
+            ContractError::FeeClaimWindowOpen => "Fee claim window not elapsed",
+            ContractError::ContentTooLong => "Content exceeds maximum length",
+            ContractError::ContentExceedsStorage => "Content exceeds available storage",
+            ContractError::SenderBlocked => "Sender is blocked by receiver",
+            ContractError::SenderNotAllowed => "Receiver only accepts allowlisted senders",
+            ContractError::EmptyMail => "Title and content are empty",
+            ContractError::PaidMailRejected => "Receiver does not accept paid mail",
+            ContractError::ThreadTooDeep => "Thread too deep",
+            ContractError::LabelLimitReached => "Label limit reached",
+            ContractError::EditWindowClosed => "Edit window has closed",
+            ContractError::ScheduledLimitReached => "Too many scheduled mails",
+            ContractError::MailboxFrozen => "Mailbox frozen",
+            ContractError::BatchTooLarge => "Batch exceeds maximum size",
+            ContractError::DuplicateMessage => "Duplicate message",
+            ContractError::ReceiverNotVerified => "Receiver does not implement messaging",
+            ContractError::TitleTooLong => "Title exceeds maximum length",
+            ContractError::AttachmentsTooLarge(_) => "Attachments exceed max_attachment_bytes",
+        }
+    }
+
+    /// The code and message, with the amount over the limit where there is one.
+    pub fn describe(&self) -> String {
+        match self {
+            ContractError::AttachmentsTooLarge(over) => {
+                format!("{}: {} by {} bytes", self.code(), self.message(), over)
+            }
+            _ => format!("{}: {}", self.code(), self.message()),
+        }
+    }
+
+    pub fn panic(&self) -> ! {
+        let message = self.describe();
+        if cfg!(target_arch = "wasm32") {
+            env::panic_str(&message)
+        } else {
+            panic!("{}", message)
+        }
+    }
+}
+
+pub(crate) fn require(condition: bool, error: ContractError) {
+    if !condition {
+        error.panic();
+    }
+}
+
+pub(crate) fn ensure(condition: bool, error: ContractError) -> Result<(), ContractError> {
+    if condition {
+        Ok(())
+    } else {
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    #[test]
+    fn codes_are_distinct() {
+        let errors = [
+            ContractError::AccountNotRegistered,
+            ContractError::NotDepositEnough,
+            ContractError::FeeMustBeNone,
+            ContractError::FeeBelowMinimum,
+            ContractError::AccountSuspended,
+            ContractError::SendCooldownActive,
+            ContractError::AttachedDepositMismatch,
+            ContractError::CallerNotSender,
+            ContractError::CallerNotReceiver,
+            ContractError::FeeNotEscrowed,
+            ContractError::FeeClaimWindowOpen,
+            ContractError::ContentTooLong,
+            ContractError::ContentExceedsStorage,
+            ContractError::SenderBlocked,
+            ContractError::SenderNotAllowed,
+            ContractError::EmptyMail,
+            ContractError::PaidMailRejected,
+            ContractError::ThreadTooDeep,
+            ContractError::LabelLimitReached,
+            ContractError::EditWindowClosed,
+            ContractError::ScheduledLimitReached,
+            ContractError::MailboxFrozen,
+            ContractError::BatchTooLarge,
+            ContractError::DuplicateMessage,
+            ContractError::ReceiverNotVerified,
+            ContractError::TitleTooLong,
+            ContractError::AttachmentsTooLarge(1),
+        ];
+        let codes: std::collections::BTreeSet<&str> =
+            errors.iter().map(ContractError::code).collect();
+        assert_eq!(codes.len(), errors.len());
+        assert_eq!(
+            ContractError::CallerNotSender.describe(),
+            "E008: Caller is not sender"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E008: Caller is not sender")]
+    fn failed_delete_reports_its_code() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+        set_context(&bob(), 1);
+        contract.delete_mail(U128(email_id));
+    }
+}
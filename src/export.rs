@@ -0,0 +1,127 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde::{Deserialize, Serialize};
+
+pub const MAX_EXPORT_LIMIT: u64 = 50;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountExport {
+    pub deposit: U128,
+    pub used: U128,
+    pub sent: Vec<U128>,
+    pub received: Vec<U128>,
+    pub archived: Vec<U128>,
+    pub contacts: Vec<AccountId>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Owner-only, so it is a change method: views cannot read the predecessor.
+    #[payable]
+    pub fn export_all(&mut self, from_index: u64, limit: u64) -> Vec<(AccountId, AccountExport)> {
+        assert_one_yocto();
+        self.assert_owner();
+        let account_ids = self.account_ids.as_vector();
+        let end = account_ids
+            .len()
+            .min(from_index.saturating_add(limit.min(MAX_EXPORT_LIMIT)));
+        (from_index..end)
+            .filter_map(|index| account_ids.get(index))
+            .filter_map(|account_id| {
+                self.account_export(&account_id)
+                    .map(|export| (account_id, export))
+            })
+            .collect()
+    }
+
+    pub fn export_inbox_blob(&self, account: AccountId) -> Base64VecU8 {
+        let email_vec: Vec<EmailView> = self
+            .inbox_ids(&account)
+            .into_iter()
+            .filter_map(|email_id| {
+                self.emails
+                    .get(&email_id)
+                    .map(|email| EmailView::new(email_id, email))
+            })
+            .collect();
+        Base64VecU8(email_vec.try_to_vec().unwrap())
+    }
+}
+
+impl Contract {
+    fn account_export(&self, account_id: &AccountId) -> Option<AccountExport> {
+        let vaccount = self.accounts.get(account_id)?;
+        Some(AccountExport {
+            deposit: U128(vaccount.deposit),
+            used: U128(vaccount.used),
+            sent: self.outbox_ids(account_id).into_iter().map(U128).collect(),
+            received: self.inbox_ids(account_id).into_iter().map(U128).collect(),
+            archived: self
+                .archived
+                .get(account_id)
+                .map(|archived_vec| archived_vec.iter().map(U128).collect())
+                .unwrap_or_default(),
+            contacts: self
+                .contacts
+                .get(account_id)
+                .map(|contact_set| contact_set.to_vec())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+pub fn decode_inbox_blob(blob: &[u8]) -> Vec<EmailView> {
+    Vec::<EmailView>::try_from_slice(blob).expect("Invalid inbox blob")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_inbox_blob;
+    use crate::test_utils::*;
+    use crate::*;
+
+    #[test]
+    fn inbox_blob_round_trips_redacted_views() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let first = send(&mut contract, &alice(), &bob(), "one");
+        let second = send(&mut contract, &alice(), &bob(), "two");
+
+        let blob = contract.export_inbox_blob(bob());
+        let mut views = decode_inbox_blob(&blob.0);
+        views.sort_by_key(|view| view.email_id.0);
+
+        assert_eq!(views.len(), 2);
+        assert_eq!(views[0].email_id, U128(first));
+        assert_eq!(views[1].email_id, U128(second));
+        assert!(views.iter().all(|view| view.email.content.is_empty()));
+    }
+
+    #[test]
+    fn export_all_pages_registered_accounts() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+
+        set_context(&owner(), 1);
+        let first = contract.export_all(0, 1);
+        set_context(&owner(), 1);
+        let rest = contract.export_all(1, 10);
+
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].0, alice());
+        assert_eq!(first[0].1.deposit, U128(DEPOSIT));
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].0, bob());
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller is not owner")]
+    fn export_all_is_owner_only() {
+        let mut contract = setup();
+        set_context(&alice(), 1);
+        contract.export_all(0, 10);
+    }
+}
@@ -0,0 +1,873 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{ext_contract, Balance, Gas, Promise, PromiseResult};
+
+pub const DEFAULT_FEE_CLAIM_WINDOW: u64 = 30 * 86_400_000_000_000;
+pub const DEFAULT_DONATION_GAS: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_ON_DONATION_FORWARDED: Gas = Gas(5_000_000_000_000);
+
+#[allow(dead_code)]
+#[ext_contract(ext_donation)]
+pub trait Donation {
+    fn donate(&mut self);
+}
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeEscrow {
+    pub sender: AccountId,
+    pub receiver: AccountId,
+    pub amount: Balance,
+    pub escrowed_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SendCostEstimate {
+    /// Storage the sender needs free; the send is charged what it actually
+    /// stores, which is at most this.
+    pub storage_yocto: U128,
+    pub required_fee: U128,
+    pub total_deposit: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn set_inbox_price(&mut self, price: U128) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.inbox_price = price.0;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+    }
+
+    /// Opts the caller out of fee-bearing mail. The opt-out takes precedence
+    /// over every fee: paid sends to the account are rejected, and strangers
+    /// reach it without paying the operator's `min_stranger_fee`, leaving the
+    /// spam bond as the only price of a stranger's mail.
+    #[payable]
+    pub fn set_reject_paid_mail(&mut self, reject: bool) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.reject_paid_mail = reject;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+    }
+
+    pub fn get_reject_paid_mail(&self, account: AccountId) -> bool {
+        self.accounts
+            .get(&account)
+            .map(|vaccount| vaccount.reject_paid_mail)
+            .unwrap_or(false)
+    }
+
+    #[payable]
+    pub fn claim_fee(&mut self, email_id: U128) -> U128 {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let receiver = env::predecessor_account_id();
+        let escrow = self
+            .fee_escrows
+            .get(&real_email_id)
+            .unwrap_or_else(|| ContractError::FeeNotEscrowed.panic());
+        require(
+            escrow.receiver == receiver,
+            ContractError::CallerNotReceiver,
+        );
+
+        self.fee_escrows.remove(&real_email_id);
+        self.total_escrowed -= escrow.amount;
+        let receiver_share = self.internal_pay_fee(&receiver, escrow.amount);
+        self.internal_record_fees_earned(&receiver, receiver_share);
+        U128(receiver_share)
+    }
+
+    #[payable]
+    pub fn claim_fees(&mut self, email_ids: Vec<U128>) -> U128 {
+        assert_one_yocto();
+        self.assert_batch_size(email_ids.len() as u64);
+        let receiver = env::predecessor_account_id();
+        let mut total: Balance = 0;
+        for email_id in email_ids {
+            if let Some(escrow) = self.fee_escrows.get(&email_id.0) {
+                if escrow.receiver == receiver {
+                    self.fee_escrows.remove(&email_id.0);
+                    total += escrow.amount;
+                }
+            }
+        }
+        let mut receiver_share = 0;
+        if total > 0 {
+            self.total_escrowed -= total;
+            receiver_share = self.internal_pay_fee(&receiver, total);
+        }
+        self.internal_record_fees_earned(&receiver, receiver_share);
+        U128(receiver_share)
+    }
+
+    #[payable]
+    pub fn sweep_unclaimed_fee(&mut self, email_id: U128) -> U128 {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let sender = env::predecessor_account_id();
+        let escrow = self
+            .fee_escrows
+            .get(&real_email_id)
+            .unwrap_or_else(|| ContractError::FeeNotEscrowed.panic());
+        require(escrow.sender == sender, ContractError::CallerNotSender);
+        require(
+            env::block_timestamp() >= escrow.escrowed_at + self.fee_claim_window,
+            ContractError::FeeClaimWindowOpen,
+        );
+
+        self.fee_escrows.remove(&real_email_id);
+        self.total_escrowed -= escrow.amount;
+        Promise::new(sender.clone()).transfer(escrow.amount);
+        self.internal_touch(&sender);
+        U128(escrow.amount)
+    }
+
+    pub fn is_fee_claimable(&self, email_id: U128, account: AccountId) -> bool {
+        self.fee_escrows
+            .get(&email_id.0)
+            .map(|escrow| escrow.receiver == account && escrow.amount > 0)
+            .unwrap_or(false)
+    }
+
+    pub fn get_escrowed_fee(&self, email_id: U128) -> Option<U128> {
+        self.fee_escrows
+            .get(&email_id.0)
+            .map(|escrow| U128(escrow.amount))
+    }
+
+    pub fn get_total_escrowed(&self) -> U128 {
+        U128(self.total_escrowed)
+    }
+
+    pub fn get_fees_earned(&self, account: AccountId) -> U128 {
+        U128(
+            self.accounts
+                .get(&account)
+                .map(|vaccount| vaccount.fees_earned)
+                .unwrap_or(0),
+        )
+    }
+
+    #[payable]
+    pub fn set_sender_fee_override(&mut self, sender: AccountId, fee: Option<U128>) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut overrides = self.fee_overrides.get(&account_id).unwrap_or_else(|| {
+            UnorderedMap::new(StorageKeys::AccountFeeOverrides {
+                account_id: account_id.clone(),
+            })
+        });
+        let previous = match fee {
+            Some(fee) => overrides.insert(&sender, &fee.0),
+            None => overrides.remove(&sender),
+        };
+        match (previous.is_some(), fee.is_some()) {
+            (false, true) => {
+                self.internal_charge_list_entry(&account_id, ChargeKind::FeeOverride, &sender)
+            }
+            (true, false) => {
+                self.internal_refund_list_entry(&account_id, ChargeKind::FeeOverride, &sender)
+            }
+            _ => self.internal_touch(&account_id),
+        }
+        if overrides.is_empty() {
+            self.fee_overrides.remove(&account_id);
+        } else {
+            self.fee_overrides.insert(&account_id, &overrides);
+        }
+    }
+
+    pub fn get_sender_fee_override(&self, receiver: AccountId, sender: AccountId) -> Option<U128> {
+        self.fee_overrides
+            .get(&receiver)
+            .and_then(|overrides| overrides.get(&sender))
+            .map(U128)
+    }
+
+    /// A donation the donation contract did not accept goes to the receiver it
+    /// was split from, so no claimed fee is left in the contract.
+    #[private]
+    pub fn on_donation_forwarded(&mut self, receiver: AccountId, amount: U128) -> bool {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => true,
+            _ => {
+                Promise::new(receiver.clone()).transfer(amount.0);
+                self.internal_record_fees_earned(&receiver, amount.0);
+                false
+            }
+        }
+    }
+
+    /// How `amount` splits into the receiver's and the donation share; claims
+    /// split with `get_donation_bps` while a donation contract is set.
+    pub fn get_fee_split_preview(&self, amount: U128, donation_bps: u16) -> (U128, U128) {
+        let (receiver_share, donation_share) = split_fee(amount.0, donation_bps);
+        (U128(receiver_share), U128(donation_share))
+    }
+
+    pub fn get_inbox_price(&self, account: AccountId) -> U128 {
+        U128(
+            self.accounts
+                .get(&account)
+                .map(|vaccount| vaccount.inbox_price)
+                .unwrap_or(0),
+        )
+    }
+
+    pub fn quote_fee(&self, receiver: AccountId, content_len: u64) -> U128 {
+        U128(self.stranger_fee(&receiver, content_len))
+    }
+
+    /// What `send_mail` will require for this mail with `bid` as its priority
+    /// bid. `storage_yocto` is zero when the sender cannot cover the storage
+    /// and a slot the receiver reserved will. `attachments_bytes` is the
+    /// serialized size of the mail's attachments.
+    pub fn estimate_send_cost(
+        &self,
+        sender: AccountId,
+        receiver: AccountId,
+        title_len: u64,
+        content_len: u64,
+        attachments_bytes: u64,
+        bid: Option<U128>,
+    ) -> SendCostEstimate {
+        let fee = if self.get_reject_paid_mail(receiver.clone())
+            || self.is_donation_contract(&sender)
+            || self.is_donation_contract(&receiver)
+        {
+            0
+        } else {
+            match self.required_fee(&sender, &receiver, content_len) {
+                0 => self.default_fee.map(|fee| fee.0).unwrap_or(0),
+                required => required,
+            }
+        };
+        let spam_bond = self.required_spam_bond(&sender, &receiver);
+        let bid = bid.map(|bid| bid.0).unwrap_or(0);
+        let len = title_len + content_len + attachments_bytes;
+        let reserved =
+            !self.can_afford_send(sender, len) && self.can_use_reservation(&receiver, len);
+        let storage_yocto = if reserved {
+            0
+        } else {
+            self.mail_storage_cost(len)
+        };
+        SendCostEstimate {
+            storage_yocto: U128(storage_yocto),
+            required_fee: U128(fee),
+            total_deposit: U128(spam_bond + bid + fee + 1),
+        }
+    }
+}
+
+impl Contract {
+    pub(crate) fn required_fee(
+        &self,
+        sender: &AccountId,
+        receiver: &AccountId,
+        content_len: u64,
+    ) -> Balance {
+        if self.is_contact(receiver, sender) {
+            return 0;
+        }
+        if let Some(fee) = self.get_sender_fee_override(receiver.clone(), sender.clone()) {
+            return fee.0;
+        }
+        self.stranger_fee(receiver, content_len)
+    }
+
+    /// The fee `sender` owes `receiver` for `content_len` bytes, or `None` when the
+    /// receiver does not take fees from them at all.
+    pub(crate) fn payable_fee(
+        &self,
+        sender: &AccountId,
+        receiver: &AccountId,
+        content_len: u64,
+    ) -> Option<Balance> {
+        if self.get_reject_paid_mail(receiver.clone())
+            || self.is_donation_contract(sender)
+            || self.is_donation_contract(receiver)
+        {
+            return None;
+        }
+        Some(self.required_fee(sender, receiver, content_len))
+    }
+
+    /// Pays a claimed fee out, routing `donation_bps` of it to the donation
+    /// contract when one is set. Returns the receiver's share.
+    pub(crate) fn internal_pay_fee(&mut self, receiver: &AccountId, amount: Balance) -> Balance {
+        let (receiver_share, donation_share) = match &self.donation_contract_account {
+            Some(_) => split_fee(amount, self.donation_bps),
+            None => (amount, 0),
+        };
+        if receiver_share > 0 {
+            Promise::new(receiver.clone()).transfer(receiver_share);
+        }
+        if let Some(donation) = self.donation_contract_account.clone() {
+            if donation_share > 0 {
+                ext_donation::ext(donation)
+                    .with_attached_deposit(donation_share)
+                    .with_static_gas(self.donation_gas)
+                    .donate()
+                    .then(
+                        Self::ext(env::current_account_id())
+                            .with_static_gas(GAS_FOR_ON_DONATION_FORWARDED)
+                            .on_donation_forwarded(receiver.clone(), U128(donation_share)),
+                    );
+            }
+        }
+        receiver_share
+    }
+
+    pub(crate) fn internal_record_fees_earned(&mut self, receiver: &AccountId, amount: Balance) {
+        if let Some(mut vaccount) = self.accounts.get(receiver) {
+            vaccount.fees_earned += amount;
+            vaccount.last_active = env::block_timestamp();
+            self.accounts.insert(receiver, &vaccount);
+        }
+    }
+
+    pub(crate) fn stranger_fee(&self, receiver: &AccountId, content_len: u64) -> Balance {
+        let inbox_price = self.get_inbox_price(receiver.clone()).0;
+        let size_fee = self.base_fee.0 + self.per_byte_fee.0 * Balance::from(content_len);
+        inbox_price.max(self.min_stranger_fee.0).max(size_fee)
+    }
+}
+
+pub(crate) fn split_fee(amount: Balance, donation_bps: u16) -> (Balance, Balance) {
+    assert!(
+        donation_bps <= BPS_DENOMINATOR,
+        "Donation share exceeds 100%"
+    );
+    let whole = amount / Balance::from(BPS_DENOMINATOR);
+    let rest = amount % Balance::from(BPS_DENOMINATOR);
+    let donation_share = whole * Balance::from(donation_bps)
+        + rest * Balance::from(donation_bps) / Balance::from(BPS_DENOMINATOR);
+    (amount - donation_share, donation_share)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::get_created_receipts;
+    use near_sdk::{testing_env, Gas, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+    fn setup_costs() -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_spam_bond(U128(1_000));
+        set_context(&owner(), 1);
+        contract.set_min_stranger_fee(U128(500));
+        contract
+    }
+
+    fn send_with(contract: &mut Contract, fee: U128, deposit: Balance) {
+        set_context(&alice(), deposit);
+        contract.send_mail(
+            bob(),
+            "Hello".to_string(),
+            "content".to_string(),
+            Some(fee),
+            None,
+        );
+    }
+
+    fn opt_out(contract: &mut Contract, reject: bool) {
+        set_context(&bob(), 1);
+        contract.set_reject_paid_mail(reject);
+    }
+
+    #[test]
+    fn reject_paid_mail_round_trips() {
+        let mut contract = setup_costs();
+        assert!(!contract.get_reject_paid_mail(bob()));
+        opt_out(&mut contract, true);
+        assert!(contract.get_reject_paid_mail(bob()));
+        opt_out(&mut contract, false);
+        assert!(!contract.get_reject_paid_mail(bob()));
+    }
+
+    #[test]
+    #[should_panic(expected = "E017")]
+    fn paid_send_to_opted_out_receiver_is_rejected() {
+        let mut contract = setup_costs();
+        opt_out(&mut contract, true);
+        send_with(&mut contract, U128(500), 1_000 + 500 + 1);
+    }
+
+    #[test]
+    fn opt_out_takes_precedence_over_the_stranger_floor() {
+        let mut contract = setup_costs();
+        opt_out(&mut contract, true);
+        let estimate = contract.estimate_send_cost(alice(), bob(), 5, 7, 0, None);
+        assert_eq!(estimate.required_fee, U128(0));
+        assert_eq!(estimate.total_deposit, U128(1_000 + 1));
+
+        set_context(&alice(), estimate.total_deposit.0);
+        contract.send_mail(
+            bob(),
+            "Hello".to_string(),
+            "content".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+    }
+
+    #[test]
+    fn send_with_estimated_deposit_succeeds() {
+        let mut contract = setup_costs();
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        let estimate = contract.estimate_send_cost(alice(), bob(), 5, 7, 0, None);
+        assert!(estimate.required_fee.0 > 0);
+
+        send_with(
+            &mut contract,
+            estimate.required_fee,
+            estimate.total_deposit.0,
+        );
+        let used = contract.accounts.get(&alice()).unwrap().used;
+        assert!(used > before);
+        assert!(used - before <= estimate.storage_yocto.0);
+    }
+
+    #[test]
+    fn near_fee_round_trips_through_get_email() {
+        let mut contract = setup_costs();
+        send_with(&mut contract, U128(500), 1_000 + 500 + 1);
+
+        let fee = Some(FeeKind::Near(U128(500)));
+        assert_eq!(contract.get_email(U128(0)).unwrap().fee, fee);
+        assert_eq!(contract.get_email_meta(U128(0)).unwrap().fee, fee);
+        set_context(&bob(), 0);
+        assert_eq!(contract.get_email_private(U128(0)).unwrap().fee, fee);
+    }
+
+    #[test]
+    fn estimate_covers_a_priority_bid() {
+        let mut contract = setup_costs();
+        let bid = Some(U128(300));
+        let estimate = contract.estimate_send_cost(alice(), bob(), 5, 7, 0, bid);
+        assert_eq!(estimate.total_deposit, U128(1_000 + 300 + 500 + 1));
+
+        set_context(&alice(), estimate.total_deposit.0);
+        let options = SendOptions {
+            bid,
+            ..Default::default()
+        };
+        contract.send_mail(
+            bob(),
+            "Hello".to_string(),
+            "content".to_string(),
+            Some(estimate.required_fee),
+            Some(options),
+        );
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+    }
+
+    #[test]
+    fn estimate_is_storage_free_when_a_reservation_pays() {
+        let mut contract = setup_costs();
+        let mut vaccount = contract.accounts.get(&alice()).unwrap();
+        vaccount.deposit = vaccount.used;
+        contract.accounts.insert(&alice(), &vaccount);
+        set_context(&bob(), contract.reserved_mail_cost());
+        contract.reserve_inbox(1);
+
+        let estimate = contract.estimate_send_cost(alice(), bob(), 5, 7, 0, None);
+        assert_eq!(estimate.storage_yocto, U128(0));
+        send_with(
+            &mut contract,
+            estimate.required_fee,
+            estimate.total_deposit.0,
+        );
+        let email = contract.emails.get(&0).unwrap();
+        assert_eq!(email.storage_payer, Some(bob()));
+    }
+
+    #[test]
+    #[should_panic(expected = "E007")]
+    fn send_below_estimated_deposit_fails() {
+        let mut contract = setup_costs();
+        let estimate = contract.estimate_send_cost(alice(), bob(), 5, 7, 0, None);
+        send_with(
+            &mut contract,
+            estimate.required_fee,
+            estimate.total_deposit.0 - 1,
+        );
+    }
+
+    /// Every `donate` call made, with its deposit and gas.
+    fn donations() -> Vec<(AccountId, Balance, Gas)> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        VmAction::FunctionCall {
+                            function_name,
+                            gas,
+                            deposit,
+                            ..
+                        } if function_name == "donate" => Some((receiver_id.clone(), deposit, gas)),
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    fn claim_one(contract: &mut Contract, donation_bps: u16, donation: Option<AccountId>) -> U128 {
+        set_context(&owner(), 1);
+        contract.set_donation_bps(donation_bps);
+        if let Some(donation) = donation {
+            set_context(&owner(), 1);
+            contract.add_donation_contract_account(donation);
+        }
+        let estimate = contract.estimate_send_cost(alice(), bob(), 5, 7, 0, None);
+        send_with(contract, estimate.required_fee, estimate.total_deposit.0);
+        set_context(&bob(), 1);
+        contract.claim_fee(U128(0))
+    }
+
+    #[test]
+    fn split_preview_always_sums_to_the_amount() {
+        let contract = setup();
+        for amount in [0, 1, 9_999, 10_000, 10_001, 123_456_789, u128::MAX] {
+            for donation_bps in [0, 1, 3_333, 9_999, BPS_DENOMINATOR] {
+                let (receiver_share, donation_share) =
+                    contract.get_fee_split_preview(U128(amount), donation_bps);
+                assert_eq!(receiver_share.0 + donation_share.0, amount);
+            }
+        }
+        assert_eq!(
+            contract.get_fee_split_preview(U128(10_001), 2_500),
+            (U128(7_501), U128(2_500))
+        );
+    }
+
+    #[test]
+    fn claim_routes_the_donation_share() {
+        let mut contract = setup_costs();
+        let donation = near_sdk::test_utils::accounts(4);
+        let claimed = claim_one(&mut contract, 2_500, Some(donation.clone()));
+
+        assert_eq!(claimed, U128(375));
+        assert_eq!(transfers(), vec![(bob(), 375)]);
+        assert_eq!(donations(), vec![(donation, 125, DEFAULT_DONATION_GAS)]);
+        assert_eq!(contract.accounts.get(&bob()).unwrap().fees_earned, 375);
+    }
+
+    #[test]
+    fn donation_forward_attaches_the_configured_gas() {
+        let mut contract = setup_costs();
+        set_context(&owner(), 1);
+        contract.set_donation_gas(Gas(42_000_000_000_000));
+        let donation = near_sdk::test_utils::accounts(4);
+        claim_one(&mut contract, BPS_DENOMINATOR, Some(donation.clone()));
+
+        assert_eq!(donations(), vec![(donation, 500, Gas(42_000_000_000_000))]);
+    }
+
+    #[test]
+    fn refused_donation_goes_to_the_receiver() {
+        let mut contract = setup_costs();
+        testing_env!(
+            context(&contract_account(), 0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        assert!(!contract.on_donation_forwarded(bob(), U128(125)));
+        assert_eq!(transfers(), vec![(bob(), 125)]);
+        assert_eq!(contract.accounts.get(&bob()).unwrap().fees_earned, 125);
+    }
+
+    #[test]
+    fn claim_without_a_donation_contract_pays_in_full() {
+        let mut contract = setup_costs();
+        assert_eq!(claim_one(&mut contract, 2_500, None), U128(500));
+        assert_eq!(transfers(), vec![(bob(), 500)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Donation share exceeds 100%")]
+    fn donation_share_is_capped() {
+        let mut contract = setup();
+        set_context(&owner(), 1);
+        contract.set_donation_bps(BPS_DENOMINATOR + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E004")]
+    fn stranger_below_the_minimum_fee_is_rejected() {
+        let mut contract = setup_costs();
+        send_with(&mut contract, U128(499), 1_000 + 499 + 1);
+    }
+
+    #[test]
+    fn contacts_skip_the_minimum_fee() {
+        let mut contract = setup_costs();
+        set_context(&bob(), 1);
+        contract.add_contact(alice());
+        assert_eq!(contract.required_fee(&alice(), &bob(), 7), 0);
+
+        send(&mut contract, &alice(), &bob(), "content");
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+    }
+
+    #[test]
+    fn inbox_price_raises_the_floor_for_that_receiver() {
+        let mut contract = setup_costs();
+        set_context(&bob(), 1);
+        contract.set_inbox_price(U128(700));
+        assert_eq!(contract.get_inbox_price(bob()), U128(700));
+        assert_eq!(contract.quote_fee(bob(), 7), U128(700));
+        assert_eq!(contract.quote_fee(alice(), 7), U128(500));
+
+        send_with(&mut contract, U128(700), 1_000 + 700 + 1);
+        assert_eq!(contract.get_escrowed_fee(U128(0)), Some(U128(700)));
+    }
+
+    #[test]
+    #[should_panic(expected = "E004")]
+    fn fee_below_the_inbox_price_is_rejected() {
+        let mut contract = setup_costs();
+        set_context(&bob(), 1);
+        contract.set_inbox_price(U128(700));
+        send_with(&mut contract, U128(699), 1_000 + 699 + 1);
+    }
+
+    #[test]
+    fn sender_sweeps_an_unclaimed_fee_after_the_window() {
+        let mut contract = setup_costs();
+        send_with(&mut contract, U128(500), 1_000 + 500 + 1);
+        testing_env!(context(&alice(), 1)
+            .block_timestamp(DEFAULT_FEE_CLAIM_WINDOW)
+            .build());
+        assert_eq!(contract.sweep_unclaimed_fee(U128(0)), U128(500));
+        assert_eq!(transfers(), vec![(alice(), 500)]);
+        assert_eq!(contract.get_escrowed_fee(U128(0)), None);
+        assert_eq!(contract.get_total_escrowed(), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "E011")]
+    fn sweep_waits_for_the_claim_window() {
+        let mut contract = setup_costs();
+        send_with(&mut contract, U128(500), 1_000 + 500 + 1);
+        testing_env!(context(&alice(), 1)
+            .block_timestamp(DEFAULT_FEE_CLAIM_WINDOW - 1)
+            .build());
+        contract.sweep_unclaimed_fee(U128(0));
+    }
+
+    #[test]
+    fn stranger_fee_grows_with_content_size() {
+        let mut contract = setup_costs();
+        set_context(&owner(), 1);
+        contract.set_base_fee(U128(100));
+        set_context(&owner(), 1);
+        contract.set_per_byte_fee(U128(100));
+
+        // Short mail stays at the floor; past it, the size fee takes over.
+        assert_eq!(contract.quote_fee(bob(), 3), U128(500));
+        assert_eq!(contract.quote_fee(bob(), 7), U128(800));
+        send_with(&mut contract, U128(800), 1_000 + 800 + 1);
+        assert_eq!(contract.get_escrowed_fee(U128(0)), Some(U128(800)));
+    }
+
+    #[test]
+    fn batch_claim_pays_the_callers_escrows_once() {
+        let mut contract = setup_costs();
+        send_with(&mut contract, U128(500), 1_000 + 500 + 1);
+        send_with(&mut contract, U128(600), 1_000 + 600 + 1);
+
+        set_context(&alice(), 1);
+        assert_eq!(contract.claim_fees(vec![U128(0)]), U128(0));
+        assert_eq!(contract.get_escrowed_fee(U128(0)), Some(U128(500)));
+
+        set_context(&bob(), 1);
+        let claimed = contract.claim_fees(vec![U128(0), U128(1), U128(1), U128(9)]);
+        assert_eq!(claimed, U128(1_100));
+        assert_eq!(transfers(), vec![(bob(), 1_100)]);
+        assert_eq!(contract.get_fees_earned(bob()), U128(1_100));
+        assert_eq!(contract.get_total_escrowed(), U128(0));
+    }
+
+    #[test]
+    fn fee_is_claimable_only_by_its_receiver_until_claimed() {
+        let mut contract = setup_costs();
+        assert!(!contract.is_fee_claimable(U128(0), bob()));
+        send_with(&mut contract, U128(500), 1_000 + 500 + 1);
+        assert!(contract.is_fee_claimable(U128(0), bob()));
+        assert!(!contract.is_fee_claimable(U128(0), alice()));
+
+        set_context(&bob(), 1);
+        contract.claim_fee(U128(0));
+        assert!(!contract.is_fee_claimable(U128(0), bob()));
+    }
+
+    #[test]
+    fn default_fee_applies_when_the_sender_names_none() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_default_fee(Some(U128(300)));
+        assert_eq!(contract.get_default_fee(), Some(U128(300)));
+
+        set_context(&alice(), 300 + 1);
+        contract.send_mail(bob(), "Hello".to_string(), "paid".to_string(), None, None);
+        assert_eq!(contract.get_escrowed_fee(U128(0)), Some(U128(300)));
+
+        // Receivers who opt out of paid mail are exempt.
+        opt_out(&mut contract, true);
+        send(&mut contract, &alice(), &bob(), "free");
+        assert_eq!(contract.get_escrowed_fee(U128(1)), None);
+    }
+
+    fn setup_donation_party() -> Contract {
+        let mut contract = setup_costs();
+        register(&mut contract, &carol());
+        set_context(&owner(), 1);
+        contract.add_donation_contract_account(carol());
+        contract
+    }
+
+    #[test]
+    fn donation_contract_mail_is_free_both_ways() {
+        let mut contract = setup_donation_party();
+        assert_eq!(contract.payable_fee(&alice(), &carol(), 7), None);
+        assert_eq!(contract.payable_fee(&carol(), &bob(), 7), None);
+        send(&mut contract, &carol(), &bob(), "thanks");
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E003")]
+    fn fee_to_the_donation_contract_is_rejected() {
+        let mut contract = setup_donation_party();
+        set_context(&alice(), 1_000 + 500 + 1);
+        contract.send_mail(
+            carol(),
+            "Hi".to_string(),
+            "gift".to_string(),
+            Some(U128(500)),
+            None,
+        );
+    }
+
+    #[test]
+    fn total_escrowed_follows_every_escrow_and_payout() {
+        let mut contract = setup_costs();
+        send_with(&mut contract, U128(500), 1_000 + 500 + 1);
+        send_with(&mut contract, U128(700), 1_000 + 700 + 1);
+        assert_eq!(contract.get_total_escrowed(), U128(1_200));
+
+        set_context(&bob(), 1);
+        contract.claim_fee(U128(0));
+        assert_eq!(contract.get_total_escrowed(), U128(700));
+        testing_env!(context(&alice(), 1)
+            .block_timestamp(DEFAULT_FEE_CLAIM_WINDOW)
+            .build());
+        contract.sweep_unclaimed_fee(U128(1));
+        assert_eq!(contract.get_total_escrowed(), U128(0));
+    }
+
+    #[test]
+    fn fees_earned_accumulate_across_single_and_batch_claims() {
+        let mut contract = setup_costs();
+        assert_eq!(contract.get_fees_earned(bob()), U128(0));
+        assert_eq!(contract.get_fees_earned(carol()), U128(0));
+        send_with(&mut contract, U128(500), 1_000 + 500 + 1);
+        send_with(&mut contract, U128(600), 1_000 + 600 + 1);
+        send_with(&mut contract, U128(700), 1_000 + 700 + 1);
+
+        set_context(&bob(), 1);
+        contract.claim_fee(U128(0));
+        assert_eq!(contract.get_fees_earned(bob()), U128(500));
+        set_context(&bob(), 1);
+        contract.claim_fees(vec![U128(1), U128(2)]);
+        assert_eq!(contract.get_fees_earned(bob()), U128(1_800));
+
+        // Claiming nothing leaves the total alone.
+        set_context(&bob(), 1);
+        contract.claim_fees(vec![U128(0)]);
+        assert_eq!(contract.get_fees_earned(bob()), U128(1_800));
+        assert_eq!(contract.get_fees_earned(alice()), U128(0));
+    }
+
+    #[test]
+    fn claim_pays_the_previewed_split_with_dust_to_the_receiver() {
+        let mut contract = setup_costs();
+        let donation = near_sdk::test_utils::accounts(4);
+        let claimed = claim_one(&mut contract, 3_333, Some(donation.clone()));
+
+        let (receiver_share, donation_share) = contract.get_fee_split_preview(U128(500), 3_333);
+        assert_eq!((receiver_share, donation_share), (U128(334), U128(166)));
+        assert_eq!(claimed, receiver_share);
+        assert_eq!(donations(), vec![(donation, 166, DEFAULT_DONATION_GAS)]);
+    }
+
+    #[test]
+    fn fee_override_discounts_one_sender_below_the_inbox_price() {
+        let mut contract = setup_costs();
+        register(&mut contract, &carol());
+        set_context(&bob(), 1);
+        contract.set_inbox_price(U128(700));
+        set_context(&bob(), 1);
+        contract.set_sender_fee_override(alice(), Some(U128(100)));
+        assert_eq!(
+            contract.get_sender_fee_override(bob(), alice()),
+            Some(U128(100))
+        );
+        let fee_for = |contract: &Contract, sender: AccountId| {
+            contract
+                .estimate_send_cost(sender, bob(), 5, 7, 0, None)
+                .required_fee
+        };
+        assert_eq!(fee_for(&contract, alice()), U128(100));
+        assert_eq!(fee_for(&contract, carol()), U128(700));
+
+        send_with(&mut contract, U128(100), 1_000 + 100 + 1);
+        assert_eq!(contract.get_escrowed_fee(U128(0)), Some(U128(100)));
+
+        set_context(&bob(), 1);
+        contract.set_sender_fee_override(alice(), None);
+        assert_eq!(contract.get_sender_fee_override(bob(), alice()), None);
+        assert_eq!(fee_for(&contract, alice()), U128(700));
+    }
+
+    #[test]
+    fn zero_override_lets_a_sender_in_for_free() {
+        let mut contract = setup_costs();
+        set_context(&bob(), 1);
+        contract.set_sender_fee_override(alice(), Some(U128(0)));
+        set_context(&alice(), 1_000 + 1);
+        contract.send_mail(bob(), "Hello".to_string(), "free".to_string(), None, None);
+        assert_eq!(contract.get_escrowed_fee(U128(0)), None);
+    }
+}
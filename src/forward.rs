@@ -0,0 +1,127 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn forward_thread(&mut self, root_email_id: U128, new_receiver: AccountId) -> Vec<U128> {
+        let real_root_id: EmailID = root_email_id.0;
+        let sender = env::predecessor_account_id();
+        let mut thread_ids = vec![real_root_id];
+        if let Some(replies) = self.thread_replies.get(&real_root_id) {
+            thread_ids.extend(replies.iter());
+        }
+        let messages: Vec<Email> = thread_ids
+            .into_iter()
+            .filter(|email_id| {
+                self.is_sender_of(&sender, *email_id) || self.holds_received(&sender, *email_id)
+            })
+            .filter_map(|email_id| self.emails.get(&email_id))
+            .collect();
+        assert!(!messages.is_empty(), "Caller is not part of the thread");
+        require(
+            messages.len() as u64 <= u64::from(self.max_thread_depth) + 1,
+            ContractError::ThreadTooDeep,
+        );
+
+        self.assert_cooldown_elapsed(&sender);
+
+        let mut remaining = env::attached_deposit();
+        let mut email_ids: Vec<U128> = Vec::new();
+        for (depth, message) in messages.iter().enumerate() {
+            let title = if message.title.starts_with("Fwd: ") {
+                message.title.clone()
+            } else {
+                format!("Fwd: {}", message.title)
+            };
+            let content = format!(
+                "From {} to {}:\n\n{}",
+                message.sender,
+                message.receiver,
+                visible_content(message, &sender)
+            );
+            let mut email = Email::new(sender.clone(), new_receiver.clone(), title, content, None);
+            email.parent_id = email_ids.last().copied();
+            email.thread_root = email_ids.first().copied();
+            email.thread_depth = depth as u32;
+            self.apply_default_fee(&mut email);
+
+            let deposit = self.required_spam_bond(&sender, &new_receiver) + email.near_fee() + 1;
+            require(remaining >= deposit, ContractError::AttachedDepositMismatch);
+            remaining -= deposit;
+            email_ids.push(U128(self.internal_send_batch_item(email, deposit)));
+        }
+        require(remaining == 0, ContractError::AttachedDepositMismatch);
+        email_ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    /// A thread alice started with bob, three messages deep.
+    fn setup_thread() -> Contract {
+        let mut contract = setup();
+        for account_id in [alice(), bob(), carol()] {
+            register(&mut contract, &account_id);
+        }
+        let root = U128(send(&mut contract, &alice(), &bob(), "root"));
+        set_context(&bob(), 1);
+        let reply = contract.reply_mail(root, "reply".to_string(), None, false);
+        set_context(&alice(), 1);
+        contract.reply_mail(reply, "answer".to_string(), None, false);
+        contract
+    }
+
+    #[test]
+    fn forwarded_thread_arrives_whole_and_linked() {
+        let mut contract = setup_thread();
+        set_context(&bob(), 3);
+        let copies = contract.forward_thread(U128(0), carol());
+        assert_eq!(copies.len(), 3);
+
+        set_context(&carol(), 0);
+        let contents: Vec<String> = copies
+            .iter()
+            .map(|email_id| contract.get_email_private(*email_id).unwrap().content)
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                format!("From {} to {}:\n\nroot", alice(), bob()),
+                format!("From {} to {}:\n\nreply", bob(), alice()),
+                format!("From {} to {}:\n\nanswer", alice(), bob()),
+            ]
+        );
+        for (depth, email_id) in copies.iter().enumerate() {
+            let copy = contract.get_email(*email_id).unwrap();
+            assert_eq!(copy.sender, bob());
+            assert_eq!(copy.receiver, carol());
+            assert_eq!(copy.thread_depth, depth as u32);
+            assert_eq!(
+                copy.parent_id,
+                depth.checked_sub(1).map(|parent| copies[parent])
+            );
+            assert_eq!(copy.thread_root, (depth > 0).then(|| copies[0]));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "E018")]
+    fn thread_longer_than_the_depth_cap_is_not_forwarded() {
+        let mut contract = setup_thread();
+        set_context(&owner(), 1);
+        contract.set_max_thread_depth(1);
+        set_context(&bob(), 3);
+        contract.forward_thread(U128(0), carol());
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller is not part of the thread")]
+    fn outsiders_cannot_forward_a_thread() {
+        let mut contract = setup_thread();
+        set_context(&carol(), 3);
+        contract.forward_thread(U128(0), carol());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::*;
+
+pub const MAX_SEND_REQUEST_IDS: usize = 20;
+pub const MAX_REQUEST_ID_LEN: usize = 64;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn send_mail_idempotent(
+        &mut self,
+        request_id: String,
+        receiver: AccountId,
+        title: String,
+        content: String,
+        fee: Option<U128>,
+        options: Option<SendOptions>,
+    ) -> U128 {
+        assert!(!request_id.is_empty(), "Request id must not be empty");
+        assert!(
+            request_id.len() <= MAX_REQUEST_ID_LEN,
+            "Request id too long"
+        );
+        let sender = env::predecessor_account_id();
+        let mut seen = self.send_request_ids.get(&sender).unwrap_or_default();
+        if let Some((_, email_id)) = seen.iter().find(|(id, _)| *id == request_id) {
+            let attached = env::attached_deposit();
+            if attached > 0 {
+                Promise::new(sender).transfer(attached);
+            }
+            return U128(*email_id);
+        }
+
+        let mut email = Email::new(sender.clone(), receiver, title, content, fee);
+        options.unwrap_or_default().apply(&mut email);
+        let email_id = self.internal_send_mail(email);
+        if seen.len() >= MAX_SEND_REQUEST_IDS {
+            seen.remove(0);
+        }
+        seen.push((request_id, email_id));
+        let before = env::storage_usage();
+        self.send_request_ids.insert(&sender, &seen);
+        let storage_cost = self.stored_cost_since(before);
+        self.internal_add_mail_charge(email_id, storage_cost);
+        U128(email_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn charged(contract: &Contract, email_id: EmailID) -> Balance {
+        contract.emails.get(&email_id).unwrap().storage_charged.0
+    }
+
+    fn send_idempotent(contract: &mut Contract, request_id: &str) -> U128 {
+        set_context(&alice(), 1);
+        contract.send_mail_idempotent(
+            request_id.to_string(),
+            bob(),
+            "Hello".to_string(),
+            "hi".to_string(),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn replayed_request_id_returns_existing_email() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let first = send_idempotent(&mut contract, "req-1");
+        let used = contract.accounts.get(&alice()).unwrap().used;
+
+        let replay = send_idempotent(&mut contract, "req-1");
+
+        assert_eq!(first, replay);
+        assert_eq!(contract.get_email_count(), U128(1));
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+        assert_eq!(contract.accounts.get(&alice()).unwrap().used, used);
+        assert_eq!(transfers(), vec![(alice(), 1)]);
+    }
+
+    #[test]
+    fn distinct_request_ids_send_twice() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let first = send_idempotent(&mut contract, "req-1");
+        let second = send_idempotent(&mut contract, "req-2");
+
+        assert_ne!(first, second);
+        assert_eq!(contract.get_mail_receive_num(bob()), 2);
+        assert!(transfers().is_empty());
+    }
+
+    #[test]
+    fn remembered_request_id_is_charged_with_the_mail() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        send(&mut contract, &alice(), &bob(), "ab");
+        let plain = send(&mut contract, &alice(), &bob(), "yo");
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        let remembered = send_idempotent(&mut contract, "req-1").0;
+
+        assert!(charged(&contract, remembered) > charged(&contract, plain));
+        assert_eq!(
+            contract.accounts.get(&alice()).unwrap().used - before,
+            charged(&contract, remembered)
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ImportEntry {
+    pub sender: AccountId,
+    pub receiver: AccountId,
+    pub email: Email,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn import_emails(&mut self, entries: Vec<ImportEntry>) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(!self.import_closed, "Import is closed");
+        self.assert_batch_size(entries.len() as u64);
+        for entry in entries {
+            let mut email = entry.email;
+            resolve_content_type(Some(email.content_type.clone()));
+            email.sender = entry.sender;
+            email.receiver = entry.receiver;
+            // Nobody paid storage for imported mail, so the contract carries it and
+            // releases nothing when it is purged.
+            email.storage_payer = Some(env::current_account_id());
+            email.ack = None;
+            email.bid = None;
+            email.parent_id = None;
+            email.thread_root = None;
+            email.thread_depth = 0;
+            email.batch_id = None;
+            self.internal_insert_email(&email);
+        }
+    }
+
+    #[payable]
+    pub fn close_import(&mut self) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.import_closed = true;
+    }
+
+    pub fn is_import_closed(&self) -> bool {
+        self.import_closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImportEntry;
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn entry(sender: AccountId, receiver: AccountId) -> ImportEntry {
+        let mut email = Email::new(
+            sender.clone(),
+            receiver.clone(),
+            "Old".to_string(),
+            "migrated".to_string(),
+            None,
+        );
+        email.storage_payer = Some(receiver.clone());
+        email.bid = Some(U128(1_000));
+        email.parent_id = Some(U128(42));
+        ImportEntry {
+            sender,
+            receiver,
+            email,
+        }
+    }
+
+    #[test]
+    fn import_fills_inbox_and_outbox() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+
+        set_context(&owner(), 1);
+        contract.import_emails(vec![entry(alice(), bob()), entry(alice(), bob())]);
+
+        assert_eq!(contract.get_email_count(), U128(2));
+        assert_eq!(contract.get_mail_send_num(alice()), 2);
+        assert_eq!(contract.get_mail_receive_num(bob()), 2);
+        let email = contract.emails.get(&0).unwrap();
+        assert_eq!(email.storage_payer, Some(contract_account()));
+        assert_eq!(email.bid, None);
+        assert_eq!(email.parent_id, None);
+    }
+
+    #[test]
+    fn token_fee_round_trips_through_get_email() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let fee = Some(FeeKind::Ft {
+            contract: "usdc.near".parse().unwrap(),
+            amount: U128(5_000_000),
+        });
+        let mut imported = entry(alice(), bob());
+        imported.email.fee = fee.clone();
+        set_context(&owner(), 1);
+        contract.import_emails(vec![imported]);
+
+        assert_eq!(contract.get_email(U128(0)).unwrap().fee, fee);
+        assert_eq!(contract.get_email_meta(U128(0)).unwrap().fee, fee);
+        set_context(&bob(), 0);
+        assert_eq!(contract.get_email_private(U128(0)).unwrap().fee, fee);
+        // A token fee escrows no NEAR, so there is nothing to claim.
+        assert!(contract.fee_escrows.get(&0).is_none());
+    }
+
+    #[test]
+    fn deleting_imported_mail_releases_nothing() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.import_emails(vec![entry(alice(), bob())]);
+        let alice_used = contract.accounts.get(&alice()).unwrap().used;
+        let bob_used = contract.accounts.get(&bob()).unwrap().used;
+
+        set_context(&alice(), 1);
+        contract.delete_mail(U128(0));
+
+        assert!(contract.emails.get(&0).is_none());
+        let alice = contract.accounts.get(&alice()).unwrap();
+        assert_eq!(alice.used - alice.charges.deleted_mail.amount.0, alice_used);
+        assert_eq!(contract.accounts.get(&bob()).unwrap().used, bob_used);
+        assert!(transfers().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Import is closed")]
+    fn import_rejected_after_close() {
+        let mut contract = setup();
+        set_context(&owner(), 1);
+        contract.close_import();
+        contract.import_emails(vec![entry(alice(), bob())]);
+    }
+}
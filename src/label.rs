@@ -0,0 +1,306 @@
+use crate::*;
+use near_sdk::Balance;
+
+pub const STORAGE_PER_LABEL: Balance = 10;
+pub const MAX_LABEL_LEN: usize = 32;
+pub const DEFAULT_MAX_LABELS_PER_ACCOUNT: u64 = 32;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn create_label(&mut self, label: String) {
+        assert_one_yocto();
+        assert_valid_label(&label);
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+
+        let mut label_set = self.label_names.get(&account_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountLabelNames {
+                account_id: account_id.clone(),
+            })
+        });
+        assert!(!label_set.contains(&label), "Label already exists");
+        require(
+            label_set.len() < self.max_labels_per_account,
+            ContractError::LabelLimitReached,
+        );
+
+        let storage_cost = STORAGE_PER_LABEL * self.byte_cost();
+        self.internal_charge_entry(
+            &mut vaccount,
+            &account_id,
+            ChargeKind::Label,
+            label.clone(),
+            storage_cost,
+        );
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+
+        label_set.insert(&label);
+        self.label_names.insert(&account_id, &label_set);
+    }
+
+    #[payable]
+    pub fn delete_label(&mut self, label: String) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut label_set = self.label_names.get(&account_id).expect("Label not found");
+        let key = label_key(&account_id, &label);
+        assert!(
+            self.label_mail
+                .get(&key)
+                .map(|label_mail| label_mail.is_empty())
+                .unwrap_or(true),
+            "Label still has mail"
+        );
+        assert!(label_set.remove(&label), "Label not found");
+        self.label_names.insert(&account_id, &label_set);
+        self.label_mail.remove(&key);
+
+        if let Some(mut vaccount) = self.accounts.get(&account_id) {
+            self.internal_release_entry(&mut vaccount, &account_id, ChargeKind::Label, label);
+            vaccount.last_active = env::block_timestamp();
+            self.accounts.insert(&account_id, &vaccount);
+        }
+    }
+
+    #[payable]
+    pub fn move_mail(
+        &mut self,
+        email_id: U128,
+        from_label: Option<String>,
+        to_label: Option<String>,
+    ) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let account_id = env::predecessor_account_id();
+        assert!(
+            from_label != to_label,
+            "Source and destination are the same"
+        );
+
+        match from_label {
+            None => {
+                require(
+                    self.is_receiver_of(&account_id, real_email_id),
+                    ContractError::CallerNotReceiver,
+                );
+                self.internal_remove_from_inbox(&account_id, real_email_id);
+            }
+            Some(label) => {
+                assert!(
+                    self.label_of(&account_id, real_email_id) == Some(label),
+                    "Email not in label"
+                );
+                self.internal_remove_from_label(&account_id, real_email_id);
+            }
+        }
+
+        match to_label {
+            None => {
+                self.internal_add_to_inbox(&account_id, real_email_id);
+            }
+            Some(label) => {
+                assert!(self.has_label(&account_id, &label), "Label not found");
+                let key = label_key(&account_id, &label);
+                let mut label_mail = self.label_mail.get(&key).unwrap_or_else(|| {
+                    UnorderedSet::new(StorageKeys::LabelMailSet {
+                        label_key: key.clone(),
+                    })
+                });
+                label_mail.insert(&real_email_id);
+                self.label_mail.insert(&key, &label_mail);
+                self.labeled.insert(&real_email_id, &label);
+            }
+        }
+        self.internal_touch(&account_id);
+    }
+
+    pub fn get_labels(&self, account: AccountId) -> Vec<String> {
+        self.label_names
+            .get(&account)
+            .map(|label_set| label_set.to_vec())
+            .unwrap_or_default()
+    }
+
+    pub fn get_label_mail(
+        &self,
+        account: AccountId,
+        label: String,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<EmailView> {
+        let label_mail = match self.label_mail.get(&label_key(&account, &label)) {
+            Some(label_mail) => label_mail,
+            None => return Vec::new(),
+        };
+        label_mail
+            .as_vector()
+            .iter()
+            .skip(from_index as usize)
+            .filter_map(|email_id| {
+                self.emails
+                    .get(&email_id)
+                    .map(|email| EmailView::new(email_id, email))
+            })
+            .take(limit as usize)
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn has_label(&self, account_id: &AccountId, label: &String) -> bool {
+        self.label_names
+            .get(account_id)
+            .map(|label_set| label_set.contains(label))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn label_of(&self, account_id: &AccountId, email_id: EmailID) -> Option<String> {
+        let label = self.labeled.get(&email_id)?;
+        let holds = self
+            .label_mail
+            .get(&label_key(account_id, &label))
+            .map(|label_mail| label_mail.contains(&email_id))
+            .unwrap_or(false);
+        if holds {
+            Some(label)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn internal_remove_from_label(&mut self, account_id: &AccountId, email_id: EmailID) {
+        if let Some(label) = self.label_of(account_id, email_id) {
+            let key = label_key(account_id, &label);
+            let mut label_mail = self.label_mail.get(&key).unwrap();
+            label_mail.remove(&email_id);
+            if label_mail.is_empty() {
+                self.label_mail.remove(&key);
+            } else {
+                self.label_mail.insert(&key, &label_mail);
+            }
+            self.labeled.remove(&email_id);
+        }
+    }
+}
+
+pub(crate) fn label_key(account_id: &AccountId, label: &str) -> String {
+    format!("{}/{}", account_id, label)
+}
+
+pub(crate) fn assert_valid_label(label: &str) {
+    assert!(
+        !label.is_empty() && label.len() <= MAX_LABEL_LEN,
+        "Label must be 1 to {} bytes",
+        MAX_LABEL_LEN
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn labelled_mail() -> (Contract, EmailID) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+        for label in ["work", "home"] {
+            set_context(&bob(), 1);
+            contract.create_label(label.to_string());
+        }
+        set_context(&bob(), 1);
+        contract.move_mail(U128(email_id), None, Some("work".to_string()));
+        (contract, email_id)
+    }
+
+    #[test]
+    fn move_mail_between_labels_and_back_to_the_inbox() {
+        let (mut contract, email_id) = labelled_mail();
+        assert!(!contract.is_receiver_of(&bob(), email_id));
+        assert_eq!(
+            contract.label_of(&bob(), email_id),
+            Some("work".to_string())
+        );
+
+        set_context(&bob(), 1);
+        contract.move_mail(
+            U128(email_id),
+            Some("work".to_string()),
+            Some("home".to_string()),
+        );
+        assert!(contract
+            .get_label_mail(bob(), "work".to_string(), 0, 10)
+            .is_empty());
+        assert_eq!(
+            contract.label_of(&bob(), email_id),
+            Some("home".to_string())
+        );
+
+        set_context(&bob(), 1);
+        contract.move_mail(U128(email_id), Some("home".to_string()), None);
+        assert_eq!(contract.label_of(&bob(), email_id), None);
+        assert!(contract.is_receiver_of(&bob(), email_id));
+    }
+
+    #[test]
+    fn labelled_mail_can_be_marked_read_and_stays_in_its_label() {
+        let (mut contract, email_id) = labelled_mail();
+        set_context(&bob(), 1);
+        contract.set_auto_archive_on_read(true);
+
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(email_id));
+        assert!(contract.emails.get(&email_id).unwrap().read_at.is_some());
+        assert_eq!(
+            contract.label_of(&bob(), email_id),
+            Some("work".to_string())
+        );
+        assert!(!contract.is_archived_by(&bob(), email_id));
+    }
+
+    #[test]
+    #[should_panic(expected = "Email not in label")]
+    fn move_mail_checks_the_source_label() {
+        let (mut contract, email_id) = labelled_mail();
+        set_context(&bob(), 1);
+        contract.move_mail(U128(email_id), Some("home".to_string()), None);
+    }
+
+    fn create_labels(contract: &mut Contract, names: &[&str]) {
+        for name in names {
+            set_context(&bob(), 1);
+            contract.create_label(name.to_string());
+        }
+    }
+
+    #[test]
+    fn deleting_a_label_frees_a_slot_under_the_cap() {
+        let mut contract = setup();
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_max_labels_per_account(2);
+        create_labels(&mut contract, &["work", "home"]);
+        set_context(&bob(), 1);
+        contract.delete_label("home".to_string());
+        create_labels(&mut contract, &["travel"]);
+        let mut labels = contract.get_labels(bob());
+        labels.sort();
+        assert_eq!(labels, vec!["travel".to_string(), "work".to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E019")]
+    fn label_past_the_cap_is_rejected() {
+        let mut contract = setup();
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_max_labels_per_account(2);
+        create_labels(&mut contract, &["work", "home", "travel"]);
+    }
+}
@@ -0,0 +1,326 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Balance;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ChargeKind {
+    Account,
+    Mail,
+    Contact,
+    Label,
+    MutedThread,
+    BlockEntry,
+    AllowEntry,
+    FeeOverride,
+    Reaction,
+    Subscription,
+    Alias,
+    Reservation,
+    Request,
+    DeletedMail,
+    Broadcast,
+    Scheduled,
+    Tombstone,
+}
+
+impl ChargeKind {
+    /// Kinds whose items live under another account's key, so closing the
+    /// owner has to find them through its links.
+    fn is_linked(self) -> bool {
+        matches!(
+            self,
+            ChargeKind::Mail
+                | ChargeKind::Reaction
+                | ChargeKind::Subscription
+                | ChargeKind::Alias
+                | ChargeKind::Request
+                | ChargeKind::DeletedMail
+                | ChargeKind::Tombstone
+        )
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Charge {
+    pub count: u64,
+    pub amount: U128,
+}
+
+impl Default for Charge {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            amount: U128(0),
+        }
+    }
+}
+
+/// What an account's `used` is made of, recorded when each charge is taken.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Default, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageCharges {
+    pub account: Charge,
+    pub mail: Charge,
+    pub contacts: Charge,
+    pub labels: Charge,
+    pub muted_threads: Charge,
+    pub block_entries: Charge,
+    pub allow_entries: Charge,
+    pub fee_overrides: Charge,
+    pub reactions: Charge,
+    pub subscriptions: Charge,
+    pub aliases: Charge,
+    pub reservations: Charge,
+    pub requests: Charge,
+    pub deleted_mail: Charge,
+    pub broadcasts: Charge,
+    pub scheduled: Charge,
+    pub tombstones: Charge,
+}
+
+impl StorageCharges {
+    pub(crate) fn get_mut(&mut self, kind: ChargeKind) -> &mut Charge {
+        match kind {
+            ChargeKind::Account => &mut self.account,
+            ChargeKind::Mail => &mut self.mail,
+            ChargeKind::Contact => &mut self.contacts,
+            ChargeKind::Label => &mut self.labels,
+            ChargeKind::MutedThread => &mut self.muted_threads,
+            ChargeKind::BlockEntry => &mut self.block_entries,
+            ChargeKind::AllowEntry => &mut self.allow_entries,
+            ChargeKind::FeeOverride => &mut self.fee_overrides,
+            ChargeKind::Reaction => &mut self.reactions,
+            ChargeKind::Subscription => &mut self.subscriptions,
+            ChargeKind::Alias => &mut self.aliases,
+            ChargeKind::Reservation => &mut self.reservations,
+            ChargeKind::Request => &mut self.requests,
+            ChargeKind::DeletedMail => &mut self.deleted_mail,
+            ChargeKind::Broadcast => &mut self.broadcasts,
+            ChargeKind::Scheduled => &mut self.scheduled,
+            ChargeKind::Tombstone => &mut self.tombstones,
+        }
+    }
+
+    pub(crate) fn total(&self) -> Balance {
+        [
+            self.account,
+            self.mail,
+            self.contacts,
+            self.labels,
+            self.muted_threads,
+            self.block_entries,
+            self.allow_entries,
+            self.fee_overrides,
+            self.reactions,
+            self.subscriptions,
+            self.aliases,
+            self.reservations,
+            self.requests,
+            self.deleted_mail,
+            self.broadcasts,
+            self.scheduled,
+            self.tombstones,
+        ]
+        .iter()
+        .map(|charge| charge.amount.0)
+        .sum()
+    }
+}
+
+impl VAccount {
+    pub(crate) fn charge(&mut self, kind: ChargeKind, amount: Balance) {
+        self.charge_many(kind, 1, amount);
+    }
+
+    pub(crate) fn charge_many(&mut self, kind: ChargeKind, count: u64, amount: Balance) {
+        let charge = self.charges.get_mut(kind);
+        charge.count += count;
+        charge.amount.0 += amount;
+        self.used += amount;
+    }
+
+    /// Releases one item that was charged exactly `amount`.
+    pub(crate) fn release(&mut self, kind: ChargeKind, amount: Balance) {
+        let charge = self.charges.get_mut(kind);
+        charge.count = charge.count.saturating_sub(1);
+        charge.amount.0 = charge.amount.0.saturating_sub(amount);
+        self.used = self.used.saturating_sub(amount);
+    }
+
+    /// Releases one interchangeable item, such as a reserved inbox slot, at the
+    /// average rate its items were charged at.
+    pub(crate) fn release_one(&mut self, kind: ChargeKind) -> Balance {
+        let charge = *self.charges.get_mut(kind);
+        if charge.count == 0 {
+            return 0;
+        }
+        let amount = charge.amount.0 / Balance::from(charge.count);
+        self.release(kind, amount);
+        amount
+    }
+}
+
+impl Contract {
+    /// Charges `amount` for a flat-rate entry and remembers it, so removing the
+    /// entry later releases what was paid rather than the current rate.
+    pub(crate) fn internal_charge_entry(
+        &mut self,
+        vaccount: &mut VAccount,
+        account_id: &AccountId,
+        kind: ChargeKind,
+        item: String,
+        amount: Balance,
+    ) {
+        require(
+            vaccount.deposit - vaccount.used >= amount,
+            ContractError::NotDepositEnough,
+        );
+        vaccount.charge(kind, amount);
+        if kind.is_linked() {
+            self.internal_link(account_id, kind, item.clone());
+        }
+        self.entry_charges
+            .insert(&(kind, account_id.clone(), item), &amount);
+    }
+
+    pub(crate) fn internal_release_entry(
+        &mut self,
+        vaccount: &mut VAccount,
+        account_id: &AccountId,
+        kind: ChargeKind,
+        item: String,
+    ) {
+        let amount = self.internal_forget_entry(account_id, kind, item);
+        vaccount.release(kind, amount);
+    }
+
+    pub(crate) fn internal_forget_entry(
+        &mut self,
+        account_id: &AccountId,
+        kind: ChargeKind,
+        item: String,
+    ) -> Balance {
+        if kind.is_linked() {
+            self.internal_unlink(account_id, kind, item.clone());
+        }
+        self.entry_charges
+            .remove(&(kind, account_id.clone(), item))
+            .unwrap_or(0)
+    }
+
+    /// Records that `account_id` holds a charge on another account's state.
+    pub(crate) fn internal_link(&mut self, account_id: &AccountId, kind: ChargeKind, item: String) {
+        let mut links = self.account_links.get(account_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountLinkSet {
+                account_id: account_id.clone(),
+            })
+        });
+        links.insert(&(kind, item));
+        self.account_links.insert(account_id, &links);
+    }
+
+    pub(crate) fn internal_unlink(
+        &mut self,
+        account_id: &AccountId,
+        kind: ChargeKind,
+        item: String,
+    ) {
+        if let Some(mut links) = self.account_links.get(account_id) {
+            if links.remove(&(kind, item)) {
+                if links.is_empty() {
+                    self.account_links.remove(account_id);
+                } else {
+                    self.account_links.insert(account_id, &links);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn used(contract: &Contract, account_id: &AccountId) -> Balance {
+        contract.accounts.get(account_id).unwrap().used
+    }
+
+    fn set_byte_cost(contract: &mut Contract, cost: Balance) {
+        set_context(&owner(), 1);
+        contract.set_storage_cost_override(Some(U128(cost)));
+    }
+
+    #[test]
+    fn contact_release_ignores_later_rate_change() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        let before = used(&contract, &alice());
+
+        set_context(&alice(), 1);
+        contract.add_contact(bob());
+        set_byte_cost(&mut contract, env::storage_byte_cost() * 3);
+        set_context(&alice(), 1);
+        contract.remove_contact(bob());
+
+        assert_eq!(used(&contract, &alice()), before);
+    }
+
+    #[test]
+    fn mail_release_ignores_later_rate_change() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let before = used(&contract, &alice());
+
+        let email_id = send(&mut contract, &alice(), &bob(), "content");
+        set_byte_cost(&mut contract, 1);
+        set_context(&alice(), 1);
+        contract.hide_mail(U128(email_id));
+        set_context(&bob(), 1);
+        contract.hide_mail(U128(email_id));
+
+        assert_eq!(used(&contract, &alice()), before);
+    }
+
+    #[test]
+    fn rate_drop_does_not_strand_charges() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let before = used(&contract, &alice());
+
+        set_byte_cost(&mut contract, 1);
+        set_context(&alice(), 1);
+        contract.add_contact(bob());
+        set_byte_cost(&mut contract, env::storage_byte_cost());
+        set_context(&alice(), 1);
+        contract.remove_contact(bob());
+
+        assert_eq!(used(&contract, &alice()), before);
+    }
+
+    #[test]
+    fn reserved_slot_round_trip() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let mut sender = contract.accounts.get(&alice()).unwrap();
+        sender.deposit = sender.used;
+        contract.accounts.insert(&alice(), &sender);
+        let before = used(&contract, &bob());
+
+        set_context(&bob(), contract.reserved_mail_cost());
+        contract.reserve_inbox(1);
+        set_byte_cost(&mut contract, env::storage_byte_cost() * 2);
+        let email_id = send(&mut contract, &alice(), &bob(), "content");
+        assert_eq!(contract.get_reserved_capacity(bob()), 0);
+        set_context(&alice(), 1);
+        contract.delete_mail(U128(email_id));
+
+        assert_eq!(used(&contract, &bob()), before);
+        assert_eq!(contract.accounts.get(&bob()).unwrap().charges.mail.count, 0);
+    }
+}
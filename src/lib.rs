@@ -1,58 +1,362 @@
+use batch::*;
+use broadcast::*;
+use consent::*;
+use edit::*;
 use email::*;
+use error::*;
+use events::*;
+use fee::*;
+use label::*;
+use ledger::*;
+use migration::*;
+use multi::*;
 use near_contract_standards::storage_management::{
     StorageBalance, StorageBalanceBounds, StorageManagement,
 };
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LookupMap, LookupSet, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::serde_json::json;
 use near_sdk::{
-    assert_one_yocto, env, json_types::U128, near_bindgen, AccountId, BorshStorageKey,
-    PanicOnDefault,
+    assert_one_yocto, env, json_types::U128, near_bindgen, AccountId, Balance, BorshStorageKey,
+    Gas, PanicOnDefault, Promise,
 };
+use notification::*;
+use reply::*;
+use resend::*;
+use schedule::*;
+use spam::*;
 use storage_impl::*;
 
+mod ack;
+mod activity;
+mod alias;
+mod archive;
+mod audit;
+mod batch;
+mod block;
+mod broadcast;
+mod consent;
+mod contact;
+mod conversation;
+mod cursor;
+mod duplicate;
+mod edit;
 mod email;
+mod error;
+mod events;
+pub mod export;
+mod fee;
+mod forward;
+mod idempotent;
+mod import;
+mod label;
+mod ledger;
+mod mail_index;
+mod merkle;
+mod migration;
+mod multi;
+mod mute;
+mod notification;
+mod owner;
+mod priority;
+mod reachability;
+mod reaction;
+mod reply;
+mod resend;
+mod reservation;
+mod retention;
+mod schedule;
+mod search;
+mod sort;
+mod spam;
+mod stats;
 mod storage_impl;
+#[cfg(test)]
+mod test_utils;
+mod thread;
+mod trash;
+mod verified;
 pub type EmailID = u128;
 
+pub(crate) struct SendPlan {
+    sender_pays: bool,
+    mail_hash: Option<Vec<u8>>,
+}
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 #[derive(BorshStorageKey, BorshSerialize)]
 pub enum StorageKeys {
     Sender,
     Receiver,
     Email,
-    SenderMail { email_id: EmailID },
-    ReceiverMail { email_id: EmailID },
+    SenderMail { account_hash: Vec<u8> },
+    ReceiverMail { account_hash: Vec<u8> },
     Account,
+    Alias,
+    Contact,
+    AccountContacts { account_id: AccountId },
+    ReportedEmail,
+    SpamBond,
+    NotificationPrefs,
+    Subscribers,
+    PublisherSubscribers { account_id: AccountId },
+    PendingCleanup,
+    SendRequestIds,
+    ConversationCursor,
+    RecentMailHashes,
+    Archived,
+    AccountArchived { account_id: AccountId },
+    Reactions,
+    EmailReactions { email_id: EmailID },
+    FeeEscrow,
+    SenderOrder,
+    ReceiverOrder,
+    AccountSenderOrder { account_id: AccountId },
+    AccountReceiverOrder { account_id: AccountId },
+    MutedThreads,
+    AccountMutedThreads { account_id: AccountId },
+    DeletedMail,
+    Conversations,
+    Conversation { pair_key: String },
+    DirectedIndex,
+    DirectedMail { directed_key: String },
+    ConversationPartners,
+    AccountPartners { account_id: AccountId },
+    LabelNames,
+    AccountLabelNames { account_id: AccountId },
+    LabelMail,
+    LabelMailSet { label_key: String },
+    Labeled,
+    Blocklists,
+    AccountBlocklist { account_id: AccountId },
+    BlockedBy,
+    AccountBlockedBy { account_id: AccountId },
+    Allowlists,
+    AccountAllowlist { account_id: AccountId },
+    DeliveryStats,
+    ThreadReplies,
+    ThreadReplyList { root_id: EmailID },
+    AccountThreads,
+    AccountThreadSet { account_id: AccountId },
+    ScheduledMail,
+    AccountScheduled { account_id: AccountId },
+    SendRequests,
+    IncomingRequests,
+    AccountIncomingRequests { account_id: AccountId },
+    Trash,
+    AccountTrash { account_id: AccountId },
+    FeeOverrides,
+    AccountFeeOverrides { account_id: AccountId },
+    AccountIds,
+    EntryCharges,
+    AccountLinks,
+    AccountLinkSet { account_id: AccountId },
+    ConversationCounts,
+    PendingBroadcasts,
+    RecentAllowed,
+    ScheduledRecords,
+    OrderTombstones,
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
+    owner_id: AccountId,
     senders: LookupMap<AccountId, UnorderedSet<EmailID>>,
     receivers: LookupMap<AccountId, UnorderedSet<EmailID>>,
     emails: UnorderedMap<EmailID, Email>,
     email_count: u128,
     accounts: LookupMap<AccountId, VAccount>,
     donation_contract_account: Option<AccountId>,
+    alias_to_account: LookupMap<String, AccountId>,
+    contacts: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    min_stranger_fee: U128,
+    import_closed: bool,
+    reported_emails: LookupSet<EmailID>,
+    spam_bond: U128,
+    spam_bonds: LookupMap<EmailID, SpamBond>,
+    notification_prefs: LookupMap<AccountId, NotificationPrefs>,
+    subscribers: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    pending_cleanups: LookupSet<AccountId>,
+    max_recipients: u64,
+    send_request_ids: LookupMap<AccountId, Vec<(String, EmailID)>>,
+    conversation_cursor: LookupMap<(AccountId, AccountId), EmailID>,
+    recent_mail_hashes: LookupMap<AccountId, Vec<(Vec<u8>, EmailID)>>,
+    archived: LookupMap<AccountId, UnorderedSet<EmailID>>,
+    reactions: LookupMap<EmailID, UnorderedMap<AccountId, String>>,
+    burn_account: Option<AccountId>,
+    fee_escrows: LookupMap<EmailID, FeeEscrow>,
+    fee_claim_window: u64,
+    base_fee: U128,
+    per_byte_fee: U128,
+    muted_threads: LookupMap<AccountId, UnorderedSet<EmailID>>,
+    deleted_mail: LookupMap<EmailID, DeletedMail>,
+    conversations: LookupMap<String, Vector<EmailID>>,
+    directed_index: LookupMap<String, Vector<EmailID>>,
+    conversation_partners: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    label_names: LookupMap<AccountId, UnorderedSet<String>>,
+    label_mail: LookupMap<String, UnorderedSet<EmailID>>,
+    labeled: LookupMap<EmailID, String>,
+    blocklists: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    blocked_by: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    allowlists: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    delivery_stats: LookupMap<EmailID, DeliveryStats>,
+    allow_empty_content: bool,
+    default_fee: Option<U128>,
+    max_thread_depth: u32,
+    max_labels_per_account: u64,
+    edit_window_ns: u64,
+    system_account: AccountId,
+    scheduled_mail: LookupMap<AccountId, Vector<(u64, ScheduleID)>>,
+    scheduled_records: LookupMap<ScheduleID, ScheduledMail>,
+    scheduled_count: ScheduleID,
+    max_scheduled_per_account: u64,
+    send_requests: LookupMap<RequestID, SendRequest>,
+    send_request_count: RequestID,
+    incoming_requests: LookupMap<AccountId, UnorderedSet<RequestID>>,
+    trash: LookupMap<AccountId, UnorderedSet<EmailID>>,
+    registered_count: u64,
+    account_ids: UnorderedSet<AccountId>,
+    entry_charges: LookupMap<(ChargeKind, AccountId, String), Balance>,
+    account_links: LookupMap<AccountId, UnorderedSet<(ChargeKind, String)>>,
+    fee_overrides: LookupMap<AccountId, UnorderedMap<AccountId, Balance>>,
+    storage_cost_override: Option<U128>,
+    max_batch_size: u64,
+    bonus_storage_bytes: u64,
+    total_escrowed: Balance,
+    thread_replies: LookupMap<EmailID, Vector<EmailID>>,
+    account_threads: LookupMap<AccountId, UnorderedSet<EmailID>>,
+    sender_order: LookupMap<AccountId, Vector<EmailID>>,
+    receiver_order: LookupMap<AccountId, Vector<EmailID>>,
+    order_tombstones: LookupMap<String, (AccountId, Balance)>,
+    mail_index_cursor: EmailID,
+    conversation_counts: LookupMap<String, u64>,
+    pending_broadcasts: LookupMap<AccountId, PendingBroadcast>,
+    version: String,
+    legacy_mail_cursor: EmailID,
+    legacy_mail_end: EmailID,
+    recent_allowed: LookupMap<AccountId, Vec<AccountId>>,
+    donation_bps: u16,
+    donation_gas: Gas,
+    max_attachment_bytes: u64,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
-    pub fn new() -> Self {
+    pub fn new(owner_id: AccountId) -> Self {
         Self {
+            owner_id,
             senders: LookupMap::new(StorageKeys::Sender),
             receivers: LookupMap::new(StorageKeys::Receiver),
             emails: UnorderedMap::new(StorageKeys::Email),
             email_count: 0,
             accounts: LookupMap::new(StorageKeys::Account),
             donation_contract_account: None,
+            alias_to_account: LookupMap::new(StorageKeys::Alias),
+            contacts: LookupMap::new(StorageKeys::Contact),
+            min_stranger_fee: U128(0),
+            import_closed: false,
+            reported_emails: LookupSet::new(StorageKeys::ReportedEmail),
+            spam_bond: U128(0),
+            spam_bonds: LookupMap::new(StorageKeys::SpamBond),
+            notification_prefs: LookupMap::new(StorageKeys::NotificationPrefs),
+            subscribers: LookupMap::new(StorageKeys::Subscribers),
+            pending_cleanups: LookupSet::new(StorageKeys::PendingCleanup),
+            max_recipients: DEFAULT_MAX_RECIPIENTS,
+            send_request_ids: LookupMap::new(StorageKeys::SendRequestIds),
+            conversation_cursor: LookupMap::new(StorageKeys::ConversationCursor),
+            recent_mail_hashes: LookupMap::new(StorageKeys::RecentMailHashes),
+            archived: LookupMap::new(StorageKeys::Archived),
+            reactions: LookupMap::new(StorageKeys::Reactions),
+            burn_account: None,
+            fee_escrows: LookupMap::new(StorageKeys::FeeEscrow),
+            fee_claim_window: DEFAULT_FEE_CLAIM_WINDOW,
+            base_fee: U128(0),
+            per_byte_fee: U128(0),
+            muted_threads: LookupMap::new(StorageKeys::MutedThreads),
+            deleted_mail: LookupMap::new(StorageKeys::DeletedMail),
+            conversations: LookupMap::new(StorageKeys::Conversations),
+            directed_index: LookupMap::new(StorageKeys::DirectedIndex),
+            conversation_partners: LookupMap::new(StorageKeys::ConversationPartners),
+            label_names: LookupMap::new(StorageKeys::LabelNames),
+            label_mail: LookupMap::new(StorageKeys::LabelMail),
+            labeled: LookupMap::new(StorageKeys::Labeled),
+            blocklists: LookupMap::new(StorageKeys::Blocklists),
+            blocked_by: LookupMap::new(StorageKeys::BlockedBy),
+            allowlists: LookupMap::new(StorageKeys::Allowlists),
+            delivery_stats: LookupMap::new(StorageKeys::DeliveryStats),
+            allow_empty_content: false,
+            default_fee: None,
+            max_thread_depth: DEFAULT_MAX_THREAD_DEPTH,
+            max_labels_per_account: DEFAULT_MAX_LABELS_PER_ACCOUNT,
+            edit_window_ns: DEFAULT_EDIT_WINDOW,
+            system_account: env::current_account_id(),
+            scheduled_mail: LookupMap::new(StorageKeys::ScheduledMail),
+            scheduled_records: LookupMap::new(StorageKeys::ScheduledRecords),
+            scheduled_count: 0,
+            max_scheduled_per_account: DEFAULT_MAX_SCHEDULED_PER_ACCOUNT,
+            send_requests: LookupMap::new(StorageKeys::SendRequests),
+            send_request_count: 0,
+            incoming_requests: LookupMap::new(StorageKeys::IncomingRequests),
+            trash: LookupMap::new(StorageKeys::Trash),
+            registered_count: 0,
+            account_ids: UnorderedSet::new(StorageKeys::AccountIds),
+            entry_charges: LookupMap::new(StorageKeys::EntryCharges),
+            account_links: LookupMap::new(StorageKeys::AccountLinks),
+            fee_overrides: LookupMap::new(StorageKeys::FeeOverrides),
+            storage_cost_override: None,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            bonus_storage_bytes: 0,
+            total_escrowed: 0,
+            thread_replies: LookupMap::new(StorageKeys::ThreadReplies),
+            account_threads: LookupMap::new(StorageKeys::AccountThreads),
+            sender_order: LookupMap::new(StorageKeys::SenderOrder),
+            receiver_order: LookupMap::new(StorageKeys::ReceiverOrder),
+            order_tombstones: LookupMap::new(StorageKeys::OrderTombstones),
+            mail_index_cursor: 0,
+            conversation_counts: LookupMap::new(StorageKeys::ConversationCounts),
+            pending_broadcasts: LookupMap::new(StorageKeys::PendingBroadcasts),
+            version: VERSION.to_string(),
+            legacy_mail_cursor: 0,
+            legacy_mail_end: 0,
+            recent_allowed: LookupMap::new(StorageKeys::RecentAllowed),
+            donation_bps: 0,
+            donation_gas: DEFAULT_DONATION_GAS,
+            max_attachment_bytes: DEFAULT_MAX_ATTACHMENT_BYTES,
         }
     }
 
+    /// Converts the baseline state to the current layout and stamps the new
+    /// version. The baseline had no owner, so the deployer names one here.
+    /// Baseline mail and accounts are converted afterwards by
+    /// `migrate_legacy_mail` and `migrate_legacy_accounts`.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(owner_id: AccountId) -> Self {
+        let old: OldContract = env::state_read().expect("Contract is not initialized");
+        Self::from_legacy(owner_id, old)
+    }
+
+    #[payable]
     pub fn add_donation_contract_account(&mut self, account: AccountId) {
+        assert_one_yocto();
+        self.assert_owner();
         self.donation_contract_account = Some(account);
     }
 
+    #[payable]
+    pub fn remove_donation_contract_account(&mut self) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.donation_contract_account = None;
+    }
+
+    pub fn get_donation_contract_account(&self) -> Option<AccountId> {
+        self.donation_contract_account.clone()
+    }
+
     #[payable]
     pub fn send_mail(
         &mut self,
@@ -60,69 +364,102 @@ impl Contract {
         title: String,
         content: String,
         fee: Option<U128>,
-    ) {
-        assert_one_yocto();
+        options: Option<SendOptions>,
+    ) -> U128 {
         let sender = env::predecessor_account_id();
-        assert!(
-            self.accounts.contains_key(&sender),
-            "Account not registered"
-        );
-        assert!(self.can_send_mail(sender.clone()), "Not deposit enough");
-
-        let mut vaccount = self.accounts.get(&sender).unwrap();
-        vaccount.used += STORAGE_PER_MAIL * env::storage_byte_cost();
-        self.accounts.insert(&sender, &vaccount);
-        let current_count = self.email_count;
-        self.email_count = self.email_count + 1;
-        let timestamp = env::block_timestamp();
+        let mut email = Email::new(sender, receiver, title, content, fee);
+        options.unwrap_or_default().apply(&mut email);
+        U128(self.internal_send_mail(email))
+    }
 
-        if Some(sender) == self.donation_contract_account {
-            assert!(fee.is_none(), "Fee must be none");
+    #[payable]
+    pub fn send_mail_v2(
+        &mut self,
+        receiver: AccountId,
+        title: String,
+        content: String,
+        fee: Option<U128>,
+        options: Option<SendOptions>,
+    ) -> SendResult {
+        let sender = env::predecessor_account_id();
+        let email_id = self.send_mail(receiver, title, content, fee, options);
+        let vaccount = self.accounts.get(&sender).unwrap();
+        SendResult {
+            email_id,
+            storage_used: U128(vaccount.used),
+            storage_remaining: U128(vaccount.deposit - vaccount.used),
         }
+    }
 
-        let email = Email {
-            title,
-            content,
-            timestamp,
-            fee,
-        };
-        self.emails.insert(&current_count, &email);
-        if let Some(mut sender_vec) = self.senders.get(&sender) {
-            sender_vec.insert(&current_count);
-            self.senders.insert(&sender, &sender_vec);
-        } else {
-            let mut sender_vec_new = UnorderedSet::new(StorageKeys::SenderMail {
-                email_id: current_count,
-            });
-            sender_vec_new.insert(&current_count);
-            self.senders.insert(&sender, &sender_vec_new);
-        }
+    pub fn get_email(&self, email_id: U128) -> Option<Email> {
+        self.emails
+            .get(&email_id.0)
+            .map(|email| EmailView::new(email_id.0, email).email)
+    }
 
-        if let Some(mut receiver_vec) = self.receivers.get(&receiver) {
-            receiver_vec.insert(&current_count);
-            self.receivers.insert(&receiver, &receiver_vec);
-        } else {
-            let mut receiver_vec_new = UnorderedSet::new(StorageKeys::ReceiverMail {
-                email_id: current_count,
-            });
-            receiver_vec_new.insert(&current_count);
-            self.receivers.insert(&receiver, &receiver_vec_new);
-        }
+    pub fn get_email_meta(&self, email_id: U128) -> Option<EmailMeta> {
+        self.emails.get(&email_id.0).map(EmailMeta::from)
     }
 
-    pub fn get_email(&self, email_id: U128) -> Email {
+    /// Reads a mail's body as the caller. A change method, since views cannot
+    /// see the caller and anyone may run them for any account.
+    pub fn get_email_private(&mut self, email_id: U128) -> Option<Email> {
         let real_email_id: EmailID = email_id.0;
-        self.emails.get(&real_email_id).unwrap()
+        let caller = env::predecessor_account_id();
+        let is_party = self.is_sender_of(&caller, real_email_id)
+            || self.holds_received(&caller, real_email_id);
+        if !is_party {
+            return None;
+        }
+        let mut email = self.emails.get(&real_email_id)?;
+        email.content = visible_content(&email, &caller);
+        Some(email)
     }
 
+    #[payable]
     pub fn delete_mail(&mut self, email_id: U128) {
+        assert_one_yocto();
         let real_email_id: EmailID = email_id.0;
         let sender = env::predecessor_account_id();
-        assert!(
-            !self.senders.get(&sender).unwrap().contains(&real_email_id),
-            "Caller is not sender"
+        require(
+            self.is_sender_of(&sender, real_email_id),
+            ContractError::CallerNotSender,
         );
-        self.emails.remove(&real_email_id);
+        if let Some(email) = self.emails.get(&real_email_id) {
+            self.internal_purge(real_email_id, &email);
+            self.internal_record_deleted(real_email_id, email.receiver);
+        }
+    }
+
+    #[payable]
+    pub fn hide_mail(&mut self, email_id: U128) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let caller = env::predecessor_account_id();
+        let email = self.emails.get(&real_email_id);
+        let is_sender = self.is_sender_of(&caller, real_email_id);
+        let is_receiver = self.holds_received(&caller, real_email_id);
+        assert!(is_sender || is_receiver, "Caller is not sender or receiver");
+
+        if is_sender {
+            self.internal_remove_from_outbox(&caller, real_email_id);
+        }
+        if is_receiver {
+            self.internal_remove_received(&caller, real_email_id);
+        }
+        if let Some(email) = email {
+            self.internal_purge_if_orphaned(real_email_id, &email);
+            self.internal_compact_parties(&email);
+            // Mail the sender still pays for must stay findable from the sender
+            // once it has left their outbox.
+            if is_sender
+                && email.storage_payer.is_none()
+                && self.holds_received(&email.receiver, real_email_id)
+            {
+                self.internal_link(&caller, ChargeKind::Mail, real_email_id.to_string());
+            }
+        }
+        self.internal_touch(&caller);
     }
 
     pub fn mail_exist(&self) -> u64 {
@@ -131,24 +468,103 @@ impl Contract {
 
     pub fn get_mail_receive(&self, receiver: AccountId) -> Vec<Email> {
         let mut email_vec: Vec<Email> = Vec::new();
-        if let Some(receiver_vec) = self.receivers.get(&receiver) {
-            for index in receiver_vec.iter() {
-                let mail = self.emails.get(&index).unwrap();
-                email_vec.push(mail);
+        for index in self.inbox_ids(&receiver) {
+            if let Some(mail) = self.emails.get(&index) {
+                email_vec.push(EmailView::new(index, mail).email);
             }
         }
-        return email_vec;
+        email_vec
     }
 
     pub fn get_mail_send(&self, sender: AccountId) -> Vec<Email> {
         let mut email_vec: Vec<Email> = Vec::new();
-        if let Some(sender_vec) = self.senders.get(&sender) {
-            for index in sender_vec.iter() {
-                let mail = self.emails.get(&index).unwrap();
-                email_vec.push(mail);
+        for index in self.outbox_ids(&sender) {
+            if let Some(mail) = self.emails.get(&index) {
+                email_vec.push(EmailView::new(index, mail).email);
+            }
+        }
+        email_vec
+    }
+
+    pub fn get_version(&self) -> String {
+        self.version.clone()
+    }
+
+    pub fn can_afford_send(&self, account: AccountId, content_len: u64) -> bool {
+        if let Some(storage_balance) = self.storage_balance_of(account) {
+            return storage_balance.available.0 >= self.mail_storage_cost(content_len);
+        }
+        false
+    }
+
+    pub fn has_received(&self, account: AccountId, email_id: U128) -> bool {
+        self.accounts.contains_key(&account) && self.holds_received(&account, email_id.0)
+    }
+
+    pub fn has_sent(&self, account: AccountId, email_id: U128) -> bool {
+        self.accounts.contains_key(&account) && self.is_sender_of(&account, email_id.0)
+    }
+
+    pub fn get_max_content_len(&self, account: AccountId) -> u64 {
+        self.affordable_len(&account).min(MAX_CONTENT_LEN as u64)
+    }
+
+    #[payable]
+    pub fn mark_as_read(&mut self, email_id: U128) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let receiver = env::predecessor_account_id();
+        assert!(
+            self.holds_received(&receiver, real_email_id),
+            "Caller is not receiver"
+        );
+        let mut email = self.emails.get(&real_email_id).unwrap();
+        if email.read_at.is_none() {
+            let read_at = env::block_timestamp();
+            email.read_at = Some(read_at);
+            self.emails.insert(&real_email_id, &email);
+            if let Some(batch_id) = email.batch_id {
+                self.internal_record_batch_read(batch_id.0);
+            }
+            if self.get_notification_prefs(receiver.clone()).on_read {
+                emit_event(
+                    "mail_read",
+                    json!({
+                        "email_id": email_id,
+                        "reader": receiver,
+                        "read_at": read_at,
+                    }),
+                );
+            }
+            if let Some(bid) = email.bid.filter(|bid| bid.0 > 0) {
+                Promise::new(receiver.clone()).transfer(bid.0);
             }
         }
-        return email_vec;
+        // Only inbox mail is archived; mail already filed elsewhere stays put.
+        if self.get_auto_archive_on_read(receiver.clone())
+            && self.is_receiver_of(&receiver, real_email_id)
+        {
+            self.internal_archive(&receiver, real_email_id);
+        }
+        self.internal_touch(&receiver);
+    }
+
+    pub fn get_unread_by_sender(&self, receiver: AccountId) -> Vec<(AccountId, u64)> {
+        let mut unread: Vec<(AccountId, u64)> = Vec::new();
+        if let Some(receiver_vec) = self.receivers.get(&receiver) {
+            for index in receiver_vec.iter() {
+                if let Some(mail) = self.emails.get(&index) {
+                    if self.is_read_by(&receiver, index, &mail) {
+                        continue;
+                    }
+                    match unread.iter_mut().find(|(sender, _)| *sender == mail.sender) {
+                        Some((_, count)) => *count += 1,
+                        None => unread.push((mail.sender, 1)),
+                    }
+                }
+            }
+        }
+        unread
     }
 
     pub fn get_mail_receive_num(&self, receiver: AccountId) -> u64 {
@@ -165,6 +581,11 @@ impl Contract {
         0
     }
 
+    /// Next id to be assigned. Ids are monotonic and never reused, even after deletions.
+    pub fn get_email_count(&self) -> U128 {
+        U128(self.email_count)
+    }
+
     pub fn mail_delete(&self) -> U128 {
         let mail_exist: u128 = self.emails.keys_as_vector().len().into();
         U128(self.email_count - mail_exist)
@@ -172,17 +593,960 @@ impl Contract {
 }
 
 impl Contract {
-    pub fn can_send_mail(&self, account_id: AccountId) -> bool {
-        let available_storage = self
-            .storage_balance_of(account_id.clone())
-            .unwrap()
-            .available
+    pub(crate) fn internal_send_mail(&mut self, email: Email) -> EmailID {
+        self.internal_send_mail_with_deposit(email, env::attached_deposit())
+    }
+
+    pub(crate) fn internal_send_mail_with_deposit(
+        &mut self,
+        mut email: Email,
+        attached: Balance,
+    ) -> EmailID {
+        self.prepare_mail(&mut email);
+        let plan = self
+            .check_send(&email, attached)
+            .unwrap_or_else(|error| error.panic());
+        self.internal_deliver(email, plan)
+    }
+
+    /// One mail of a multi-mail call. The caller checks the cooldown once up
+    /// front, since each delivery restarts it.
+    pub(crate) fn internal_send_batch_item(
+        &mut self,
+        mut email: Email,
+        attached: Balance,
+    ) -> EmailID {
+        self.prepare_mail(&mut email);
+        let plan = self
+            .check_send_item(&email, attached)
+            .unwrap_or_else(|error| error.panic());
+        self.internal_deliver(email, plan)
+    }
+
+    pub(crate) fn prepare_mail(&self, email: &mut Email) {
+        email.title = sanitize_title(&email.title);
+        email.content = sanitize_content(&email.content);
+        self.apply_default_fee(email);
+    }
+
+    /// Every precondition of a send, checked without touching state so callbacks
+    /// can refund instead of panicking.
+    pub(crate) fn check_send(
+        &self,
+        email: &Email,
+        attached: Balance,
+    ) -> Result<SendPlan, ContractError> {
+        ensure(
+            self.cooldown_elapsed(&email.sender),
+            ContractError::SendCooldownActive,
+        )?;
+        self.check_send_item(email, attached)
+    }
+
+    /// `check_send` without the cooldown, for mail after the first of a call.
+    pub(crate) fn check_send_item(
+        &self,
+        email: &Email,
+        attached: Balance,
+    ) -> Result<SendPlan, ContractError> {
+        let sender = &email.sender;
+        let receiver = &email.receiver;
+        ensure(
+            self.allow_empty_content || !email.title.is_empty() || !email.content.is_empty(),
+            ContractError::EmptyMail,
+        )?;
+        ensure(
+            self.accounts.contains_key(sender),
+            ContractError::AccountNotRegistered,
+        )?;
+        ensure(
+            !self.is_spam_suspended(sender),
+            ContractError::AccountSuspended,
+        )?;
+        self.check_accepts_sender(receiver, sender)?;
+
+        let spam_bond = self.required_spam_bond(sender, receiver);
+        let bid = email.bid.map(|bid| bid.0).unwrap_or(0);
+        let fee = email.near_fee();
+        ensure(
+            attached == spam_bond + bid + fee + 1,
+            ContractError::AttachedDepositMismatch,
+        )?;
+        let attached_bytes = attachments_bytes(&email.attachments);
+        ensure(
+            attached_bytes <= self.max_attachment_bytes,
+            ContractError::AttachmentsTooLarge(
+                attached_bytes.saturating_sub(self.max_attachment_bytes),
+            ),
+        )?;
+        let content_len = email.stored_len();
+        let sender_pays = self.can_afford_send(sender.clone(), content_len);
+        let over_storage = !sender_pays && !self.can_use_reservation(receiver, content_len);
+        let over_cap = email.content.len() > MAX_CONTENT_LEN;
+        ensure(
+            !over_storage || (over_cap && self.affordable_len(sender) >= MAX_CONTENT_LEN as u64),
+            ContractError::ContentExceedsStorage,
+        )?;
+        check_lengths(&email.title, &email.content)?;
+
+        // A receiver's opt-out from paid mail comes before every fee floor,
+        // including `min_stranger_fee`; see `set_reject_paid_mail`.
+        if self.get_reject_paid_mail(receiver.clone()) {
+            ensure(email.fee.is_none(), ContractError::PaidMailRejected)?;
+        } else if self.is_donation_contract(sender) || self.is_donation_contract(receiver) {
+            ensure(email.fee.is_none(), ContractError::FeeMustBeNone)?;
+        } else {
+            ensure(
+                fee >= self.required_fee(sender, receiver, email.content.len() as u64),
+                ContractError::FeeBelowMinimum,
+            )?;
+        }
+
+        Ok(SendPlan {
+            sender_pays,
+            mail_hash: self.check_not_duplicate(email)?,
+        })
+    }
+
+    pub(crate) fn internal_deliver(&mut self, mut email: Email, plan: SendPlan) -> EmailID {
+        let sender = email.sender.clone();
+        let receiver = email.receiver.clone();
+        let spam_bond = self.required_spam_bond(&sender, &receiver);
+        let fee = email.near_fee();
+        if !plan.sender_pays {
+            email.storage_payer = Some(receiver.clone());
+        }
+
+        // The record and every index it joins are charged at what they
+        // actually took; `check_send` only made sure the upper bound was free.
+        let before = env::storage_usage();
+        let email_id = self.internal_insert_email(&email);
+        if !plan.sender_pays {
+            self.internal_link(&receiver, ChargeKind::Mail, email_id.to_string());
+        }
+        if let Some(mail_hash) = plan.mail_hash {
+            self.internal_record_mail_hash(&receiver, mail_hash, email_id);
+        }
+        let storage_cost = self.stored_cost_since(before);
+        email.storage_charged = U128(storage_cost);
+        self.emails.insert(&email_id, &email);
+
+        if !plan.sender_pays {
+            self.internal_use_reservation(&receiver, storage_cost);
+        }
+        let mut vaccount = self.accounts.get(&sender).unwrap();
+        if plan.sender_pays {
+            vaccount.charge(ChargeKind::Mail, storage_cost);
+        }
+        vaccount.sent_count += 1;
+        vaccount.last_sent_at = email.timestamp;
+        vaccount.last_active = email.timestamp;
+        self.accounts.insert(&sender, &vaccount);
+
+        if self.get_allowlist_only(receiver.clone()) {
+            self.internal_note_allowed(&receiver, &sender);
+        }
+        if fee > 0 {
+            let escrow = FeeEscrow {
+                sender: sender.clone(),
+                receiver: receiver.clone(),
+                amount: fee,
+                escrowed_at: email.timestamp,
+            };
+            self.fee_escrows.insert(&email_id, &escrow);
+            self.total_escrowed += fee;
+        }
+        if spam_bond > 0 {
+            let bond = SpamBond {
+                sender: sender.clone(),
+                amount: spam_bond,
+                posted_at: email.timestamp,
+            };
+            self.spam_bonds.insert(&email_id, &bond);
+        }
+
+        self.internal_notify_new_mail(email_id, &sender, &receiver);
+        email_id
+    }
+
+    pub(crate) fn internal_notify_new_mail(
+        &self,
+        email_id: EmailID,
+        sender: &AccountId,
+        receiver: &AccountId,
+    ) {
+        if self.get_notification_prefs(receiver.clone()).on_new_mail {
+            let mut data = json!({
+                "email_id": U128(email_id),
+                "sender": sender,
+                "receiver": receiver,
+            });
+            if let Some(webhook_tag) = self.get_webhook_tag(receiver.clone()) {
+                data["webhook_tag"] = json!(webhook_tag);
+            }
+            emit_event("new_mail", data);
+        }
+    }
+
+    pub(crate) fn internal_insert_email(&mut self, email: &Email) -> EmailID {
+        let current_count = self.email_count;
+        self.email_count += 1;
+        let sender = &email.sender;
+        let receiver = &email.receiver;
+
+        self.emails.insert(&current_count, email);
+        if let Some(mut sender_vec) = self.senders.get(sender) {
+            sender_vec.insert(&current_count);
+            self.senders.insert(sender, &sender_vec);
+        } else {
+            let mut sender_vec_new = UnorderedSet::new(StorageKeys::SenderMail {
+                account_hash: env::sha256(sender.as_bytes()),
+            });
+            sender_vec_new.insert(&current_count);
+            self.senders.insert(sender, &sender_vec_new);
+        }
+
+        self.internal_add_to_inbox(receiver, current_count);
+        self.internal_index_mail(sender, receiver, current_count);
+        self.internal_index_conversation(sender, receiver, current_count);
+        self.internal_index_thread(email, current_count);
+        current_count
+    }
+
+    pub(crate) fn affordable_len(&self, account_id: &AccountId) -> u64 {
+        match self.storage_balance_of(account_id.clone()) {
+            Some(storage_balance) => {
+                let bytes = storage_balance.available.0 / self.byte_cost();
+                bytes.saturating_sub(STORAGE_PER_MAIL) as u64
+            }
+            None => 0,
+        }
+    }
+
+    pub(crate) fn apply_default_fee(&self, email: &mut Email) {
+        if email.fee.is_none()
+            && !self.is_donation_contract(&email.sender)
+            && !self.is_donation_contract(&email.receiver)
+            && !self.get_reject_paid_mail(email.receiver.clone())
+        {
+            email.fee = self.default_fee.map(FeeKind::Near);
+        }
+    }
+
+    pub(crate) fn assert_not_empty(&self, title: &str, content: &str) {
+        require(
+            self.allow_empty_content || !title.is_empty() || !content.is_empty(),
+            ContractError::EmptyMail,
+        );
+    }
+
+    pub(crate) fn is_sender_of(&self, account_id: &AccountId, email_id: EmailID) -> bool {
+        self.senders
+            .get(account_id)
+            .map(|sender_vec| sender_vec.contains(&email_id))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn is_receiver_of(&self, account_id: &AccountId, email_id: EmailID) -> bool {
+        self.receivers
+            .get(account_id)
+            .map(|receiver_vec| receiver_vec.contains(&email_id))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn is_donation_contract(&self, account_id: &AccountId) -> bool {
+        self.donation_contract_account.as_ref() == Some(account_id)
+    }
+
+    pub(crate) fn internal_remove_from_outbox(&mut self, sender: &AccountId, email_id: EmailID) {
+        if let Some(mut sender_vec) = self.senders.get(sender) {
+            sender_vec.remove(&email_id);
+            if sender_vec.is_empty() {
+                self.senders.remove(sender);
+            } else {
+                self.senders.insert(sender, &sender_vec);
+            }
+        }
+    }
+
+    pub(crate) fn internal_add_to_inbox(&mut self, receiver: &AccountId, email_id: EmailID) {
+        let mut receiver_vec = self.receivers.get(receiver).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::ReceiverMail {
+                account_hash: env::sha256(receiver.as_bytes()),
+            })
+        });
+        receiver_vec.insert(&email_id);
+        self.receivers.insert(receiver, &receiver_vec);
+    }
+
+    pub(crate) fn internal_remove_from_inbox(&mut self, receiver: &AccountId, email_id: EmailID) {
+        if let Some(mut receiver_vec) = self.receivers.get(receiver) {
+            receiver_vec.remove(&email_id);
+            if receiver_vec.is_empty() {
+                self.receivers.remove(receiver);
+            } else {
+                self.receivers.insert(receiver, &receiver_vec);
+            }
+        }
+    }
+
+    pub(crate) fn holds_received(&self, account_id: &AccountId, email_id: EmailID) -> bool {
+        self.is_receiver_of(account_id, email_id)
+            || self.is_archived_by(account_id, email_id)
+            || self.label_of(account_id, email_id).is_some()
+            || self.is_trashed_by(account_id, email_id)
+    }
+
+    pub(crate) fn internal_remove_received(&mut self, account_id: &AccountId, email_id: EmailID) {
+        self.internal_remove_from_inbox(account_id, email_id);
+        self.internal_remove_from_archive(account_id, email_id);
+        self.internal_remove_from_label(account_id, email_id);
+        self.internal_remove_from_trash(account_id, email_id);
+    }
+
+    pub(crate) fn internal_purge_if_orphaned(&mut self, email_id: EmailID, email: &Email) {
+        if !self.is_sender_of(&email.sender, email_id)
+            && !self.holds_received(&email.receiver, email_id)
+        {
+            self.internal_purge(email_id, email);
+        }
+    }
+
+    /// Removes the account's copy of a received mail and settles its storage:
+    /// mail the receiver paid for goes with their copy, anything else is purged
+    /// once nobody holds it.
+    pub(crate) fn internal_drop_received(
+        &mut self,
+        account_id: &AccountId,
+        email_id: EmailID,
+        email: &Email,
+    ) {
+        self.internal_remove_received(account_id, email_id);
+        if email.storage_payer.as_ref() == Some(account_id) && email.sender != *account_id {
+            self.internal_purge(email_id, email);
+        } else {
+            self.internal_purge_if_orphaned(email_id, email);
+            self.internal_compact_parties(email);
+        }
+    }
+
+    /// Removes a mail everywhere it is held and settles what it was holding.
+    pub(crate) fn internal_purge(&mut self, email_id: EmailID, email: &Email) {
+        self.emails.remove(&email_id);
+        self.internal_remove_from_outbox(&email.sender, email_id);
+        self.internal_remove_received(&email.receiver, email_id);
+        let payer = email.storage_payer.as_ref().unwrap_or(&email.sender);
+        self.internal_unlink(payer, ChargeKind::Mail, email_id.to_string());
+        self.internal_compact_parties(email);
+        self.internal_release_mail_storage(email);
+        self.internal_leave_tombstones(payer, email_id, email);
+        self.internal_clear_reactions(email_id);
+        self.internal_uncount_conversation(&email.sender, &email.receiver);
+        if email.read_at.is_none() {
+            if let Some(bid) = email.bid.filter(|bid| bid.0 > 0) {
+                Promise::new(email.sender.clone()).transfer(bid.0);
+            }
+        }
+    }
+
+    pub(crate) fn internal_touch(&mut self, account_id: &AccountId) {
+        if let Some(mut vaccount) = self.accounts.get(account_id) {
+            vaccount.last_active = env::block_timestamp();
+            self.accounts.insert(account_id, &vaccount);
+        }
+    }
+
+    pub(crate) fn internal_release_mail_storage(&mut self, email: &Email) {
+        let payer = email.storage_payer.as_ref().unwrap_or(&email.sender);
+        if *payer == env::current_account_id() {
+            return;
+        }
+        if let Some(mut vaccount) = self.accounts.get(payer) {
+            vaccount.release(ChargeKind::Mail, email.storage_charged.0);
+            self.accounts.insert(payer, &vaccount);
+        }
+    }
+
+    /// Adds `amount` to what a stored mail is charged, for state written on its
+    /// behalf after it was delivered. It is released with the mail.
+    pub(crate) fn internal_add_mail_charge(&mut self, email_id: EmailID, amount: Balance) {
+        let mut email = match self.emails.get(&email_id) {
+            Some(email) => email,
+            None => return,
+        };
+        let payer = email.storage_payer.clone().unwrap_or(email.sender.clone());
+        if let Some(mut vaccount) = self.accounts.get(&payer) {
+            require(
+                vaccount.deposit - vaccount.used >= amount,
+                ContractError::NotDepositEnough,
+            );
+            vaccount.charge_many(ChargeKind::Mail, 0, amount);
+            self.accounts.insert(&payer, &vaccount);
+        }
+        email.storage_charged = U128(email.storage_charged.0 + amount);
+        self.emails.insert(&email_id, &email);
+    }
+}
+
+/// The size caps every send path enforces on a mail's title and body.
+pub(crate) fn check_lengths(title: &str, content: &str) -> Result<(), ContractError> {
+    ensure(title.len() <= MAX_TITLE_LEN, ContractError::TitleTooLong)?;
+    ensure(
+        content.len() <= MAX_CONTENT_LEN,
+        ContractError::ContentTooLong,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::testing_env;
+
+    fn setup_mail() -> (Contract, EmailID) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+        (contract, email_id)
+    }
+
+    #[test]
+    fn deleting_only_mail_drops_both_sets() {
+        let (mut contract, email_id) = setup_mail();
+        set_context(&alice(), 1);
+        contract.delete_mail(U128(email_id));
+
+        assert!(contract.senders.get(&alice()).is_none());
+        assert!(contract.receivers.get(&bob()).is_none());
+        assert!(!contract.holds_received(&bob(), email_id));
+    }
+
+    #[test]
+    fn unarchiving_last_mail_drops_archive_set() {
+        let (mut contract, email_id) = setup_mail();
+        set_context(&bob(), 1);
+        contract.archive_mail(U128(email_id));
+        assert!(contract.receivers.get(&bob()).is_none());
+
+        set_context(&bob(), 1);
+        contract.unarchive_mail(U128(email_id));
+        assert!(contract.archived.get(&bob()).is_none());
+        assert!(contract.is_receiver_of(&bob(), email_id));
+    }
+
+    #[test]
+    fn moving_last_mail_out_of_label_drops_label_set() {
+        let (mut contract, email_id) = setup_mail();
+        set_context(&bob(), 1);
+        contract.create_label("work".to_string());
+        set_context(&bob(), 1);
+        contract.move_mail(U128(email_id), None, Some("work".to_string()));
+
+        set_context(&bob(), 1);
+        contract.move_mail(U128(email_id), Some("work".to_string()), None);
+        assert!(contract
+            .label_mail
+            .get(&label_key(&bob(), "work"))
+            .is_none());
+    }
+
+    /// Runs a send that must be rejected and checks it numbered no mail. The
+    /// mocked state keeps writes made before the panic, unlike a real receipt.
+    fn assert_rejected_keeps_count(contract: &mut Contract, send: impl FnOnce(&mut Contract)) {
+        let before = contract.email_count;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| send(contract)));
+        assert!(result.is_err());
+        assert_eq!(contract.email_count, before);
+    }
+
+    fn idempotent(contract: &mut Contract, request_id: &str, deposit: Balance) -> U128 {
+        set_context(&alice(), deposit);
+        contract.send_mail_idempotent(
+            request_id.to_string(),
+            bob(),
+            "Hello".to_string(),
+            "content".to_string(),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn one_sided_hide_keeps_the_other_view() {
+        let (mut contract, email_id) = setup_mail();
+        set_context(&bob(), 1);
+        contract.hide_mail(U128(email_id));
+
+        assert!(!contract.holds_received(&bob(), email_id));
+        assert!(contract.is_sender_of(&alice(), email_id));
+        assert!(contract.emails.get(&email_id).is_some());
+    }
+
+    #[test]
+    fn two_sided_hide_frees_storage() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+
+        set_context(&alice(), 1);
+        contract.hide_mail(U128(email_id));
+        set_context(&bob(), 1);
+        contract.hide_mail(U128(email_id));
+
+        assert!(contract.emails.get(&email_id).is_none());
+        assert_eq!(contract.accounts.get(&alice()).unwrap().used, before);
+    }
+
+    #[test]
+    fn send_charges_the_indexes_it_creates() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        let first = send(&mut contract, &alice(), &bob(), "hi");
+        let second = send(&mut contract, &alice(), &bob(), "yo");
+        let charged = |email_id| contract.emails.get(&email_id).unwrap().storage_charged.0;
+
+        // The first mail between two accounts also creates their shared indexes.
+        assert!(charged(first) > charged(second));
+        assert!(charged(second) > contract.byte_cost() * 2);
+        assert_eq!(
+            contract.accounts.get(&alice()).unwrap().used - before,
+            charged(first) + charged(second)
+        );
+
+        for email_id in [first, second] {
+            set_context(&alice(), 1);
+            contract.hide_mail(U128(email_id));
+            set_context(&bob(), 1);
+            contract.hide_mail(U128(email_id));
+        }
+        assert_eq!(contract.accounts.get(&alice()).unwrap().used, before);
+    }
+
+    #[test]
+    fn longest_first_contact_stays_within_the_per_mail_bound() {
+        let mut contract = setup();
+        let sender: AccountId = "s".repeat(64).parse().unwrap();
+        let receiver: AccountId = "r".repeat(64).parse().unwrap();
+        register(&mut contract, &sender);
+        register(&mut contract, &receiver);
+        let email_id = send(&mut contract, &sender, &receiver, "hi");
+        set_context(&receiver, 1);
+        let reply_id = contract
+            .reply_mail(U128(email_id), "back".to_string(), None, false)
             .0;
 
-        if let Some(sender) = self.senders.get(&account_id) {
-            let mail_len: u128 = (sender.len() + 1).into();
-            return available_storage > (mail_len * STORAGE_PER_MAIL * env::storage_byte_cost());
+        for (email_id, content_len) in [(email_id, 7), (reply_id, 9)] {
+            let charged = contract.emails.get(&email_id).unwrap().storage_charged.0;
+            assert!(charged <= contract.mail_storage_cost(content_len));
+        }
+    }
+
+    #[test]
+    fn can_afford_send_at_the_exact_boundary() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        let cost = contract.mail_storage_cost(100);
+        let mut vaccount = contract.accounts.get(&alice()).unwrap();
+        vaccount.deposit = vaccount.used + cost;
+        contract.accounts.insert(&alice(), &vaccount);
+        assert!(contract.can_afford_send(alice(), 100));
+
+        vaccount.deposit -= 1;
+        contract.accounts.insert(&alice(), &vaccount);
+        assert!(!contract.can_afford_send(alice(), 100));
+        assert!(!contract.can_afford_send(bob(), 0));
+    }
+
+    #[test]
+    fn private_read_is_party_only() {
+        let (mut contract, email_id) = setup_mail();
+        set_context(&carol(), 0);
+        assert!(contract.get_email_private(U128(email_id)).is_none());
+
+        for party in [alice(), bob()] {
+            set_context(&party, 0);
+            let email = contract.get_email_private(U128(email_id)).unwrap();
+            assert_eq!(email.content, "hi");
         }
-        return available_storage > (STORAGE_PER_MAIL * env::storage_byte_cost());
+    }
+
+    #[test]
+    fn get_email_hides_content_and_unknown_ids() {
+        let (contract, email_id) = setup_mail();
+        assert!(contract
+            .get_email(U128(email_id))
+            .unwrap()
+            .content
+            .is_empty());
+        assert!(contract.get_email(U128(email_id + 1)).is_none());
+    }
+
+    #[test]
+    fn version_is_set_by_new_and_migrate() {
+        let contract = setup();
+        assert_eq!(contract.get_version(), VERSION);
+
+        write_baseline_state(&[]);
+        set_context(&contract_account(), 0);
+        assert_eq!(Contract::migrate(owner()).get_version(), VERSION);
+    }
+
+    #[test]
+    fn rejected_sends_do_not_advance_email_count() {
+        let (mut contract, _) = setup_mail();
+        assert_rejected_keeps_count(&mut contract, |contract| {
+            set_context(&alice(), 0);
+            contract.send_mail(bob(), "Hello".to_string(), "hi".to_string(), None, None);
+        });
+
+        set_context(&bob(), 1);
+        contract.block_sender(alice());
+        assert_rejected_keeps_count(&mut contract, |contract| {
+            send(contract, &alice(), &bob(), "blocked");
+        });
+    }
+
+    #[test]
+    fn rejected_schedule_does_not_advance_email_count() {
+        let (mut contract, _) = setup_mail();
+        set_context(&owner(), 1);
+        contract.set_max_scheduled_per_account(0);
+        assert_rejected_keeps_count(&mut contract, |contract| {
+            set_context(&alice(), 1);
+            contract.schedule_mail(bob(), "Later".to_string(), "soon".to_string(), None, 1_000);
+        });
+    }
+
+    #[test]
+    fn idempotent_replay_does_not_advance_email_count() {
+        let (mut contract, _) = setup_mail();
+        let email_id = idempotent(&mut contract, "req-1", 1);
+        let count = contract.email_count;
+
+        assert_eq!(idempotent(&mut contract, "req-1", 1), email_id);
+        assert_eq!(contract.email_count, count);
+        assert_rejected_keeps_count(&mut contract, |contract| {
+            idempotent(contract, "req-2", 0);
+        });
+    }
+
+    fn send_attached(contract: &mut Contract, attachments: Vec<Attachment>) -> EmailID {
+        set_context(&alice(), 1);
+        let options = SendOptions {
+            attachments: Some(attachments),
+            ..Default::default()
+        };
+        contract
+            .send_mail(
+                bob(),
+                "Files".to_string(),
+                "see attached".to_string(),
+                None,
+                Some(options),
+            )
+            .0
+    }
+
+    fn attachment(cid_len: usize) -> Attachment {
+        Attachment {
+            cid: "b".repeat(cid_len),
+            name: "report.pdf".to_string(),
+            size: 1 << 20,
+        }
+    }
+
+    #[test]
+    fn attachments_fill_the_byte_budget_exactly() {
+        let (mut contract, _) = setup_mail();
+        let files = vec![attachment(59), attachment(40)];
+        set_context(&owner(), 1);
+        contract.set_max_attachment_bytes(attachments_bytes(&files));
+
+        let email_id = send_attached(&mut contract, files.clone());
+        assert_eq!(
+            contract.get_email(U128(email_id)).unwrap().attachments,
+            files
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E027: Attachments exceed max_attachment_bytes by 1 bytes")]
+    fn attachments_one_byte_over_the_budget_are_rejected() {
+        let (mut contract, _) = setup_mail();
+        let files = vec![attachment(59), attachment(40)];
+        set_context(&owner(), 1);
+        contract.set_max_attachment_bytes(attachments_bytes(&files) - 1);
+        send_attached(&mut contract, files);
+    }
+
+    #[test]
+    fn attachments_are_charged_as_stored_bytes() {
+        let (mut contract, _) = setup_mail();
+        let files = vec![attachment(59)];
+        let estimate =
+            contract.estimate_send_cost(alice(), bob(), 5, 12, attachments_bytes(&files), None);
+        let bare = contract.estimate_send_cost(alice(), bob(), 5, 12, 0, None);
+        assert_eq!(
+            estimate.storage_yocto.0 - bare.storage_yocto.0,
+            contract.byte_cost() * Balance::from(attachments_bytes(&files))
+        );
+
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        send_attached(&mut contract, files);
+        let used = contract.accounts.get(&alice()).unwrap().used - before;
+        assert!(used <= estimate.storage_yocto.0);
+    }
+
+    #[test]
+    fn unread_is_grouped_by_sender() {
+        let (mut contract, first) = setup_mail();
+        register(&mut contract, &carol());
+        send(&mut contract, &alice(), &bob(), "again");
+        send(&mut contract, &carol(), &bob(), "hello");
+        assert_eq!(
+            contract.get_unread_by_sender(bob()),
+            vec![(alice(), 2), (carol(), 1)]
+        );
+
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(first));
+        assert_eq!(
+            contract.get_unread_by_sender(bob()),
+            vec![(alice(), 1), (carol(), 1)]
+        );
+        assert!(contract.get_unread_by_sender(alice()).is_empty());
+    }
+
+    #[test]
+    fn cleared_donation_contract_pays_the_spam_bond_again() {
+        let (mut contract, _) = setup_mail();
+        set_context(&owner(), 1);
+        contract.set_spam_bond(U128(1_000));
+        set_context(&owner(), 1);
+        contract.add_donation_contract_account(alice());
+        assert_eq!(contract.required_spam_bond(&alice(), &bob()), 0);
+
+        set_context(&owner(), 1);
+        contract.remove_donation_contract_account();
+        assert_eq!(contract.get_donation_contract_account(), None);
+        assert_eq!(contract.required_spam_bond(&alice(), &bob()), 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller is not owner")]
+    fn only_the_owner_clears_the_donation_contract() {
+        let (mut contract, _) = setup_mail();
+        set_context(&alice(), 1);
+        contract.remove_donation_contract_account();
+    }
+
+    #[test]
+    fn email_count_is_the_next_id_and_survives_deletes() {
+        let (mut contract, email_id) = setup_mail();
+        assert_eq!(contract.get_email_count(), U128(1));
+        set_context(&alice(), 1);
+        contract.delete_mail(U128(email_id));
+        assert_eq!(contract.get_email_count(), U128(1));
+
+        let next = contract.get_email_count();
+        assert_eq!(send(&mut contract, &alice(), &bob(), "next"), next.0);
+        assert_eq!(contract.get_email_count(), U128(2));
+    }
+
+    #[test]
+    fn send_mail_returns_the_id_it_stored() {
+        let (mut contract, first) = setup_mail();
+        let second = send(&mut contract, &alice(), &bob(), "second");
+        assert_eq!((first, second), (0, 1));
+        set_context(&bob(), 0);
+        let email = contract.get_email_private(U128(second)).unwrap();
+        assert_eq!(email.content, "second");
+    }
+
+    /// Leaves alice free storage for `len` bytes of mail.
+    fn fund_for(contract: &mut Contract, len: u64) {
+        let mut vaccount = contract.accounts.get(&alice()).unwrap();
+        vaccount.deposit = vaccount.used + contract.mail_storage_cost(len);
+        contract.accounts.insert(&alice(), &vaccount);
+    }
+
+    #[test]
+    #[should_panic(expected = "E012")]
+    fn content_past_the_cap_is_too_long() {
+        let (mut contract, _) = setup_mail();
+        send(
+            &mut contract,
+            &alice(),
+            &bob(),
+            &"x".repeat(MAX_CONTENT_LEN + 1),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E013")]
+    fn content_past_free_storage_exceeds_storage() {
+        let (mut contract, _) = setup_mail();
+        fund_for(&mut contract, 100);
+        assert_eq!(contract.get_max_content_len(alice()), 100);
+        send(&mut contract, &alice(), &bob(), &"x".repeat(100));
+    }
+
+    #[test]
+    fn max_content_len_is_the_tighter_limit() {
+        let (mut contract, _) = setup_mail();
+        assert_eq!(
+            contract.get_max_content_len(alice()),
+            MAX_CONTENT_LEN as u64
+        );
+        fund_for(&mut contract, 100);
+        assert_eq!(contract.get_max_content_len(alice()), 100);
+        assert_eq!(contract.get_max_content_len(carol()), 0);
+    }
+
+    #[test]
+    fn delete_credits_the_sender_its_storage() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+        set_context(&alice(), 1);
+        contract.delete_mail(U128(email_id));
+
+        let sender = contract.accounts.get(&alice()).unwrap();
+        assert_eq!(sender.charges.mail.count, 0);
+        assert_eq!(sender.used - sender.charges.deleted_mail.amount.0, before);
+        assert!(contract.get_mail_receive(bob()).is_empty());
+    }
+
+    #[test]
+    fn last_party_to_hide_releases_the_sender_storage() {
+        let (mut contract, email_id) = setup_mail();
+        set_context(&alice(), 1);
+        contract.hide_mail(U128(email_id));
+        assert_eq!(
+            contract.accounts.get(&alice()).unwrap().charges.mail.count,
+            1
+        );
+
+        set_context(&bob(), 1);
+        contract.hide_mail(U128(email_id));
+        assert!(contract.emails.get(&email_id).is_none());
+        assert_eq!(
+            contract.accounts.get(&alice()).unwrap().charges.mail.count,
+            0
+        );
+    }
+
+    #[test]
+    fn first_read_emits_one_mail_read_event() {
+        let (mut contract, email_id) = setup_mail();
+        testing_env!(context(&bob(), 1).block_timestamp(42).build());
+        contract.mark_as_read(U128(email_id));
+        let read = events("mail_read");
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0]["email_id"], "0");
+        assert_eq!(read[0]["reader"], bob().to_string());
+        assert_eq!(read[0]["read_at"], 42);
+
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(email_id));
+        assert!(events("mail_read").is_empty());
+    }
+
+    #[test]
+    fn read_event_follows_the_on_read_pref() {
+        let (mut contract, email_id) = setup_mail();
+        set_context(&bob(), 1);
+        contract.set_notification_prefs(NotificationPrefs {
+            on_read: false,
+            ..Default::default()
+        });
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(email_id));
+        assert!(events("mail_read").is_empty());
+    }
+
+    fn send_titled(contract: &mut Contract, title: &str, content: &str) -> U128 {
+        set_context(&alice(), 1);
+        contract.send_mail(bob(), title.to_string(), content.to_string(), None, None)
+    }
+
+    #[test]
+    #[should_panic(expected = "E016")]
+    fn blank_mail_is_rejected_by_default() {
+        let (mut contract, _) = setup_mail();
+        send_titled(&mut contract, "  ", "");
+    }
+
+    #[test]
+    fn empty_mail_needs_only_one_part_or_the_owner_switch() {
+        let (mut contract, _) = setup_mail();
+        send_titled(&mut contract, "Subject only", "");
+        send_titled(&mut contract, "", "body only");
+
+        set_context(&owner(), 1);
+        contract.set_allow_empty_content(true);
+        assert!(contract.get_allow_empty_content());
+        send_titled(&mut contract, "", "");
+        assert_eq!(contract.get_mail_receive_num(bob()), 4);
+    }
+
+    #[test]
+    fn mail_set_prefixes_are_per_account_and_stable() {
+        let prefix = |account: &AccountId| {
+            StorageKeys::ReceiverMail {
+                account_hash: env::sha256(account.as_bytes()),
+            }
+            .try_to_vec()
+            .unwrap()
+        };
+        assert_eq!(prefix(&bob()), prefix(&bob()));
+        assert_ne!(prefix(&bob()), prefix(&alice()));
+
+        // Mail that arrives after the first still lands in the same set.
+        let (mut contract, first) = setup_mail();
+        register(&mut contract, &carol());
+        send(&mut contract, &carol(), &bob(), "later");
+        let inbox = contract.receivers.get(&bob()).unwrap();
+        assert_eq!(inbox.to_vec(), vec![first, 1]);
+        assert!(contract.receivers.get(&carol()).is_none());
+    }
+
+    #[test]
+    fn membership_checks_follow_each_side() {
+        let (mut contract, email_id) = setup_mail();
+        let email_id = U128(email_id);
+        assert!(contract.has_sent(alice(), email_id));
+        assert!(contract.has_received(bob(), email_id));
+        assert!(!contract.has_sent(bob(), email_id));
+        assert!(!contract.has_received(alice(), email_id));
+        assert!(!contract.has_received(carol(), email_id));
+
+        set_context(&bob(), 1);
+        contract.hide_mail(email_id);
+        assert!(!contract.has_received(bob(), email_id));
+        assert!(contract.has_sent(alice(), email_id));
+    }
+
+    #[test]
+    fn send_result_matches_the_storage_balance_after_the_send() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        send(&mut contract, &alice(), &bob(), "first");
+
+        set_context(&alice(), 1);
+        let result =
+            contract.send_mail_v2(bob(), "Hello".to_string(), "second".to_string(), None, None);
+        assert_eq!(result.email_id, U128(1));
+        let balance = contract.storage_balance_of(alice()).unwrap();
+        assert_eq!(result.storage_remaining, balance.available);
+        assert_eq!(result.storage_used.0, balance.total.0 - balance.available.0);
     }
 }
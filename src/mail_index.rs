@@ -0,0 +1,548 @@
+use crate::*;
+use near_sdk::Balance;
+
+/// Most trailing tombstones one removal pops from an order vector; the rest go
+/// with later removals or `compact_mail_order`.
+pub const MAX_ORDER_COMPACTION: u64 = 16;
+
+/// Bytes the runtime counts for every stored record on top of its key and value.
+const STORAGE_RECORD_OVERHEAD: u64 = 40;
+
+/// Which of an account's order vectors a slot is in.
+#[derive(Clone, Copy)]
+pub(crate) enum OrderSide {
+    Sent,
+    Received,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Pops up to `limit` trailing tombstones from `account`'s order vectors,
+    /// settling what each was charged. Anyone may crank it. Returns whether both
+    /// vectors now end in live mail or are gone.
+    #[payable]
+    pub fn compact_mail_order(&mut self, account: AccountId, limit: u64) -> bool {
+        assert_one_yocto();
+        self.assert_batch_size(limit);
+        let mut budget = limit;
+        self.internal_compact_order(OrderSide::Sent, &account, &mut budget)
+            && self.internal_compact_order(OrderSide::Received, &account, &mut budget)
+    }
+
+    /// Indexes up to `limit` mail ids from the migration cursor onward into the
+    /// order vectors. Anyone may crank it; reads use sorted sets until it is done.
+    #[payable]
+    pub fn migrate_mail_index(&mut self, limit: u64) -> bool {
+        assert_one_yocto();
+        self.assert_batch_size(limit);
+        assert!(self.is_legacy_mail_migrated(), "Legacy mail not migrated");
+        let end = self
+            .email_count
+            .min(self.mail_index_cursor + EmailID::from(limit));
+        for email_id in self.mail_index_cursor..end {
+            if let Some(email) = self.emails.get(&email_id) {
+                self.internal_push_order(&email.sender, &email.receiver, email_id);
+            }
+        }
+        self.mail_index_cursor = end;
+        self.is_mail_index_migrated()
+    }
+
+    pub fn is_mail_index_migrated(&self) -> bool {
+        self.mail_index_cursor >= self.email_count
+    }
+
+    /// Inbox page by position: returns the live mail among positions
+    /// `from_index..from_index + limit`, so the next page starts at
+    /// `from_index + limit` however many of these were removed.
+    pub fn get_mail_receive_paginated(
+        &self,
+        receiver: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<EmailView> {
+        let receiver_vec = match self.receivers.get(&receiver) {
+            Some(receiver_vec) => receiver_vec,
+            None => return Vec::new(),
+        };
+        let positions: Vec<EmailID> = match self.ordered(&self.receiver_order, &receiver) {
+            Some(order) => {
+                let end = order.len().min(from_index.saturating_add(limit));
+                (from_index..end)
+                    .filter_map(|index| order.get(index))
+                    .collect()
+            }
+            None => sorted_ids(&receiver_vec)
+                .into_iter()
+                .skip(from_index as usize)
+                .take(limit as usize)
+                .collect(),
+        };
+        positions
+            .into_iter()
+            .filter(|email_id| receiver_vec.contains(email_id))
+            .filter_map(|email_id| {
+                self.emails
+                    .get(&email_id)
+                    .map(|email| EmailView::new(email_id, email))
+            })
+            .collect()
+    }
+
+    /// Inbox page by cursor: up to `limit` live mail with ids above
+    /// `after_email_id`, seeking to the cursor rather than scanning from the top.
+    pub fn get_mail_receive_after(
+        &self,
+        receiver: AccountId,
+        after_email_id: Option<U128>,
+        limit: u64,
+    ) -> Vec<EmailView> {
+        let receiver_vec = match self.receivers.get(&receiver) {
+            Some(receiver_vec) => receiver_vec,
+            None => return Vec::new(),
+        };
+        let after = after_email_id.map(|after| after.0);
+        let ids: Vec<EmailID> = match self.ordered(&self.receiver_order, &receiver) {
+            Some(order) => (seek_after(&order, after)..order.len())
+                .filter_map(|index| order.get(index))
+                .filter(|email_id| receiver_vec.contains(email_id))
+                .take(limit as usize)
+                .collect(),
+            None => sorted_ids(&receiver_vec)
+                .into_iter()
+                .filter(|email_id| after.is_none_or(|after| *email_id > after))
+                .take(limit as usize)
+                .collect(),
+        };
+        ids.into_iter()
+            .filter_map(|email_id| {
+                self.emails
+                    .get(&email_id)
+                    .map(|email| EmailView::new(email_id, email))
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_index_mail(
+        &mut self,
+        sender: &AccountId,
+        receiver: &AccountId,
+        email_id: EmailID,
+    ) {
+        // Mail inserted while the migration is behind is picked up by the cursor.
+        if self.mail_index_cursor == email_id {
+            self.internal_push_order(sender, receiver, email_id);
+            self.mail_index_cursor += 1;
+        }
+    }
+
+    /// Appends to both parties' order vectors. The sets stay the source of truth
+    /// for membership; an id missing from the set is that slot's tombstone, and
+    /// only trailing tombstones are ever popped. Keeping the sets means
+    /// membership checks and removals stay O(1) rather than scanning a vector.
+    fn internal_push_order(&mut self, sender: &AccountId, receiver: &AccountId, email_id: EmailID) {
+        let mut order = self.sender_order.get(sender).unwrap_or_else(|| {
+            Vector::new(StorageKeys::AccountSenderOrder {
+                account_id: sender.clone(),
+            })
+        });
+        order.push(&email_id);
+        self.sender_order.insert(sender, &order);
+
+        let mut order = self.receiver_order.get(receiver).unwrap_or_else(|| {
+            Vector::new(StorageKeys::AccountReceiverOrder {
+                account_id: receiver.clone(),
+            })
+        });
+        order.push(&email_id);
+        self.receiver_order.insert(receiver, &order);
+    }
+
+    /// Pops `owner`'s trailing tombstones on one side within `budget`. Slots in
+    /// the middle stay, so positions before them never move. Returns whether
+    /// the vector now ends in live mail or is gone.
+    pub(crate) fn internal_compact_order(
+        &mut self,
+        side: OrderSide,
+        owner: &AccountId,
+        budget: &mut u64,
+    ) -> bool {
+        let mut order = match self.order_index(side).get(owner) {
+            Some(order) => order,
+            None => return true,
+        };
+        let len = order.len();
+        let done = loop {
+            let email_id = match order.len().checked_sub(1).and_then(|last| order.get(last)) {
+                Some(email_id) => email_id,
+                None => break true,
+            };
+            if self.holds_slot(side, owner, email_id) {
+                break true;
+            }
+            if *budget == 0 {
+                break false;
+            }
+            order.pop();
+            *budget -= 1;
+            self.internal_settle_tombstone(side, owner, email_id);
+        };
+        if order.is_empty() {
+            self.order_index_mut(side).remove(owner);
+        } else if order.len() < len {
+            self.order_index_mut(side).insert(owner, &order);
+        }
+        done
+    }
+
+    /// Compacts both parties' vectors after one of them let go of a mail.
+    pub(crate) fn internal_compact_parties(&mut self, email: &Email) {
+        let mut budget = MAX_ORDER_COMPACTION;
+        self.internal_compact_order(OrderSide::Sent, &email.sender, &mut budget);
+        let mut budget = MAX_ORDER_COMPACTION;
+        self.internal_compact_order(OrderSide::Received, &email.receiver, &mut budget);
+    }
+
+    /// Leaves the purged mail's order slots that could not be popped to `payer`
+    /// as tombstones, each charged its slot and its record until it is popped.
+    /// A tombstone the payer cannot cover is carried by the contract.
+    pub(crate) fn internal_leave_tombstones(
+        &mut self,
+        payer: &AccountId,
+        email_id: EmailID,
+        email: &Email,
+    ) {
+        let mut vaccount = match self.accounts.get(payer) {
+            Some(vaccount) => vaccount,
+            None => return,
+        };
+        for (side, owner) in [
+            (OrderSide::Sent, &email.sender),
+            (OrderSide::Received, &email.receiver),
+        ] {
+            let slot_held = self
+                .order_index(side)
+                .get(owner)
+                .is_some_and(|order| order_holds(&order, email_id));
+            if !slot_held {
+                continue;
+            }
+            let item = slot_item(side, owner, email_id);
+            let record = (payer.clone(), 0);
+            let bytes = slot_bytes(side, owner) + record_bytes(&item, &record);
+            let amount = self.byte_cost() * Balance::from(bytes);
+            if vaccount.deposit - vaccount.used < amount {
+                continue;
+            }
+            let before = env::storage_usage();
+            self.internal_link(payer, ChargeKind::Tombstone, item.clone());
+            let amount = amount + self.stored_cost_since(before);
+            vaccount.charge(ChargeKind::Tombstone, amount);
+            self.order_tombstones
+                .insert(&item, &(payer.clone(), amount));
+        }
+        self.accounts.insert(payer, &vaccount);
+    }
+
+    fn internal_settle_tombstone(&mut self, side: OrderSide, owner: &AccountId, email_id: EmailID) {
+        let item = slot_item(side, owner, email_id);
+        if let Some((payer, amount)) = self.order_tombstones.remove(&item) {
+            self.internal_unlink(&payer, ChargeKind::Tombstone, item);
+            if let Some(mut vaccount) = self.accounts.get(&payer) {
+                vaccount.release(ChargeKind::Tombstone, amount);
+                self.accounts.insert(&payer, &vaccount);
+            }
+        }
+    }
+
+    /// What a tombstone `item` was charged, for the storage audit.
+    pub(crate) fn tombstone_charge(&self, item: &str) -> Option<Balance> {
+        self.order_tombstones
+            .get(&item.to_string())
+            .map(|(_, amount)| amount)
+    }
+
+    fn holds_slot(&self, side: OrderSide, owner: &AccountId, email_id: EmailID) -> bool {
+        match side {
+            OrderSide::Sent => self.is_sender_of(owner, email_id),
+            OrderSide::Received => self.holds_received(owner, email_id),
+        }
+    }
+
+    fn order_index(&self, side: OrderSide) -> &LookupMap<AccountId, Vector<EmailID>> {
+        match side {
+            OrderSide::Sent => &self.sender_order,
+            OrderSide::Received => &self.receiver_order,
+        }
+    }
+
+    fn order_index_mut(&mut self, side: OrderSide) -> &mut LookupMap<AccountId, Vector<EmailID>> {
+        match side {
+            OrderSide::Sent => &mut self.sender_order,
+            OrderSide::Received => &mut self.receiver_order,
+        }
+    }
+
+    /// The account's order vector, once the migration has made it complete.
+    pub(crate) fn ordered(
+        &self,
+        index: &LookupMap<AccountId, Vector<EmailID>>,
+        account_id: &AccountId,
+    ) -> Option<Vector<EmailID>> {
+        if self.is_mail_index_migrated() {
+            index.get(account_id)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn inbox_ids(&self, receiver: &AccountId) -> Vec<EmailID> {
+        match self.receivers.get(receiver) {
+            Some(receiver_vec) => {
+                live_ids(self.ordered(&self.receiver_order, receiver), &receiver_vec)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn outbox_ids(&self, sender: &AccountId) -> Vec<EmailID> {
+        match self.senders.get(sender) {
+            Some(sender_vec) => live_ids(self.ordered(&self.sender_order, sender), &sender_vec),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn sorted_ids(set: &UnorderedSet<EmailID>) -> Vec<EmailID> {
+    let mut ids: Vec<EmailID> = set.iter().collect();
+    ids.sort_unstable();
+    ids
+}
+
+/// First position in `order` holding an id above `after`. Ids are pushed in
+/// increasing order, so the vector is sorted and can be bisected.
+fn seek_after(order: &Vector<EmailID>, after: Option<EmailID>) -> u64 {
+    let after = match after {
+        Some(after) => after,
+        None => return 0,
+    };
+    let (mut low, mut high) = (0, order.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if order.get(mid).is_some_and(|email_id| email_id <= after) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// Whether `order` still has a slot for `email_id`, by bisecting the sorted ids.
+fn order_holds(order: &Vector<EmailID>, email_id: EmailID) -> bool {
+    let position = seek_after(order, email_id.checked_sub(1));
+    order.get(position) == Some(email_id)
+}
+
+/// Names one order slot, as `sent:` or `received:`, the owner and the mail id.
+fn slot_item(side: OrderSide, owner: &AccountId, email_id: EmailID) -> String {
+    let side = match side {
+        OrderSide::Sent => "sent",
+        OrderSide::Received => "received",
+    };
+    format!("{side}:{owner}:{email_id}")
+}
+
+/// What one slot of `owner`'s order vector takes: the vector prefix and index
+/// as key, the id as value, and the per-record overhead.
+fn slot_bytes(side: OrderSide, owner: &AccountId) -> u64 {
+    let account_id = owner.clone();
+    let prefix = match side {
+        OrderSide::Sent => StorageKeys::AccountSenderOrder { account_id },
+        OrderSide::Received => StorageKeys::AccountReceiverOrder { account_id },
+    };
+    let key_len = prefix.try_to_vec().unwrap().len() as u64 + 8;
+    key_len + 16 + STORAGE_RECORD_OVERHEAD
+}
+
+/// What one `order_tombstones` record takes.
+fn record_bytes(item: &String, record: &(AccountId, Balance)) -> u64 {
+    let key_len =
+        StorageKeys::OrderTombstones.try_to_vec().unwrap().len() + item.try_to_vec().unwrap().len();
+    let value_len = record.try_to_vec().unwrap().len();
+    (key_len + value_len) as u64 + STORAGE_RECORD_OVERHEAD
+}
+
+fn live_ids(order: Option<Vector<EmailID>>, set: &UnorderedSet<EmailID>) -> Vec<EmailID> {
+    match order {
+        Some(order) => order
+            .iter()
+            .filter(|email_id| set.contains(email_id))
+            .collect(),
+        None => sorted_ids(set),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MAX_ORDER_COMPACTION;
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn page(contract: &Contract, from_index: u64, limit: u64) -> Vec<EmailID> {
+        contract
+            .get_mail_receive_paginated(bob(), from_index, limit)
+            .into_iter()
+            .map(|view| view.email_id.0)
+            .collect()
+    }
+
+    fn setup_inbox(count: usize) -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        for _ in 0..count {
+            send(&mut contract, &alice(), &bob(), "content");
+        }
+        contract
+    }
+
+    #[test]
+    fn removing_early_mail_keeps_later_positions() {
+        let mut contract = setup_inbox(4);
+        assert_eq!(page(&contract, 2, 2), vec![2, 3]);
+
+        set_context(&bob(), 1);
+        contract.hide_mail(U128(0));
+
+        assert_eq!(page(&contract, 0, 2), vec![1]);
+        assert_eq!(page(&contract, 2, 2), vec![2, 3]);
+    }
+
+    fn after(contract: &Contract, after_email_id: Option<EmailID>, limit: u64) -> Vec<EmailID> {
+        contract
+            .get_mail_receive_after(bob(), after_email_id.map(U128), limit)
+            .into_iter()
+            .map(|view| view.email_id.0)
+            .collect()
+    }
+
+    #[test]
+    fn cursor_page_seeks_past_removed_mail() {
+        let mut contract = setup_inbox(5);
+        set_context(&bob(), 1);
+        contract.hide_mail(U128(2));
+
+        assert_eq!(after(&contract, None, 2), vec![0, 1]);
+        assert_eq!(after(&contract, Some(1), 2), vec![3, 4]);
+        assert_eq!(after(&contract, Some(2), 10), vec![3, 4]);
+        assert!(after(&contract, Some(4), 10).is_empty());
+    }
+
+    #[test]
+    fn cursor_page_matches_before_migration() {
+        let mut contract = setup_inbox(4);
+        contract.mail_index_cursor = 0;
+        assert_eq!(after(&contract, Some(0), 2), vec![1, 2]);
+    }
+
+    #[test]
+    fn migration_is_bounded_and_catches_up() {
+        let mut contract = setup_inbox(3);
+        contract.sender_order.remove(&alice());
+        contract.receiver_order.remove(&bob());
+        contract.mail_index_cursor = 0;
+        send(&mut contract, &alice(), &bob(), "late");
+        assert!(!contract.is_mail_index_migrated());
+        assert_eq!(page(&contract, 0, 10), vec![0, 1, 2, 3]);
+
+        set_context(&owner(), 1);
+        assert!(!contract.migrate_mail_index(2));
+        assert!(contract.migrate_mail_index(10));
+
+        assert_eq!(page(&contract, 0, 10), vec![0, 1, 2, 3]);
+        assert_eq!(contract.receiver_order.get(&bob()).unwrap().len(), 4);
+        assert_eq!(contract.outbox_ids(&alice()), vec![0, 1, 2, 3]);
+    }
+
+    fn drop_everywhere(contract: &mut Contract, email_id: EmailID) {
+        for account_id in [alice(), bob()] {
+            set_context(&account_id, 1);
+            contract.hide_mail(U128(email_id));
+        }
+    }
+
+    fn order_len(index: &LookupMap<AccountId, Vector<EmailID>>, account_id: &AccountId) -> u64 {
+        index.get(account_id).map(|order| order.len()).unwrap_or(0)
+    }
+
+    fn tombstones(contract: &Contract) -> Charge {
+        contract.accounts.get(&alice()).unwrap().charges.tombstones
+    }
+
+    #[test]
+    fn removing_the_newest_mail_pops_its_slots() {
+        let mut contract = setup_inbox(3);
+        drop_everywhere(&mut contract, 2);
+
+        assert_eq!(order_len(&contract.receiver_order, &bob()), 2);
+        assert_eq!(order_len(&contract.sender_order, &alice()), 2);
+        assert_eq!(tombstones(&contract).count, 0);
+    }
+
+    #[test]
+    fn middle_tombstones_stay_charged_until_popped() {
+        let mut contract = setup_inbox(3);
+        drop_everywhere(&mut contract, 2);
+        let used = contract.accounts.get(&alice()).unwrap().used;
+        let second = contract.emails.get(&1).unwrap().storage_charged.0;
+        send(&mut contract, &alice(), &bob(), "again");
+        drop_everywhere(&mut contract, 1);
+
+        assert_eq!(order_len(&contract.receiver_order, &bob()), 3);
+        assert_eq!(tombstones(&contract).count, 2);
+        assert!(tombstones(&contract).amount.0 > 0);
+        set_context(&owner(), 1);
+        let audit = contract.audit_storage(alice()).unwrap();
+        assert_eq!(audit.drift, U128(0));
+
+        drop_everywhere(&mut contract, 3);
+        assert_eq!(order_len(&contract.receiver_order, &bob()), 1);
+        assert_eq!(order_len(&contract.sender_order, &alice()), 1);
+        assert_eq!(tombstones(&contract).amount, U128(0));
+        assert_eq!(contract.accounts.get(&alice()).unwrap().used, used - second);
+    }
+
+    #[test]
+    fn emptied_vectors_are_removed_across_compaction_cranks() {
+        // Dropping the last mail compacts the receiver's vector twice over: once
+        // when it is purged and once as the receiver lets go of it.
+        let count = 2 * MAX_ORDER_COMPACTION as usize + 4;
+        let mut contract = setup_inbox(count);
+        for email_id in 0..count as EmailID {
+            drop_everywhere(&mut contract, email_id);
+        }
+        assert_eq!(order_len(&contract.receiver_order, &bob()), 4);
+        assert!(tombstones(&contract).count > 0);
+
+        set_context(&carol(), 1);
+        assert!(!contract.compact_mail_order(bob(), 2));
+        set_context(&carol(), 1);
+        assert!(contract.compact_mail_order(bob(), 10));
+
+        assert!(contract.sender_order.get(&alice()).is_none());
+        assert!(contract.receiver_order.get(&bob()).is_none());
+        assert_eq!(tombstones(&contract).count, 0);
+        assert_eq!(tombstones(&contract).amount, U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn migration_requires_one_yocto() {
+        let mut contract = setup_inbox(1);
+        set_context(&owner(), 0);
+        contract.migrate_mail_index(1);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_inbox_root(&self, account: AccountId) -> Base64VecU8 {
+        let mut email_ids: Vec<EmailID> = self
+            .receivers
+            .get(&account)
+            .map(|receiver_vec| receiver_vec.to_vec())
+            .unwrap_or_default();
+        email_ids.sort_unstable();
+
+        let mut leaves: Vec<Vec<u8>> = Vec::new();
+        for email_id in email_ids {
+            if let Some(mail) = self.emails.get(&email_id) {
+                let mut leaf = email_id.to_le_bytes().to_vec();
+                leaf.extend(env::sha256(mail.content.as_bytes()));
+                leaves.push(env::sha256(&leaf));
+            }
+        }
+        Base64VecU8(merkle_root(leaves))
+    }
+}
+
+pub(crate) fn merkle_root(mut nodes: Vec<Vec<u8>>) -> Vec<u8> {
+    if nodes.is_empty() {
+        return env::sha256(&[]);
+    }
+    while nodes.len() > 1 {
+        nodes = nodes
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => env::sha256(&[left.as_slice(), right.as_slice()].concat()),
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    nodes.remove(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merkle_root;
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn leaf(email_id: EmailID, content: &str) -> Vec<u8> {
+        let mut leaf = email_id.to_le_bytes().to_vec();
+        leaf.extend(env::sha256(content.as_bytes()));
+        env::sha256(&leaf)
+    }
+
+    #[test]
+    fn inbox_root_commits_to_each_mail_in_id_order() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        assert_eq!(contract.get_inbox_root(bob()).0, env::sha256(&[]));
+
+        send(&mut contract, &alice(), &bob(), "first");
+        assert_eq!(contract.get_inbox_root(bob()).0, leaf(0, "first"));
+        send(&mut contract, &alice(), &bob(), "second");
+        let root = [leaf(0, "first"), leaf(1, "second")].concat();
+        assert_eq!(contract.get_inbox_root(bob()).0, env::sha256(&root));
+    }
+
+    #[test]
+    fn odd_node_is_carried_up_unhashed() {
+        let nodes = vec![vec![1], vec![2], vec![3]];
+        let left = env::sha256(&[1, 2]);
+        let root = env::sha256(&[left, vec![3]].concat());
+        assert_eq!(merkle_root(nodes), root);
+    }
+}
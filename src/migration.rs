@@ -0,0 +1,272 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// The flat per-mail charge the baseline took, which converted mail keeps.
+pub(crate) const BASELINE_STORAGE_PER_MAIL: Balance = 10;
+
+/// The contract state as the baseline deployment stored it.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct OldContract {
+    pub senders: LookupMap<AccountId, UnorderedSet<EmailID>>,
+    pub receivers: LookupMap<AccountId, UnorderedSet<EmailID>>,
+    pub emails: UnorderedMap<EmailID, OldEmail>,
+    pub email_count: u128,
+    pub accounts: LookupMap<AccountId, OldVAccount>,
+    pub donation_contract_account: Option<AccountId>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct OldEmail {
+    pub title: String,
+    pub content: String,
+    pub timestamp: u64,
+    pub fee: Option<U128>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct OldVAccount {
+    pub deposit: Balance,
+    pub used: Balance,
+}
+
+/// The parties of one baseline mail, which the baseline record did not keep.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LegacyMail {
+    pub email_id: U128,
+    pub sender: AccountId,
+    pub receiver: AccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Converts baseline mail from the legacy cursor onward, examining at most
+    /// `limit` ids. `mail` names the parties of every id still stored, in id
+    /// order; each pair is checked against the baseline indexes, so anyone may
+    /// crank it.
+    #[payable]
+    pub fn migrate_legacy_mail(&mut self, mail: Vec<LegacyMail>, limit: u64) -> bool {
+        assert_one_yocto();
+        self.assert_batch_size(limit);
+        let byte_cost = self.byte_cost();
+        let end = self
+            .legacy_mail_end
+            .min(self.legacy_mail_cursor + EmailID::from(limit));
+        let mut parties = mail.into_iter();
+        let mut email_id = self.legacy_mail_cursor;
+        while email_id < end {
+            let mut legacy: UnorderedMap<EmailID, OldEmail> = retyped(&self.emails);
+            if legacy.get(&email_id).is_some() {
+                let entry = match parties.next() {
+                    Some(entry) => entry,
+                    None => break,
+                };
+                assert!(entry.email_id.0 == email_id, "Legacy mail out of order");
+                assert!(
+                    self.is_sender_of(&entry.sender, email_id)
+                        && self.is_receiver_of(&entry.receiver, email_id),
+                    "Legacy mail parties do not match"
+                );
+                // Replacing in place would decode the old record as the new
+                // type, so the old record is removed under its own type first.
+                let old = legacy.remove(&email_id).unwrap();
+                self.emails = retyped(&legacy);
+                let mut email = Email::new(
+                    entry.sender,
+                    entry.receiver,
+                    old.title,
+                    old.content,
+                    old.fee,
+                );
+                email.timestamp = old.timestamp;
+                email.storage_charged = U128(byte_cost * BASELINE_STORAGE_PER_MAIL);
+                self.emails.insert(&email_id, &email);
+            }
+            email_id += 1;
+        }
+        self.legacy_mail_cursor = email_id;
+        self.is_legacy_mail_migrated()
+    }
+
+    /// Converts the baseline records of `accounts`, skipping any that are
+    /// unregistered or already converted. Returns how many were converted.
+    #[payable]
+    pub fn migrate_legacy_accounts(&mut self, accounts: Vec<AccountId>) -> u64 {
+        assert_one_yocto();
+        self.assert_batch_size(accounts.len() as u64);
+        let mut migrated = 0;
+        for account_id in accounts {
+            let old = match self.legacy_account(&account_id) {
+                Some(old) => old,
+                None => continue,
+            };
+            let account_part = old.used.min(self.min_deposit());
+            let sent = self
+                .senders
+                .get(&account_id)
+                .map(|sender_vec| sender_vec.len())
+                .unwrap_or(0);
+            let mut vaccount = VAccount::new(old.deposit, old.used);
+            vaccount.sent_count = sent;
+            vaccount.charges.account = Charge {
+                count: 1,
+                amount: U128(account_part),
+            };
+            vaccount.charges.mail = Charge {
+                count: sent,
+                amount: U128(old.used - account_part),
+            };
+            // `insert` would decode the old record as the new type.
+            env::storage_write(&account_key(&account_id), &vaccount.try_to_vec().unwrap());
+            self.registered_count += 1;
+            self.account_ids.insert(&account_id);
+            migrated += 1;
+        }
+        migrated
+    }
+
+    pub fn is_legacy_mail_migrated(&self) -> bool {
+        self.legacy_mail_cursor >= self.legacy_mail_end
+    }
+}
+
+impl Contract {
+    /// Builds the current state around the baseline collections, which keep
+    /// their prefixes. Mail and account records are still in the baseline
+    /// layout until the legacy cranks convert them.
+    pub(crate) fn from_legacy(owner_id: AccountId, old: OldContract) -> Self {
+        let mut contract = Self::new(owner_id);
+        contract.senders = old.senders;
+        contract.receivers = old.receivers;
+        contract.emails = retyped(&old.emails);
+        contract.email_count = old.email_count;
+        contract.legacy_mail_end = old.email_count;
+        contract.donation_contract_account = old.donation_contract_account;
+        contract
+    }
+
+    /// The baseline record of `account_id`, if it has not been converted. Both
+    /// layouts live under the same key, and only a baseline record is exactly
+    /// the two balances long.
+    fn legacy_account(&self, account_id: &AccountId) -> Option<OldVAccount> {
+        env::storage_read(&account_key(account_id))
+            .and_then(|raw| OldVAccount::try_from_slice(&raw).ok())
+    }
+}
+
+/// Where `accounts` keeps the record of `account_id`.
+fn account_key(account_id: &AccountId) -> Vec<u8> {
+    [
+        StorageKeys::Account.try_to_vec().unwrap(),
+        account_id.try_to_vec().unwrap(),
+    ]
+    .concat()
+}
+
+/// The same stored map under another record type; only the handle changes.
+fn retyped<V, W>(map: &UnorderedMap<EmailID, V>) -> UnorderedMap<EmailID, W>
+where
+    V: BorshSerialize + BorshDeserialize,
+    W: BorshSerialize + BorshDeserialize,
+{
+    UnorderedMap::try_from_slice(&map.try_to_vec().unwrap()).expect("Emails layout mismatch")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retyped, LegacyMail, OldEmail};
+    use crate::test_utils::*;
+    use crate::*;
+    use near_contract_standards::storage_management::StorageManagement;
+
+    fn parties(email_id: EmailID) -> LegacyMail {
+        LegacyMail {
+            email_id: U128(email_id),
+            sender: alice(),
+            receiver: bob(),
+        }
+    }
+
+    /// Baseline state with mail 0 and 2 from alice to bob; mail 1 was deleted.
+    fn migrated() -> Contract {
+        write_baseline_state(&[Some("first"), None, Some("third")]);
+        set_context(&contract_account(), 0);
+        Contract::migrate(owner())
+    }
+
+    #[test]
+    fn migrate_converts_baseline_state() {
+        let mut contract = migrated();
+        assert_eq!(contract.email_count, 3);
+        assert!(!contract.is_legacy_mail_migrated());
+        assert_eq!(contract.owner_id, owner());
+
+        set_context(&alice(), 1);
+        assert!(contract.migrate_legacy_mail(vec![parties(0), parties(2)], 10));
+        set_context(&alice(), 1);
+        assert_eq!(
+            contract.migrate_legacy_accounts(vec![alice(), bob(), carol()]),
+            2
+        );
+        set_context(&alice(), 1);
+        assert_eq!(contract.migrate_legacy_accounts(vec![alice()]), 0);
+
+        set_context(&bob(), 0);
+        let email = contract.get_email_private(U128(2)).unwrap();
+        assert_eq!(email.content, "third");
+        assert_eq!(email.sender, alice());
+        assert_eq!(email.timestamp, 7);
+        let balance = contract.storage_balance_of(alice()).unwrap();
+        assert_eq!(balance.total, U128(DEPOSIT));
+        assert_eq!(contract.get_registered_count(), 2);
+
+        // The converted state is fully usable.
+        send(&mut contract, &alice(), &bob(), "new");
+        set_context(&alice(), 1);
+        assert!(contract.migrate_mail_index(10));
+        assert_eq!(contract.get_mail_receive_paginated(bob(), 0, 10).len(), 3);
+    }
+
+    #[test]
+    fn legacy_fee_becomes_a_near_fee() {
+        let mut contract = migrated();
+        let mut legacy: UnorderedMap<EmailID, OldEmail> = retyped(&contract.emails);
+        let mut old = legacy.get(&0).unwrap();
+        old.fee = Some(U128(500));
+        legacy.insert(&0, &old);
+
+        set_context(&alice(), 1);
+        contract.migrate_legacy_mail(vec![parties(0), parties(2)], 10);
+        let email = contract.get_email(U128(0)).unwrap();
+        assert_eq!(email.fee, Some(FeeKind::Near(U128(500))));
+        assert_eq!(contract.get_email(U128(2)).unwrap().fee, None);
+    }
+
+    #[test]
+    fn legacy_mail_converts_in_bounded_steps() {
+        let mut contract = migrated();
+        set_context(&alice(), 1);
+        assert!(!contract.migrate_legacy_mail(vec![parties(0), parties(2)], 2));
+        assert_eq!(contract.legacy_mail_cursor, 2);
+        set_context(&alice(), 1);
+        assert!(contract.migrate_legacy_mail(vec![parties(2)], 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Legacy mail parties do not match")]
+    fn legacy_mail_rejects_wrong_parties() {
+        let mut contract = migrated();
+        set_context(&alice(), 1);
+        let mut entry = parties(0);
+        entry.receiver = carol();
+        contract.migrate_legacy_mail(vec![entry], 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "Legacy mail not migrated")]
+    fn mail_index_waits_for_legacy_mail() {
+        let mut contract = migrated();
+        set_context(&alice(), 1);
+        contract.migrate_mail_index(10);
+    }
+}
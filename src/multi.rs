@@ -0,0 +1,113 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DeliveryStats {
+    pub recipients: u64,
+    pub read: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn send_mail_multi(
+        &mut self,
+        receivers: Vec<AccountId>,
+        title: String,
+        content: String,
+        fee: Option<U128>,
+        options: Option<SendOptions>,
+    ) -> Vec<U128> {
+        assert!(!receivers.is_empty(), "No recipients");
+        self.assert_batch_size(receivers.len() as u64);
+        self.assert_recipient_count(receivers.len() as u64);
+        let sender = env::predecessor_account_id();
+        self.assert_cooldown_elapsed(&sender);
+        let options = options.unwrap_or_default();
+        let batch_id = self.email_count;
+        let mut remaining = env::attached_deposit();
+        let mut email_ids: Vec<U128> = Vec::new();
+
+        for receiver in receivers {
+            let mut email = Email::new(
+                sender.clone(),
+                receiver.clone(),
+                title.clone(),
+                content.clone(),
+                fee,
+            );
+            options.clone().apply(&mut email);
+            email.batch_id = Some(U128(batch_id));
+            self.apply_default_fee(&mut email);
+
+            let deposit = self.required_spam_bond(&sender, &receiver)
+                + email.bid.map(|bid| bid.0).unwrap_or(0)
+                + email.near_fee()
+                + 1;
+            require(remaining >= deposit, ContractError::AttachedDepositMismatch);
+            remaining -= deposit;
+            email_ids.push(U128(self.internal_send_batch_item(email, deposit)));
+        }
+        require(remaining == 0, ContractError::AttachedDepositMismatch);
+
+        self.delivery_stats.insert(
+            &batch_id,
+            &DeliveryStats {
+                recipients: email_ids.len() as u64,
+                read: 0,
+            },
+        );
+        email_ids
+    }
+
+    pub fn get_delivery_stats(&self, email_id: U128) -> Option<DeliveryStats> {
+        let batch_id = self
+            .emails
+            .get(&email_id.0)
+            .and_then(|email| email.batch_id)
+            .unwrap_or(email_id);
+        self.delivery_stats.get(&batch_id.0)
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_record_batch_read(&mut self, batch_id: EmailID) {
+        if let Some(mut stats) = self.delivery_stats.get(&batch_id) {
+            stats.read += 1;
+            self.delivery_stats.insert(&batch_id, &stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    #[test]
+    fn delivery_stats_count_each_first_read_across_the_batch() {
+        let mut contract = setup();
+        for account in [alice(), bob(), carol()] {
+            register(&mut contract, &account);
+        }
+        set_context(&alice(), 2);
+        let email_ids = contract.send_mail_multi(
+            vec![bob(), carol()],
+            "Hello".to_string(),
+            "all".to_string(),
+            None,
+            None,
+        );
+        let stats = contract.get_delivery_stats(email_ids[1]).unwrap();
+        assert_eq!((stats.recipients, stats.read), (2, 0));
+
+        for _ in 0..2 {
+            set_context(&bob(), 1);
+            contract.mark_as_read(email_ids[0]);
+        }
+        let stats = contract.get_delivery_stats(email_ids[1]).unwrap();
+        assert_eq!((stats.recipients, stats.read), (2, 1));
+        assert!(contract.get_delivery_stats(U128(9)).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::*;
+use near_sdk::Balance;
+
+pub const STORAGE_PER_MUTED_THREAD: Balance = 10;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn mute_thread(&mut self, root_email_id: U128) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+
+        let mut muted_set = self.muted_threads.get(&account_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountMutedThreads {
+                account_id: account_id.clone(),
+            })
+        });
+        assert!(
+            !muted_set.contains(&root_email_id.0),
+            "Thread already muted"
+        );
+
+        let storage_cost = STORAGE_PER_MUTED_THREAD * self.byte_cost();
+        self.internal_charge_entry(
+            &mut vaccount,
+            &account_id,
+            ChargeKind::MutedThread,
+            root_email_id.0.to_string(),
+            storage_cost,
+        );
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+
+        muted_set.insert(&root_email_id.0);
+        self.muted_threads.insert(&account_id, &muted_set);
+    }
+
+    #[payable]
+    pub fn unmute_thread(&mut self, root_email_id: U128) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut muted_set = self
+            .muted_threads
+            .get(&account_id)
+            .expect("Thread not muted");
+        assert!(muted_set.remove(&root_email_id.0), "Thread not muted");
+        self.muted_threads.insert(&account_id, &muted_set);
+
+        if let Some(mut vaccount) = self.accounts.get(&account_id) {
+            self.internal_release_entry(
+                &mut vaccount,
+                &account_id,
+                ChargeKind::MutedThread,
+                root_email_id.0.to_string(),
+            );
+            vaccount.last_active = env::block_timestamp();
+            self.accounts.insert(&account_id, &vaccount);
+        }
+    }
+
+    pub fn is_thread_muted(&self, account: AccountId, root_email_id: U128) -> bool {
+        self.muted_threads
+            .get(&account)
+            .map(|muted_set| muted_set.contains(&root_email_id.0))
+            .unwrap_or(false)
+    }
+}
+
+impl Contract {
+    pub(crate) fn is_thread_muted_by(
+        &self,
+        account_id: &AccountId,
+        email_id: EmailID,
+        email: &Email,
+    ) -> bool {
+        let root = email.thread_root.map(|root| root.0).unwrap_or(email_id);
+        self.is_thread_muted(account_id.clone(), U128(root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    #[test]
+    fn muted_thread_drops_out_of_the_unread_count() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let root = U128(send(&mut contract, &alice(), &bob(), "root"));
+        set_context(&bob(), 1);
+        let reply = contract.reply_mail(root, "reply".to_string(), None, false);
+        set_context(&alice(), 1);
+        contract.reply_mail(reply, "again".to_string(), None, false);
+        send(&mut contract, &alice(), &bob(), "unrelated");
+        assert_eq!(contract.get_unread_count(bob()), 3);
+
+        set_context(&bob(), 1);
+        contract.mute_thread(root);
+        assert!(contract.is_thread_muted(bob(), root));
+        assert!(!contract.is_thread_muted(alice(), root));
+        assert_eq!(contract.get_unread_count(bob()), 1);
+
+        set_context(&bob(), 1);
+        contract.unmute_thread(root);
+        assert_eq!(contract.get_unread_count(bob()), 3);
+        assert_eq!(
+            contract
+                .accounts
+                .get(&bob())
+                .unwrap()
+                .charges
+                .muted_threads
+                .count,
+            0
+        );
+    }
+}
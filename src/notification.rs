@@ -0,0 +1,142 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+pub const MAX_WEBHOOK_TAG_LEN: usize = 32;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NotificationPrefs {
+    pub on_new_mail: bool,
+    pub on_reaction: bool,
+    pub on_contact_request: bool,
+    pub on_read: bool,
+}
+
+impl Default for NotificationPrefs {
+    fn default() -> Self {
+        Self {
+            on_new_mail: true,
+            on_reaction: true,
+            on_contact_request: true,
+            on_read: true,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn set_notification_prefs(&mut self, prefs: NotificationPrefs) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.accounts.contains_key(&account_id),
+            "Account not registered"
+        );
+        self.notification_prefs.insert(&account_id, &prefs);
+        self.internal_touch(&account_id);
+    }
+
+    #[payable]
+    pub fn set_webhook_tag(&mut self, tag: Option<String>) {
+        assert_one_yocto();
+        if let Some(tag) = tag.as_ref() {
+            assert!(
+                !tag.is_empty() && tag.len() <= MAX_WEBHOOK_TAG_LEN,
+                "Webhook tag must be 1 to {} bytes",
+                MAX_WEBHOOK_TAG_LEN
+            );
+        }
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.webhook_tag = tag;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+    }
+
+    pub fn get_webhook_tag(&self, account: AccountId) -> Option<String> {
+        self.accounts
+            .get(&account)
+            .and_then(|vaccount| vaccount.webhook_tag)
+    }
+
+    pub fn get_notification_prefs(&self, account: AccountId) -> NotificationPrefs {
+        self.notification_prefs.get(&account).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MAX_WEBHOOK_TAG_LEN;
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn setup_prefs(prefs: NotificationPrefs) -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&bob(), 1);
+        contract.set_notification_prefs(prefs);
+        contract
+    }
+
+    #[test]
+    fn prefs_default_to_every_notification() {
+        let contract = setup();
+        let prefs = contract.get_notification_prefs(bob());
+        assert!(
+            prefs.on_new_mail && prefs.on_reaction && prefs.on_contact_request && prefs.on_read
+        );
+    }
+
+    #[test]
+    fn muted_new_mail_emits_no_event() {
+        let mut contract = setup_prefs(NotificationPrefs {
+            on_new_mail: false,
+            ..Default::default()
+        });
+        assert!(!contract.get_notification_prefs(bob()).on_new_mail);
+        send(&mut contract, &alice(), &bob(), "quiet");
+        assert!(events("new_mail").is_empty());
+
+        send(&mut contract, &bob(), &alice(), "loud");
+        assert_eq!(events("new_mail")[0]["receiver"], alice().to_string());
+    }
+
+    #[test]
+    fn muted_contact_requests_emit_no_event() {
+        let mut contract = setup_prefs(NotificationPrefs {
+            on_contact_request: false,
+            ..Default::default()
+        });
+        set_context(&alice(), 1);
+        contract.add_contact(bob());
+        assert!(events("contact_request").is_empty());
+    }
+
+    #[test]
+    fn webhook_tag_rides_on_new_mail_events() {
+        let mut contract = setup_prefs(NotificationPrefs::default());
+        set_context(&bob(), 1);
+        contract.set_webhook_tag(Some("inbox-7".to_string()));
+        assert_eq!(contract.get_webhook_tag(bob()), Some("inbox-7".to_string()));
+        send(&mut contract, &alice(), &bob(), "tagged");
+        assert_eq!(events("new_mail")[0]["webhook_tag"], "inbox-7");
+
+        set_context(&bob(), 1);
+        contract.set_webhook_tag(None);
+        send(&mut contract, &alice(), &bob(), "untagged");
+        assert!(events("new_mail")[0].get("webhook_tag").is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Webhook tag must be 1 to 32 bytes")]
+    fn overlong_webhook_tag_is_rejected() {
+        let mut contract = setup_prefs(NotificationPrefs::default());
+        set_context(&bob(), 1);
+        contract.set_webhook_tag(Some("t".repeat(MAX_WEBHOOK_TAG_LEN + 1)));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn set_min_stranger_fee(&mut self, fee: U128) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.min_stranger_fee = fee;
+    }
+
+    pub fn get_min_stranger_fee(&self) -> U128 {
+        self.min_stranger_fee
+    }
+
+    #[payable]
+    pub fn set_spam_bond(&mut self, bond: U128) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.spam_bond = bond;
+    }
+
+    pub fn get_spam_bond(&self) -> U128 {
+        self.spam_bond
+    }
+
+    #[payable]
+    pub fn set_max_recipients(&mut self, max_recipients: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.max_recipients = max_recipients;
+    }
+
+    pub fn get_max_recipients(&self) -> u64 {
+        self.max_recipients
+    }
+
+    #[payable]
+    pub fn set_burn_account(&mut self, account: Option<AccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.burn_account = account;
+    }
+
+    pub fn get_burn_account(&self) -> Option<AccountId> {
+        self.burn_account.clone()
+    }
+
+    #[payable]
+    pub fn set_fee_claim_window(&mut self, window: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.fee_claim_window = window;
+    }
+
+    pub fn get_fee_claim_window(&self) -> u64 {
+        self.fee_claim_window
+    }
+
+    #[payable]
+    pub fn set_base_fee(&mut self, fee: U128) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.base_fee = fee;
+    }
+
+    pub fn get_base_fee(&self) -> U128 {
+        self.base_fee
+    }
+
+    #[payable]
+    pub fn set_per_byte_fee(&mut self, fee: U128) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.per_byte_fee = fee;
+    }
+
+    pub fn get_per_byte_fee(&self) -> U128 {
+        self.per_byte_fee
+    }
+
+    #[payable]
+    pub fn set_allow_empty_content(&mut self, allow: bool) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.allow_empty_content = allow;
+    }
+
+    pub fn get_allow_empty_content(&self) -> bool {
+        self.allow_empty_content
+    }
+
+    #[payable]
+    pub fn set_default_fee(&mut self, fee: Option<U128>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.default_fee = fee;
+    }
+
+    pub fn get_default_fee(&self) -> Option<U128> {
+        self.default_fee
+    }
+
+    #[payable]
+    pub fn set_max_thread_depth(&mut self, depth: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.max_thread_depth = depth;
+    }
+
+    pub fn get_max_thread_depth(&self) -> u32 {
+        self.max_thread_depth
+    }
+
+    #[payable]
+    pub fn set_max_labels_per_account(&mut self, max_labels: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.max_labels_per_account = max_labels;
+    }
+
+    pub fn get_max_labels_per_account(&self) -> u64 {
+        self.max_labels_per_account
+    }
+
+    #[payable]
+    pub fn set_edit_window_ns(&mut self, window: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.edit_window_ns = window;
+    }
+
+    pub fn get_edit_window_ns(&self) -> u64 {
+        self.edit_window_ns
+    }
+
+    #[payable]
+    pub fn set_system_account(&mut self, account: AccountId) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.system_account = account;
+    }
+
+    pub fn get_system_account(&self) -> AccountId {
+        self.system_account.clone()
+    }
+
+    #[payable]
+    pub fn set_max_scheduled_per_account(&mut self, max_scheduled: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.max_scheduled_per_account = max_scheduled;
+    }
+
+    pub fn get_max_scheduled_per_account(&self) -> u64 {
+        self.max_scheduled_per_account
+    }
+
+    #[payable]
+    pub fn set_storage_cost_override(&mut self, cost: Option<U128>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.storage_cost_override = cost;
+    }
+
+    pub fn get_storage_cost_override(&self) -> Option<U128> {
+        self.storage_cost_override
+    }
+
+    #[payable]
+    pub fn set_max_batch_size(&mut self, max_batch_size: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.max_batch_size = max_batch_size;
+    }
+
+    pub fn get_max_batch_size(&self) -> u64 {
+        self.max_batch_size
+    }
+
+    #[payable]
+    pub fn set_bonus_storage_bytes(&mut self, bytes: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.bonus_storage_bytes = bytes;
+    }
+
+    pub fn get_bonus_storage_bytes(&self) -> u64 {
+        self.bonus_storage_bytes
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    /// Share of every claimed fee, in basis points, routed to the donation
+    /// contract while one is set.
+    #[payable]
+    pub fn set_donation_bps(&mut self, donation_bps: u16) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            donation_bps <= BPS_DENOMINATOR,
+            "Donation share exceeds 100%"
+        );
+        self.donation_bps = donation_bps;
+    }
+
+    pub fn get_donation_bps(&self) -> u16 {
+        self.donation_bps
+    }
+
+    /// Gas attached to each `donate` call that forwards a claimed fee's
+    /// donation share.
+    #[payable]
+    pub fn set_donation_gas(&mut self, gas: Gas) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.donation_gas = gas;
+    }
+
+    pub fn get_donation_gas(&self) -> Gas {
+        self.donation_gas
+    }
+
+    /// Caps the total serialized size of a mail's attachments.
+    #[payable]
+    pub fn set_max_attachment_bytes(&mut self, max_bytes: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.max_attachment_bytes = max_bytes;
+    }
+
+    pub fn get_max_attachment_bytes(&self) -> u64 {
+        self.max_attachment_bytes
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Caller is not owner"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_priority_inbox(&self, receiver: AccountId, limit: u64) -> Vec<EmailView> {
+        let mut email_vec: Vec<EmailView> = Vec::new();
+        if let Some(receiver_vec) = self.receivers.get(&receiver) {
+            for index in receiver_vec.iter() {
+                if let Some(mail) = self.emails.get(&index) {
+                    email_vec.push(EmailView::new(index, mail));
+                }
+            }
+        }
+        sort_by_priority(&mut email_vec);
+        email_vec.truncate(limit as usize);
+        email_vec
+    }
+}
+
+pub(crate) fn sort_by_priority(email_vec: &mut [EmailView]) {
+    email_vec.sort_by(|a, b| {
+        let a_bid = a.email.bid.map(|bid| bid.0).unwrap_or(0);
+        let b_bid = b.email.bid.map(|bid| bid.0).unwrap_or(0);
+        b_bid.cmp(&a_bid).then(b.email_id.0.cmp(&a.email_id.0))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn send_bid(contract: &mut Contract, bid: Balance, content: &str) -> EmailID {
+        set_context(&alice(), bid + 1);
+        let options = SendOptions {
+            bid: Some(U128(bid)),
+            ..Default::default()
+        };
+        contract
+            .send_mail(
+                bob(),
+                "Hello".to_string(),
+                content.to_string(),
+                None,
+                Some(options),
+            )
+            .0
+    }
+
+    #[test]
+    fn higher_bids_rank_first_then_newer_mail() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let low = send_bid(&mut contract, 100, "low");
+        let none = send(&mut contract, &alice(), &bob(), "none");
+        let high = send_bid(&mut contract, 300, "high");
+        let tie = send_bid(&mut contract, 100, "tie");
+
+        let ids: Vec<EmailID> = contract
+            .get_priority_inbox(bob(), 10)
+            .iter()
+            .map(|view| view.email_id.0)
+            .collect();
+        assert_eq!(ids, vec![high, tie, low, none]);
+        assert_eq!(contract.get_priority_inbox(bob(), 2).len(), 2);
+    }
+
+    #[test]
+    fn bid_is_paid_to_the_receiver_on_read() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = send_bid(&mut contract, 300, "high");
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(email_id));
+        assert_eq!(transfers(), vec![(bob(), 300)]);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum CanMessageResult {
+    Allowed { required_fee: U128 },
+    SenderNotRegistered,
+    MailboxFrozen,
+    SenderSuspended,
+    CooldownActive,
+    SenderBlocked,
+    SenderNotAllowed,
+    StorageFull,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn can_message(&self, sender: AccountId, receiver: AccountId) -> CanMessageResult {
+        let vaccount = match self.accounts.get(&sender) {
+            Some(vaccount) => vaccount,
+            None => return CanMessageResult::SenderNotRegistered,
+        };
+        if self.is_spam_suspended(&sender) {
+            return CanMessageResult::SenderSuspended;
+        }
+        let now = env::block_timestamp();
+        let cooldown = current_cooldown(&vaccount, now);
+        if cooldown > 0 && now < vaccount.last_sent_at + cooldown {
+            return CanMessageResult::CooldownActive;
+        }
+        if self.is_frozen(receiver.clone()) {
+            return CanMessageResult::MailboxFrozen;
+        }
+        if self.is_blocked_by(&receiver, &sender) {
+            return CanMessageResult::SenderBlocked;
+        }
+        if self.get_allowlist_only(receiver.clone()) && !self.is_allowed_sender(&receiver, &sender)
+        {
+            return CanMessageResult::SenderNotAllowed;
+        }
+        if !self.can_afford_send(sender.clone(), 0) && !self.can_use_reservation(&receiver, 0) {
+            return CanMessageResult::StorageFull;
+        }
+
+        let required_fee = if self.get_reject_paid_mail(receiver.clone())
+            || self.is_donation_contract(&sender)
+            || self.is_donation_contract(&receiver)
+        {
+            0
+        } else {
+            self.required_fee(&sender, &receiver, 0)
+        };
+        CanMessageResult::Allowed {
+            required_fee: U128(required_fee),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    use super::CanMessageResult;
+
+    fn allowed(fee: Balance) -> CanMessageResult {
+        CanMessageResult::Allowed {
+            required_fee: U128(fee),
+        }
+    }
+
+    #[test]
+    fn can_message_names_the_first_gate_that_denies() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        assert!(contract.can_message(carol(), bob()) == CanMessageResult::SenderNotRegistered);
+
+        set_context(&owner(), 1);
+        contract.set_min_stranger_fee(U128(500));
+        assert!(contract.can_message(alice(), bob()) == allowed(500));
+        set_context(&bob(), 1);
+        contract.add_contact(alice());
+        assert!(contract.can_message(alice(), bob()) == allowed(0));
+
+        set_context(&bob(), 1);
+        contract.block_sender(alice());
+        assert!(contract.can_message(alice(), bob()) == CanMessageResult::SenderBlocked);
+        set_context(&bob(), 1);
+        contract.freeze_mailbox();
+        assert!(contract.can_message(alice(), bob()) == CanMessageResult::MailboxFrozen);
+    }
+
+    #[test]
+    fn can_message_reports_full_storage() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let mut vaccount = contract.accounts.get(&alice()).unwrap();
+        vaccount.deposit = vaccount.used;
+        contract.accounts.insert(&alice(), &vaccount);
+        assert!(contract.can_message(alice(), bob()) == CanMessageResult::StorageFull);
+    }
+}
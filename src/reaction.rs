@@ -0,0 +1,173 @@
+use crate::*;
+use near_sdk::collections::UnorderedMap;
+use near_sdk::serde_json::json;
+use near_sdk::Balance;
+
+pub const STORAGE_PER_REACTION: Balance = 10;
+pub const MAX_REACTION_LEN: usize = 16;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn react(&mut self, email_id: U128, reaction: String) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let account_id = env::predecessor_account_id();
+        assert!(
+            !reaction.is_empty() && reaction.len() <= MAX_REACTION_LEN,
+            "Invalid reaction"
+        );
+        let email = self.emails.get(&real_email_id).expect("Email not found");
+        assert!(
+            email.sender == account_id || email.receiver == account_id,
+            "Caller is not sender or receiver"
+        );
+
+        let mut reactions = self.reactions.get(&real_email_id).unwrap_or_else(|| {
+            UnorderedMap::new(StorageKeys::EmailReactions {
+                email_id: real_email_id,
+            })
+        });
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        if reactions.get(&account_id).is_none() {
+            let storage_cost = STORAGE_PER_REACTION * self.byte_cost();
+            self.internal_charge_entry(
+                &mut vaccount,
+                &account_id,
+                ChargeKind::Reaction,
+                real_email_id.to_string(),
+                storage_cost,
+            );
+        }
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+
+        reactions.insert(&account_id, &reaction);
+        self.reactions.insert(&real_email_id, &reactions);
+
+        let other = if email.sender == account_id {
+            email.receiver
+        } else {
+            email.sender
+        };
+        if self.get_notification_prefs(other.clone()).on_reaction {
+            emit_event(
+                "reaction",
+                json!({
+                    "email_id": email_id,
+                    "account_id": account_id,
+                    "reaction": reaction,
+                    "notify": other,
+                }),
+            );
+        }
+    }
+
+    #[payable]
+    pub fn remove_reaction(&mut self, email_id: U128) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let account_id = env::predecessor_account_id();
+        let mut reactions = self
+            .reactions
+            .get(&real_email_id)
+            .expect("Reaction not found");
+        assert!(
+            reactions.remove(&account_id).is_some(),
+            "Reaction not found"
+        );
+        self.reactions.insert(&real_email_id, &reactions);
+
+        if let Some(mut vaccount) = self.accounts.get(&account_id) {
+            self.internal_release_entry(
+                &mut vaccount,
+                &account_id,
+                ChargeKind::Reaction,
+                real_email_id.to_string(),
+            );
+            vaccount.last_active = env::block_timestamp();
+            self.accounts.insert(&account_id, &vaccount);
+        }
+    }
+
+    pub fn get_reactions_paginated(
+        &self,
+        email_id: U128,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<(AccountId, String)> {
+        if let Some(reactions) = self.reactions.get(&email_id.0) {
+            let keys = reactions.keys_as_vector();
+            let values = reactions.values_as_vector();
+            return (from_index..std::cmp::min(from_index + limit, keys.len()))
+                .map(|index| (keys.get(index).unwrap(), values.get(index).unwrap()))
+                .collect();
+        }
+        Vec::new()
+    }
+
+    pub fn get_reaction_count(&self, email_id: U128) -> u64 {
+        self.reactions
+            .get(&email_id.0)
+            .map(|reactions| reactions.len())
+            .unwrap_or(0)
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_clear_reactions(&mut self, email_id: EmailID) {
+        if let Some(mut reactions) = self.reactions.remove(&email_id) {
+            for account_id in reactions.keys() {
+                let item = email_id.to_string();
+                match self.accounts.get(&account_id) {
+                    Some(mut vaccount) => {
+                        self.internal_release_entry(
+                            &mut vaccount,
+                            &account_id,
+                            ChargeKind::Reaction,
+                            item,
+                        );
+                        self.accounts.insert(&account_id, &vaccount);
+                    }
+                    None => {
+                        self.internal_forget_entry(&account_id, ChargeKind::Reaction, item);
+                    }
+                }
+            }
+            reactions.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    #[test]
+    fn reactions_page_in_insertion_order() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = U128(send(&mut contract, &alice(), &bob(), "hi"));
+        set_context(&alice(), 1);
+        contract.react(email_id, "+1".to_string());
+        set_context(&bob(), 1);
+        contract.react(email_id, "heart".to_string());
+
+        assert_eq!(contract.get_reaction_count(email_id), 2);
+        assert_eq!(
+            contract.get_reactions_paginated(email_id, 0, 10),
+            vec![(alice(), "+1".to_string()), (bob(), "heart".to_string())]
+        );
+        assert_eq!(
+            contract.get_reactions_paginated(email_id, 1, 10),
+            vec![(bob(), "heart".to_string())]
+        );
+        assert!(contract.get_reactions_paginated(email_id, 2, 10).is_empty());
+        assert!(contract.get_reactions_paginated(U128(9), 0, 10).is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::*;
+
+pub const DEFAULT_MAX_THREAD_DEPTH: u32 = 64;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn reply_mail(
+        &mut self,
+        email_id: U128,
+        content: String,
+        fee: Option<U128>,
+        quote: bool,
+    ) -> U128 {
+        let real_email_id: EmailID = email_id.0;
+        let sender = env::predecessor_account_id();
+        let parent = self.emails.get(&real_email_id).expect("Email not found");
+        let receiver = if self.holds_received(&sender, real_email_id) {
+            parent.sender.clone()
+        } else if self.is_sender_of(&sender, real_email_id) {
+            parent.receiver.clone()
+        } else {
+            env::panic_str("Caller is not sender or receiver")
+        };
+
+        let title = if parent.title.starts_with("Re: ") {
+            parent.title.clone()
+        } else {
+            format!("Re: {}", parent.title)
+        };
+        let content = if quote {
+            quote_content(
+                &content,
+                &visible_content(&parent, &sender),
+                MAX_CONTENT_LEN,
+            )
+        } else {
+            content
+        };
+
+        let mut email = Email::new(sender, receiver, title, content, fee);
+        email.parent_id = Some(email_id);
+        email.thread_root = Some(parent.thread_root.unwrap_or(email_id));
+        email.thread_depth = parent.thread_depth + 1;
+        require(
+            email.thread_depth <= self.max_thread_depth,
+            ContractError::ThreadTooDeep,
+        );
+        U128(self.internal_send_mail(email))
+    }
+}
+
+pub(crate) fn quote_content(content: &str, parent_content: &str, max_len: usize) -> String {
+    let separator = "\n\n";
+    if content.len() + separator.len() >= max_len {
+        return content.to_string();
+    }
+
+    let quoted: Vec<String> = parent_content
+        .lines()
+        .map(|line| format!("> {}", line))
+        .collect();
+    let mut result = format!("{}{}{}", content, separator, quoted.join("\n"));
+    if result.len() > max_len {
+        let mut end = max_len;
+        while !result.is_char_boundary(end) {
+            end -= 1;
+        }
+        result.truncate(end);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn setup_depth(max_depth: u32) -> (Contract, U128) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_max_thread_depth(max_depth);
+        let root = U128(send(&mut contract, &alice(), &bob(), "root"));
+        (contract, root)
+    }
+
+    #[test]
+    fn reply_at_the_depth_limit_is_accepted() {
+        let (mut contract, root) = setup_depth(1);
+        assert_eq!(contract.get_max_thread_depth(), 1);
+        set_context(&bob(), 1);
+        let reply = contract.reply_mail(root, "reply".to_string(), None, false);
+        assert_eq!(contract.get_email(reply).unwrap().thread_depth, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E018")]
+    fn reply_past_the_depth_limit_is_rejected() {
+        let (mut contract, root) = setup_depth(1);
+        set_context(&bob(), 1);
+        let reply = contract.reply_mail(root, "reply".to_string(), None, false);
+        set_context(&alice(), 1);
+        contract.reply_mail(reply, "too deep".to_string(), None, false);
+    }
+}
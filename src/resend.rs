@@ -0,0 +1,186 @@
+use crate::*;
+use near_sdk::Balance;
+
+pub const RESEND_REQUEST_TITLE: &str = "Resend requested";
+pub const STORAGE_PER_DELETED_MAIL: Balance = 10;
+
+/// What is kept of a mail its sender deleted, so the receiver can still ask
+/// for it again.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct DeletedMail {
+    pub sender: AccountId,
+    pub receiver: AccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn request_resend(&mut self, email_id: U128) -> U128 {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let receiver = env::predecessor_account_id();
+        let record = self
+            .deleted_mail
+            .get(&real_email_id)
+            .expect("Email was not deleted by its sender");
+        require(
+            record.receiver == receiver,
+            ContractError::CallerNotReceiver,
+        );
+        self.internal_forget_deleted(real_email_id, &record);
+        let sender = record.sender;
+
+        let content = format!("{} asked you to resend email {}", receiver, real_email_id);
+        let mut vaccount = self
+            .accounts
+            .get(&receiver)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        let storage_cost =
+            self.mail_storage_cost((RESEND_REQUEST_TITLE.len() + content.len()) as u64);
+        require(
+            vaccount.deposit - vaccount.used >= storage_cost,
+            ContractError::NotDepositEnough,
+        );
+        vaccount.charge(ChargeKind::Mail, storage_cost);
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&receiver, &vaccount);
+
+        let system = self.system_account.clone();
+        let mut notice = Email::new(
+            system.clone(),
+            sender.clone(),
+            RESEND_REQUEST_TITLE.to_string(),
+            content,
+            None,
+        );
+        notice.parent_id = Some(email_id);
+        notice.storage_payer = Some(receiver.clone());
+        notice.storage_charged = U128(storage_cost);
+        let notice_id = self.internal_insert_email(&notice);
+        self.internal_link(&receiver, ChargeKind::Mail, notice_id.to_string());
+        self.internal_notify_new_mail(notice_id, &system, &sender);
+        U128(notice_id)
+    }
+}
+
+impl Contract {
+    /// Keeps the sender and receiver of a mail its sender deleted, charged to
+    /// the sender until the receiver asks for it or the sender closes. A sender
+    /// without room for the record still deletes; the mail just can't be
+    /// asked for again.
+    pub(crate) fn internal_record_deleted(&mut self, email_id: EmailID, receiver: AccountId) {
+        let sender = env::predecessor_account_id();
+        let mut vaccount = self.accounts.get(&sender).unwrap();
+        let storage_cost = STORAGE_PER_DELETED_MAIL * self.byte_cost();
+        if vaccount.deposit - vaccount.used < storage_cost {
+            return;
+        }
+        self.internal_charge_entry(
+            &mut vaccount,
+            &sender,
+            ChargeKind::DeletedMail,
+            email_id.to_string(),
+            storage_cost,
+        );
+        self.accounts.insert(&sender, &vaccount);
+        self.deleted_mail
+            .insert(&email_id, &DeletedMail { sender, receiver });
+    }
+
+    fn internal_forget_deleted(&mut self, email_id: EmailID, record: &DeletedMail) {
+        self.deleted_mail.remove(&email_id);
+        let item = email_id.to_string();
+        match self.accounts.get(&record.sender) {
+            Some(mut vaccount) => {
+                self.internal_release_entry(
+                    &mut vaccount,
+                    &record.sender,
+                    ChargeKind::DeletedMail,
+                    item,
+                );
+                self.accounts.insert(&record.sender, &vaccount);
+            }
+            None => {
+                self.internal_forget_entry(&record.sender, ChargeKind::DeletedMail, item);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn setup_deleted() -> (Contract, EmailID, Balance) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        register(&mut contract, &carol());
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+        set_context(&alice(), 1);
+        contract.delete_mail(U128(email_id));
+        (contract, email_id, before)
+    }
+
+    #[test]
+    fn resend_request_reaches_sender_after_delete() {
+        let (mut contract, email_id, before) = setup_deleted();
+        let record_cost = STORAGE_PER_DELETED_MAIL * contract.byte_cost();
+        assert_eq!(
+            contract.accounts.get(&alice()).unwrap().used,
+            before + record_cost
+        );
+
+        set_context(&bob(), 1);
+        let notice_id = contract.request_resend(U128(email_id));
+
+        let notice = contract.emails.get(&notice_id.0).unwrap();
+        assert_eq!(notice.sender, contract.system_account);
+        assert_eq!(notice.parent_id, Some(U128(email_id)));
+        assert!(contract.holds_received(&alice(), notice_id.0));
+        assert_eq!(contract.accounts.get(&alice()).unwrap().used, before);
+    }
+
+    #[test]
+    #[should_panic(expected = "E009")]
+    fn only_the_receiver_requests_resend() {
+        let (mut contract, email_id, _) = setup_deleted();
+        set_context(&carol(), 1);
+        contract.request_resend(U128(email_id));
+    }
+
+    #[test]
+    #[should_panic(expected = "Email was not deleted by its sender")]
+    fn resend_is_requested_once() {
+        let (mut contract, email_id, _) = setup_deleted();
+        set_context(&bob(), 1);
+        contract.request_resend(U128(email_id));
+        set_context(&bob(), 1);
+        contract.request_resend(U128(email_id));
+    }
+
+    #[test]
+    fn notices_come_from_the_configured_system_account() {
+        let (mut contract, email_id, _) = setup_deleted();
+        assert_eq!(contract.get_system_account(), contract_account());
+        set_context(&owner(), 1);
+        contract.set_system_account(carol());
+        assert_eq!(contract.get_system_account(), carol());
+
+        set_context(&bob(), 1);
+        let notice_id = contract.request_resend(U128(email_id));
+        let notice = contract.get_email(notice_id).unwrap();
+        assert_eq!(notice.sender, carol());
+        assert_eq!(notice.receiver, alice());
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller is not owner")]
+    fn only_the_owner_sets_the_system_account() {
+        let mut contract = setup();
+        set_context(&alice(), 1);
+        contract.set_system_account(alice());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::*;
+use near_sdk::Balance;
+
+pub const RESERVED_MAIL_LEN: u64 = 256;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn reserve_inbox(&mut self, count: u64) {
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        let reservation_cost = self.reserved_mail_cost() * Balance::from(count);
+        let amount = env::attached_deposit();
+        assert!(count > 0, "Count must be positive");
+        require(amount >= reservation_cost, ContractError::NotDepositEnough);
+
+        vaccount.deposit += amount;
+        vaccount.charge_many(ChargeKind::Reservation, count, reservation_cost);
+        vaccount.reserved_capacity += count;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+    }
+
+    pub fn get_reserved_capacity(&self, account: AccountId) -> u64 {
+        self.accounts
+            .get(&account)
+            .map(|vaccount| vaccount.reserved_capacity)
+            .unwrap_or(0)
+    }
+}
+
+impl Contract {
+    pub(crate) fn can_use_reservation(&self, receiver: &AccountId, content_len: u64) -> bool {
+        content_len <= RESERVED_MAIL_LEN && self.get_reserved_capacity(receiver.clone()) > 0
+    }
+
+    /// Turns one reserved slot into a charge of `storage_cost` for the mail.
+    pub(crate) fn internal_use_reservation(&mut self, receiver: &AccountId, storage_cost: Balance) {
+        let mut vaccount = self.accounts.get(receiver).unwrap();
+        vaccount.reserved_capacity -= 1;
+        vaccount.release_one(ChargeKind::Reservation);
+        vaccount.charge(ChargeKind::Mail, storage_cost);
+        self.accounts.insert(receiver, &vaccount);
+    }
+
+    pub(crate) fn reserved_mail_cost(&self) -> Balance {
+        self.mail_storage_cost(RESERVED_MAIL_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    use super::RESERVED_MAIL_LEN;
+
+    /// Bob reserves two slots; alice has no free storage of her own.
+    fn setup_reserved() -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&bob(), 2 * contract.reserved_mail_cost());
+        contract.reserve_inbox(2);
+        let mut sender = contract.accounts.get(&alice()).unwrap();
+        sender.deposit = sender.used;
+        contract.accounts.insert(&alice(), &sender);
+        contract
+    }
+
+    #[test]
+    fn a_reserved_slot_pays_for_one_mail() {
+        let mut contract = setup_reserved();
+        assert_eq!(contract.get_reserved_capacity(bob()), 2);
+        send(&mut contract, &alice(), &bob(), "hi");
+
+        assert_eq!(contract.get_reserved_capacity(bob()), 1);
+        let charges = contract.accounts.get(&bob()).unwrap().charges;
+        assert_eq!(charges.reservations.count, 1);
+        assert_eq!(charges.mail.count, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E013")]
+    fn mail_longer_than_a_slot_cannot_use_it() {
+        let mut contract = setup_reserved();
+        send(
+            &mut contract,
+            &alice(),
+            &bob(),
+            &"x".repeat(RESERVED_MAIL_LEN as usize),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E002")]
+    fn reservation_needs_its_storage_up_front() {
+        let mut contract = setup();
+        register(&mut contract, &bob());
+        set_context(&bob(), 2 * contract.reserved_mail_cost() - 1);
+        contract.reserve_inbox(2);
+    }
+}
@@ -0,0 +1,416 @@
+use crate::label::label_key;
+use crate::stats::NANOS_PER_DAY;
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn set_retention(&mut self, retention_days: Option<u32>) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        if let Some(days) = retention_days {
+            assert!(days > 0, "Retention must be at least one day");
+        }
+        vaccount.retention_days = retention_days;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+    }
+
+    pub fn get_retention(&self, account: AccountId) -> Option<u32> {
+        self.accounts
+            .get(&account)
+            .and_then(|vaccount| vaccount.retention_days)
+    }
+
+    #[payable]
+    pub fn set_auto_purge_read_after(&mut self, after_ns: Option<u64>) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.auto_purge_read_after_ns = after_ns;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+    }
+
+    pub fn get_auto_purge_read_after(&self, account: AccountId) -> Option<u64> {
+        self.accounts
+            .get(&account)
+            .and_then(|vaccount| vaccount.auto_purge_read_after_ns)
+    }
+
+    pub fn apply_auto_purge(
+        &mut self,
+        account: AccountId,
+        cursor: Option<PurgeCursor>,
+        limit: u64,
+    ) -> PurgeProgress {
+        self.assert_batch_size(limit);
+        let after_ns = self
+            .get_auto_purge_read_after(account.clone())
+            .expect("No auto-purge policy");
+        let cutoff = env::block_timestamp().saturating_sub(after_ns);
+        self.internal_purge_received(&account, cursor, limit, |mail| {
+            mail.read_at.is_some_and(|read_at| read_at < cutoff)
+        })
+    }
+
+    pub fn apply_retention(
+        &mut self,
+        account: AccountId,
+        cursor: Option<PurgeCursor>,
+        limit: u64,
+    ) -> PurgeProgress {
+        self.assert_batch_size(limit);
+        let retention_days = self
+            .get_retention(account.clone())
+            .expect("No retention policy");
+        let cutoff =
+            env::block_timestamp().saturating_sub(u64::from(retention_days) * NANOS_PER_DAY);
+        self.internal_purge_received(&account, cursor, limit, |mail| mail.timestamp < cutoff)
+    }
+
+    pub fn purge_expired(
+        &mut self,
+        account: AccountId,
+        cursor: Option<PurgeCursor>,
+        limit: u64,
+    ) -> PurgeProgress {
+        self.assert_batch_size(limit);
+        let now = env::block_timestamp();
+        let last_active = self
+            .accounts
+            .get(&account)
+            .map(|vaccount| vaccount.last_active)
+            .unwrap_or(0);
+        self.internal_purge_received(&account, cursor, limit, |mail| {
+            is_expired(mail, last_active, now)
+        })
+    }
+}
+
+/// Where a purge sweep stopped. Containers are the inbox, the archive, each
+/// label and the trash, in that order; each is walked from its last position
+/// down, so removing a match only moves ids that were already examined.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurgeCursor {
+    pub container: u64,
+    /// Positions left to examine in `container`; `None` starts at its end.
+    pub position: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurgeProgress {
+    pub purged: u64,
+    /// Pass back to continue the sweep; `None` once every container is done.
+    pub cursor: Option<PurgeCursor>,
+}
+
+impl Contract {
+    /// Examines up to `limit` of the account's received ids from `cursor` on,
+    /// wherever they are filed, and drops the ones matching `matches`. Each
+    /// container visited counts against `limit` too, empty or not.
+    fn internal_purge_received(
+        &mut self,
+        account_id: &AccountId,
+        cursor: Option<PurgeCursor>,
+        limit: u64,
+        matches: impl Fn(&Email) -> bool,
+    ) -> PurgeProgress {
+        let mut cursor = cursor.unwrap_or(PurgeCursor {
+            container: 0,
+            position: None,
+        });
+        let mut budget = limit;
+        let mut purged: Vec<(EmailID, Email)> = Vec::new();
+        let next = loop {
+            let container = match self.received_container(account_id, cursor.container) {
+                Some(container) => container,
+                None => break None,
+            };
+            if budget == 0 {
+                break Some(cursor);
+            }
+            budget -= 1;
+            if let Some(container) = container {
+                let ids = container.as_vector();
+                let mut position = cursor.position.unwrap_or(ids.len()).min(ids.len());
+                while position > 0 && budget > 0 {
+                    position -= 1;
+                    budget -= 1;
+                    if let Some(email_id) = ids.get(position) {
+                        if let Some(mail) = self.emails.get(&email_id).filter(|mail| matches(mail))
+                        {
+                            purged.push((email_id, mail));
+                        }
+                    }
+                }
+                if position > 0 {
+                    cursor.position = Some(position);
+                    break Some(cursor);
+                }
+            }
+            cursor = PurgeCursor {
+                container: cursor.container + 1,
+                position: None,
+            };
+        };
+
+        for (email_id, mail) in purged.iter() {
+            self.internal_drop_received(account_id, *email_id, mail);
+        }
+        PurgeProgress {
+            purged: purged.len() as u64,
+            cursor: next,
+        }
+    }
+
+    /// The account's `index`th received container: `None` past the last one,
+    /// `Some(None)` for one that holds nothing.
+    fn received_container(
+        &self,
+        account_id: &AccountId,
+        index: u64,
+    ) -> Option<Option<UnorderedSet<EmailID>>> {
+        let label_count = self
+            .label_names
+            .get(account_id)
+            .map_or(0, |label_set| label_set.len());
+        match index {
+            0 => Some(self.receivers.get(account_id)),
+            1 => Some(self.archived.get(account_id)),
+            index if index < 2 + label_count => {
+                let label = self
+                    .label_names
+                    .get(account_id)
+                    .and_then(|label_set| label_set.as_vector().get(index - 2));
+                Some(label.and_then(|label| self.label_mail.get(&label_key(account_id, &label))))
+            }
+            index if index == 2 + label_count => Some(self.trash.get(account_id)),
+            _ => None,
+        }
+    }
+}
+
+fn is_expired(mail: &Email, receiver_last_active: u64, now: u64) -> bool {
+    let expires_at = match mail.expires_at {
+        Some(expires_at) => expires_at,
+        None => return false,
+    };
+    if mail.read_at.is_none() && receiver_last_active < mail.timestamp {
+        return now >= expires_at.saturating_add(mail.grace_period_ns);
+    }
+    now >= expires_at
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PurgeCursor, PurgeProgress};
+    use crate::stats::NANOS_PER_DAY;
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::testing_env;
+
+    fn setup_retention() -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&bob(), 1);
+        contract.set_retention(Some(1));
+        contract
+    }
+
+    fn run_retention(contract: &mut Contract) -> u64 {
+        testing_env!(context(&bob(), 0)
+            .block_timestamp(2 * NANOS_PER_DAY)
+            .build());
+        sweep(|cursor| contract.apply_retention(bob(), cursor, 2)).0
+    }
+
+    /// Runs a purge crank to completion, returning what it purged and how
+    /// many calls it took.
+    fn sweep(mut crank: impl FnMut(Option<PurgeCursor>) -> PurgeProgress) -> (u64, u64) {
+        let (mut purged, mut calls) = (0, 0);
+        let mut cursor = None;
+        loop {
+            let progress = crank(cursor);
+            purged += progress.purged;
+            calls += 1;
+            cursor = progress.cursor;
+            if cursor.is_none() {
+                return (purged, calls);
+            }
+        }
+    }
+
+    #[test]
+    fn retention_covers_every_container() {
+        let mut contract = setup_retention();
+        let ids: Vec<EmailID> = (0..4)
+            .map(|_| send(&mut contract, &alice(), &bob(), "old"))
+            .collect();
+        set_context(&bob(), 1);
+        contract.archive_mail(U128(ids[1]));
+        set_context(&bob(), 1);
+        contract.create_label("work".to_string());
+        set_context(&bob(), 1);
+        contract.move_mail(U128(ids[2]), None, Some("work".to_string()));
+        set_context(&bob(), 1);
+        contract.trash_mail(U128(ids[3]));
+
+        assert_eq!(run_retention(&mut contract), 4);
+        for email_id in ids {
+            assert!(!contract.holds_received(&bob(), email_id));
+        }
+        assert_eq!(contract.get_mail_send_num(alice()), 4);
+    }
+
+    #[test]
+    fn retention_examines_at_most_limit_ids() {
+        let mut contract = setup_retention();
+        for _ in 0..5 {
+            send(&mut contract, &alice(), &bob(), "new");
+        }
+
+        set_context(&bob(), 0);
+        let progress = contract.apply_retention(bob(), None, 3);
+        assert_eq!(progress.purged, 0);
+        assert_eq!(
+            progress.cursor,
+            Some(PurgeCursor {
+                container: 0,
+                position: Some(3),
+            })
+        );
+        // The inbox and its five ids, then the archive and the trash.
+        let progress = contract.apply_retention(bob(), None, 8);
+        assert_eq!((progress.purged, progress.cursor), (0, None));
+    }
+
+    #[test]
+    fn auto_purge_waits_for_the_read_threshold() {
+        let mut contract = setup_retention();
+        let email_id = send(&mut contract, &alice(), &bob(), "read me");
+        set_context(&bob(), 1);
+        contract.set_auto_purge_read_after(Some(1_000));
+        testing_env!(context(&bob(), 1).block_timestamp(100).build());
+        contract.mark_as_read(U128(email_id));
+
+        testing_env!(context(&carol(), 0).block_timestamp(1_050).build());
+        assert_eq!(contract.apply_auto_purge(bob(), None, 10).purged, 0);
+        testing_env!(context(&carol(), 0).block_timestamp(1_200).build());
+        assert_eq!(contract.apply_auto_purge(bob(), None, 10).purged, 1);
+        assert!(!contract.holds_received(&bob(), email_id));
+    }
+
+    #[test]
+    fn auto_purge_with_nothing_to_purge_stays_bounded() {
+        let mut contract = setup_retention();
+        for _ in 0..5 {
+            send(&mut contract, &alice(), &bob(), "unread");
+        }
+        set_context(&bob(), 1);
+        contract.set_auto_purge_read_after(Some(1));
+
+        testing_env!(context(&bob(), 0).block_timestamp(1_000).build());
+        let progress = contract.apply_auto_purge(bob(), None, 2);
+        assert_eq!(progress.purged, 0);
+        assert!(progress.cursor.is_some());
+        let (purged, calls) = sweep(|cursor| contract.apply_auto_purge(bob(), cursor, 2));
+        assert_eq!(purged, 0);
+        assert!(calls > 1);
+        assert_eq!(contract.get_mail_receive_num(bob()), 5);
+    }
+
+    fn send_expiring(contract: &mut Contract, grace_period_ns: u64) -> EmailID {
+        testing_env!(context(&alice(), 1).block_timestamp(50).build());
+        let options = SendOptions {
+            expires_at: Some(1_000),
+            grace_period_ns: Some(grace_period_ns),
+            ..Default::default()
+        };
+        contract
+            .send_mail(
+                bob(),
+                "Hello".to_string(),
+                "soon gone".to_string(),
+                None,
+                Some(options),
+            )
+            .0
+    }
+
+    #[test]
+    fn inactive_receiver_keeps_expired_mail_through_grace() {
+        let mut contract = setup_retention();
+        let email_id = send_expiring(&mut contract, 500);
+
+        testing_env!(context(&carol(), 0).block_timestamp(1_200).build());
+        assert_eq!(contract.purge_expired(bob(), None, 10).purged, 0);
+        testing_env!(context(&carol(), 0).block_timestamp(1_600).build());
+        assert_eq!(contract.purge_expired(bob(), None, 10).purged, 1);
+        assert!(!contract.holds_received(&bob(), email_id));
+    }
+
+    #[test]
+    fn active_receiver_loses_expired_mail_on_time() {
+        let mut contract = setup_retention();
+        send_expiring(&mut contract, 500);
+        testing_env!(context(&bob(), 1).block_timestamp(100).build());
+        contract.set_retention(None);
+
+        testing_env!(context(&carol(), 0).block_timestamp(1_200).build());
+        assert_eq!(contract.purge_expired(bob(), None, 10).purged, 1);
+    }
+
+    #[test]
+    fn expiry_sweep_with_nothing_expired_stays_bounded() {
+        let mut contract = setup_retention();
+        for _ in 0..5 {
+            send(&mut contract, &alice(), &bob(), "kept");
+        }
+        set_context(&carol(), 0);
+        let progress = contract.purge_expired(bob(), None, 2);
+        assert_eq!(progress.purged, 0);
+        assert!(progress.cursor.is_some());
+    }
+
+    #[test]
+    fn retention_releases_orphaned_sender_storage() {
+        let mut contract = setup_retention();
+        let before = contract.accounts.get(&alice()).unwrap().used;
+        let email_id = send(&mut contract, &alice(), &bob(), "old");
+        set_context(&alice(), 1);
+        contract.hide_mail(U128(email_id));
+
+        assert_eq!(run_retention(&mut contract), 1);
+        assert!(contract.emails.get(&email_id).is_none());
+        assert_eq!(contract.accounts.get(&alice()).unwrap().used, before);
+    }
+
+    #[test]
+    fn retention_releases_receiver_paid_mail() {
+        let mut contract = setup_retention();
+        set_context(&bob(), contract.reserved_mail_cost());
+        contract.reserve_inbox(1);
+        let mut sender = contract.accounts.get(&alice()).unwrap();
+        sender.deposit = sender.used;
+        contract.accounts.insert(&alice(), &sender);
+        let email_id = send(&mut contract, &alice(), &bob(), "old");
+
+        assert_eq!(run_retention(&mut contract), 1);
+        assert!(contract.emails.get(&email_id).is_none());
+        let receiver = contract.accounts.get(&bob()).unwrap();
+        assert_eq!(receiver.charges.mail.count, 0);
+        assert!(contract.account_links.get(&bob()).is_none());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+pub const DEFAULT_MAX_SCHEDULED_PER_ACCOUNT: u64 = 20;
+
+pub type ScheduleID = u128;
+
+/// A mail held by the contract until `deliver_at`. `email.storage_charged` is
+/// what the sender was charged for holding it.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ScheduledMail {
+    pub email: Email,
+    pub deliver_at: u64,
+    pub attached: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScheduledMailView {
+    pub schedule_id: U128,
+    pub receiver: AccountId,
+    pub title: String,
+    pub deliver_at: u64,
+    pub fee: Option<FeeKind>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Holds a mail until `deliver_at`. Nothing reaches the receiver until
+    /// `deliver_scheduled` delivers it: no inbox entry, no fee escrow and no
+    /// spam bond. The attached deposit is kept until then, and the mail is
+    /// checked again on delivery; one that no longer passes is dropped and the
+    /// deposit refunded. Held mail counts towards `max_scheduled_per_account`
+    /// until it is delivered.
+    #[payable]
+    pub fn schedule_mail(
+        &mut self,
+        receiver: AccountId,
+        title: String,
+        content: String,
+        fee: Option<U128>,
+        deliver_at: u64,
+    ) -> U128 {
+        assert!(
+            deliver_at > env::block_timestamp(),
+            "Delivery time must be in the future"
+        );
+        let sender = env::predecessor_account_id();
+        let attached = env::attached_deposit();
+        let mut email = Email::new(sender.clone(), receiver, title, content, fee);
+        self.prepare_mail(&mut email);
+        self.check_send(&email, attached)
+            .unwrap_or_else(|error| error.panic());
+        let mut queue = self.scheduled_mail.get(&sender).unwrap_or_else(|| {
+            Vector::new(StorageKeys::AccountScheduled {
+                account_id: sender.clone(),
+            })
+        });
+        require(
+            queue.len() < self.max_scheduled_per_account,
+            ContractError::ScheduledLimitReached,
+        );
+
+        let mut vaccount = self.accounts.get(&sender).unwrap();
+        let storage_cost = self.mail_storage_cost(email.stored_len());
+        require(
+            vaccount.deposit - vaccount.used >= storage_cost,
+            ContractError::NotDepositEnough,
+        );
+        vaccount.charge(ChargeKind::Scheduled, storage_cost);
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&sender, &vaccount);
+        email.storage_charged = U128(storage_cost);
+
+        let schedule_id = self.scheduled_count;
+        self.scheduled_count += 1;
+        self.scheduled_records.insert(
+            &schedule_id,
+            &ScheduledMail {
+                email,
+                deliver_at,
+                attached: U128(attached),
+            },
+        );
+        queue_in_order(&mut queue, deliver_at, schedule_id);
+        self.scheduled_mail.insert(&sender, &queue);
+        U128(schedule_id)
+    }
+
+    /// Delivers up to `limit` of `sender`'s held mail that is due, earliest
+    /// first. Anyone may crank it. Returns how many were taken off the queue,
+    /// delivered or refunded.
+    #[payable]
+    pub fn deliver_scheduled(&mut self, sender: AccountId, limit: u64) -> u64 {
+        assert_one_yocto();
+        self.assert_batch_size(limit);
+        let mut queue = match self.scheduled_mail.get(&sender) {
+            Some(queue) => queue,
+            None => return 0,
+        };
+        let now = env::block_timestamp();
+        let mut taken = 0;
+        while taken < limit {
+            match queue.len().checked_sub(1).and_then(|last| queue.get(last)) {
+                Some((deliver_at, _)) if deliver_at <= now => {}
+                _ => break,
+            }
+            let (_, schedule_id) = queue.pop().unwrap();
+            self.internal_deliver_scheduled(schedule_id, now);
+            taken += 1;
+        }
+        if queue.is_empty() {
+            self.scheduled_mail.remove(&sender);
+        } else {
+            self.scheduled_mail.insert(&sender, &queue);
+        }
+        taken
+    }
+
+    /// Held mail of `account` in delivery order, without bodies.
+    pub fn get_scheduled(
+        &self,
+        account: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ScheduledMailView> {
+        let queue = match self.scheduled_mail.get(&account) {
+            Some(queue) => queue,
+            None => return Vec::new(),
+        };
+        (0..queue.len())
+            .rev()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|index| {
+                let (deliver_at, schedule_id) = queue.get(index)?;
+                let record = self.scheduled_records.get(&schedule_id)?;
+                Some(ScheduledMailView {
+                    schedule_id: U128(schedule_id),
+                    receiver: record.email.receiver,
+                    title: record.email.title,
+                    deliver_at,
+                    fee: record.email.fee,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    fn internal_deliver_scheduled(&mut self, schedule_id: ScheduleID, now: u64) {
+        let record = match self.scheduled_records.remove(&schedule_id) {
+            Some(record) => record,
+            None => return,
+        };
+        let mut email = record.email;
+        if let Some(mut vaccount) = self.accounts.get(&email.sender) {
+            vaccount.release(ChargeKind::Scheduled, email.storage_charged.0);
+            self.accounts.insert(&email.sender, &vaccount);
+        }
+        email.storage_charged = U128(0);
+        email.timestamp = now;
+        match self.check_send_item(&email, record.attached.0) {
+            Ok(plan) => {
+                self.internal_deliver(email, plan);
+            }
+            Err(error) => {
+                env::log_str(&error.describe());
+                if record.attached.0 > 0 {
+                    Promise::new(email.sender).transfer(record.attached.0);
+                }
+            }
+        }
+    }
+
+    /// Drops a closed account's held mail within `budget`, refunding what was
+    /// attached to each. Returns whether the queue is gone.
+    pub(crate) fn internal_drop_scheduled(
+        &mut self,
+        account_id: &AccountId,
+        budget: &mut u64,
+    ) -> bool {
+        let mut queue = match self.scheduled_mail.get(account_id) {
+            Some(queue) => queue,
+            None => return true,
+        };
+        while *budget > 0 {
+            let (_, schedule_id) = match queue.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            *budget -= 1;
+            if let Some(record) = self.scheduled_records.remove(&schedule_id) {
+                if record.attached.0 > 0 {
+                    Promise::new(account_id.clone()).transfer(record.attached.0);
+                }
+            }
+        }
+        if queue.is_empty() {
+            self.scheduled_mail.remove(account_id);
+            true
+        } else {
+            self.scheduled_mail.insert(account_id, &queue);
+            false
+        }
+    }
+
+    /// What `account_id`'s held mail was charged, for the storage audit.
+    pub(crate) fn scheduled_charges(&self, account_id: &AccountId) -> Vec<Balance> {
+        self.scheduled_mail
+            .get(account_id)
+            .map(|queue| {
+                queue
+                    .iter()
+                    .filter_map(|(_, schedule_id)| self.scheduled_records.get(&schedule_id))
+                    .map(|record| record.email.storage_charged.0)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Keeps the queue sorted latest first, so the next delivery is popped from the
+/// end. The queue is at most `max_scheduled_per_account` long.
+fn queue_in_order(queue: &mut Vector<(u64, ScheduleID)>, deliver_at: u64, schedule_id: ScheduleID) {
+    queue.push(&(deliver_at, schedule_id));
+    let mut index = queue.len() - 1;
+    while index > 0 {
+        let before = queue.get(index - 1).unwrap();
+        if before.0 >= deliver_at {
+            break;
+        }
+        queue.replace(index, &before);
+        queue.replace(index - 1, &(deliver_at, schedule_id));
+        index -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::testing_env;
+
+    const LATER: u64 = 1_000;
+
+    fn schedule_at(contract: &mut Contract, deliver_at: u64) -> ScheduleID {
+        set_context(&alice(), 1);
+        contract
+            .schedule_mail(
+                bob(),
+                "Later".to_string(),
+                format!("soon {deliver_at}"),
+                None,
+                deliver_at,
+            )
+            .0
+    }
+
+    fn schedule(contract: &mut Contract) -> ScheduleID {
+        schedule_at(contract, LATER)
+    }
+
+    fn crank_at(contract: &mut Contract, now: u64) -> u64 {
+        testing_env!(context(&carol(), 1).block_timestamp(now).build());
+        contract.deliver_scheduled(alice(), 10)
+    }
+
+    fn setup_limit(max_scheduled: u64) -> Contract {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_max_scheduled_per_account(max_scheduled);
+        contract
+    }
+
+    fn used(contract: &Contract, account_id: &AccountId) -> Balance {
+        contract.accounts.get(account_id).unwrap().used
+    }
+
+    #[test]
+    fn scheduled_mail_is_held_until_delivery() {
+        let mut contract = setup_limit(2);
+        let used_before = used(&contract, &alice());
+        let schedule_id = schedule(&mut contract);
+
+        let scheduled = contract.get_scheduled(alice(), 0, 10);
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduled[0].schedule_id, U128(schedule_id));
+        assert_eq!(scheduled[0].deliver_at, LATER);
+        assert!(used(&contract, &alice()) > used_before);
+        assert_eq!(contract.get_mail_receive_num(bob()), 0);
+        assert_eq!(contract.get_email_count(), U128(0));
+
+        assert_eq!(crank_at(&mut contract, LATER - 1), 0);
+        assert_eq!(contract.get_mail_receive_num(bob()), 0);
+
+        assert_eq!(crank_at(&mut contract, LATER), 1);
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+        assert!(contract.get_scheduled(alice(), 0, 10).is_empty());
+        set_context(&bob(), 0);
+        let email = contract.get_email_private(U128(0)).unwrap();
+        assert_eq!(email.content, format!("soon {LATER}"));
+        assert_eq!(email.timestamp, LATER);
+        assert_eq!(
+            used(&contract, &alice()),
+            used_before + email.storage_charged.0
+        );
+    }
+
+    #[test]
+    fn delivery_goes_earliest_first_and_stops_at_the_limit() {
+        let mut contract = setup_limit(3);
+        schedule_at(&mut contract, 300);
+        schedule_at(&mut contract, 100);
+        schedule_at(&mut contract, 200);
+        let order: Vec<u64> = contract
+            .get_scheduled(alice(), 0, 10)
+            .iter()
+            .map(|view| view.deliver_at)
+            .collect();
+        assert_eq!(order, vec![100, 200, 300]);
+
+        assert_eq!(crank_at(&mut contract, 250), 2);
+        set_context(&bob(), 0);
+        assert_eq!(
+            contract.get_email_private(U128(0)).unwrap().content,
+            "soon 100"
+        );
+        testing_env!(context(&carol(), 1).block_timestamp(400).build());
+        assert_eq!(contract.deliver_scheduled(alice(), 0), 0);
+        assert_eq!(contract.get_scheduled(alice(), 0, 10).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E021")]
+    fn schedule_limit_is_enforced() {
+        let mut contract = setup_limit(1);
+        schedule(&mut contract);
+        schedule_at(&mut contract, LATER + 1);
+    }
+
+    #[test]
+    fn delivery_frees_a_schedule_slot() {
+        let mut contract = setup_limit(1);
+        schedule_at(&mut contract, 100);
+        crank_at(&mut contract, 100);
+        testing_env!(context(&alice(), 1).block_timestamp(100).build());
+        contract.schedule_mail(bob(), "Again".to_string(), "later".to_string(), None, LATER);
+        assert_eq!(contract.get_scheduled(alice(), 0, 10).len(), 1);
+    }
+
+    #[test]
+    fn failed_delivery_refunds_the_deposit_and_storage() {
+        let mut contract = setup_limit(1);
+        let used_before = used(&contract, &alice());
+        schedule(&mut contract);
+        set_context(&bob(), 1);
+        contract.block_sender(alice());
+
+        assert_eq!(crank_at(&mut contract, LATER), 1);
+        assert_eq!(contract.get_mail_receive_num(bob()), 0);
+        assert_eq!(used(&contract, &alice()), used_before);
+        assert_eq!(transfers(), vec![(alice(), 1)]);
+    }
+
+    #[test]
+    fn locked_sends_do_not_use_schedule_slots() {
+        let mut contract = setup_limit(1);
+        set_context(&alice(), 1);
+        let options = SendOptions {
+            locked_until: Some(LATER),
+            ..Default::default()
+        };
+        contract.send_mail(
+            bob(),
+            "Capsule".to_string(),
+            "open later".to_string(),
+            None,
+            Some(options),
+        );
+
+        assert!(contract.get_scheduled(alice(), 0, 10).is_empty());
+        schedule(&mut contract);
+        assert_eq!(contract.get_scheduled(alice(), 0, 10).len(), 1);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SearchQuery {
+    pub text: Option<String>,
+    pub sender: Option<AccountId>,
+    pub after_ts: Option<u64>,
+    pub before_ts: Option<u64>,
+    #[serde(default)]
+    pub unread_only: bool,
+    pub from_index: u64,
+    pub limit: u64,
+}
+
+impl SearchQuery {
+    fn matches(&self, email: &Email, text: &Option<String>) -> bool {
+        text.as_ref()
+            .is_none_or(|text| email.title.to_lowercase().contains(text))
+            && self
+                .sender
+                .as_ref()
+                .is_none_or(|sender| &email.sender == sender)
+            && self.after_ts.is_none_or(|ts| email.timestamp > ts)
+            && self.before_ts.is_none_or(|ts| email.timestamp < ts)
+            && (!self.unread_only || email.read_at.is_none())
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn search_sent_titles(
+        &self,
+        sender: AccountId,
+        query: String,
+        limit: u64,
+    ) -> Vec<EmailView> {
+        let query = query.to_lowercase();
+        self.outbox_ids(&sender)
+            .into_iter()
+            .rev()
+            .filter_map(|email_id| {
+                self.emails
+                    .get(&email_id)
+                    .map(|email| EmailView::new(email_id, email))
+            })
+            .filter(|view| view.email.title.to_lowercase().contains(&query))
+            .take(limit as usize)
+            .collect()
+    }
+
+    pub fn search(&self, account: AccountId, query: SearchQuery) -> Vec<EmailView> {
+        let text = query.text.as_ref().map(|text| text.to_lowercase());
+        self.inbox_ids(&account)
+            .into_iter()
+            .rev()
+            .filter_map(|email_id| {
+                self.emails
+                    .get(&email_id)
+                    .map(|email| EmailView::new(email_id, email))
+            })
+            .filter(|view| query.matches(&view.email, &text))
+            .skip(query.from_index as usize)
+            .take(query.limit as usize)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SearchQuery;
+    use crate::test_utils::*;
+    use crate::*;
+
+    use near_sdk::testing_env;
+
+    fn send_titled(contract: &mut Contract, from: &AccountId, title: &str, timestamp: u64) {
+        testing_env!(context(from, 1).block_timestamp(timestamp).build());
+        contract.send_mail(bob(), title.to_string(), "body".to_string(), None, None);
+    }
+
+    fn setup_search() -> Contract {
+        let mut contract = setup();
+        for account in [alice(), bob(), carol()] {
+            register(&mut contract, &account);
+        }
+        send_titled(&mut contract, &alice(), "Invoice March", 10);
+        send_titled(&mut contract, &carol(), "invoice april", 20);
+        send_titled(&mut contract, &alice(), "Lunch", 30);
+        send_titled(&mut contract, &alice(), "INVOICE May", 40);
+        contract
+    }
+
+    fn ids(views: Vec<EmailView>) -> Vec<u128> {
+        views.iter().map(|view| view.email_id.0).collect()
+    }
+
+    #[test]
+    fn outbox_search_matches_titles_newest_first() {
+        let contract = setup_search();
+        let found = contract.search_sent_titles(alice(), "invoice".to_string(), 10);
+        assert_eq!(ids(found), vec![3, 0]);
+        let found = contract.search_sent_titles(alice(), "Invoice".to_string(), 1);
+        assert_eq!(ids(found), vec![3]);
+        assert!(contract
+            .search_sent_titles(bob(), "invoice".to_string(), 10)
+            .is_empty());
+    }
+
+    fn query() -> SearchQuery {
+        SearchQuery {
+            text: None,
+            sender: None,
+            after_ts: None,
+            before_ts: None,
+            unread_only: false,
+            from_index: 0,
+            limit: 10,
+        }
+    }
+
+    #[test]
+    fn search_combines_every_filter() {
+        let mut contract = setup_search();
+        let invoices = SearchQuery {
+            text: Some("Invoice".to_string()),
+            ..query()
+        };
+        assert_eq!(ids(contract.search(bob(), invoices)), vec![3, 1, 0]);
+
+        let from_alice = SearchQuery {
+            text: Some("invoice".to_string()),
+            sender: Some(alice()),
+            before_ts: Some(40),
+            ..query()
+        };
+        assert_eq!(ids(contract.search(bob(), from_alice)), vec![0]);
+
+        set_context(&bob(), 1);
+        contract.mark_as_read(U128(3));
+        let unread_after = SearchQuery {
+            after_ts: Some(10),
+            unread_only: true,
+            ..query()
+        };
+        assert_eq!(ids(contract.search(bob(), unread_after)), vec![2, 1]);
+    }
+
+    #[test]
+    fn search_pages_after_filtering() {
+        let contract = setup_search();
+        let page = SearchQuery {
+            text: Some("invoice".to_string()),
+            from_index: 1,
+            limit: 1,
+            ..query()
+        };
+        assert_eq!(ids(contract.search(bob(), page)), vec![1]);
+    }
+}
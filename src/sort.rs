@@ -0,0 +1,103 @@
+use crate::priority::sort_by_priority;
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[allow(clippy::enum_variant_names)]
+pub enum SortMode {
+    NewestFirst,
+    OldestFirst,
+    PriorityFirst,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn set_inbox_sort(&mut self, mode: SortMode) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.inbox_sort = mode;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+    }
+
+    pub fn get_inbox_sort(&self, account: AccountId) -> SortMode {
+        self.accounts
+            .get(&account)
+            .map(|vaccount| vaccount.inbox_sort)
+            .unwrap_or(SortMode::NewestFirst)
+    }
+
+    pub fn get_mail_receive_sorted(
+        &self,
+        receiver: AccountId,
+        order: Option<SortMode>,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<EmailView> {
+        let order = order.unwrap_or_else(|| self.get_inbox_sort(receiver.clone()));
+        let mut email_vec: Vec<EmailView> = self
+            .inbox_ids(&receiver)
+            .into_iter()
+            .filter_map(|email_id| {
+                self.emails
+                    .get(&email_id)
+                    .map(|email| EmailView::new(email_id, email))
+            })
+            .collect();
+        match order {
+            SortMode::NewestFirst => email_vec.reverse(),
+            SortMode::OldestFirst => {}
+            SortMode::PriorityFirst => sort_by_priority(&mut email_vec),
+        }
+        email_vec
+            .into_iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    use super::SortMode;
+
+    fn ids(views: Vec<EmailView>) -> Vec<u128> {
+        views.iter().map(|view| view.email_id.0).collect()
+    }
+
+    #[test]
+    fn inbox_sort_pref_is_the_default_order() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        for content in ["one", "two", "three"] {
+            send(&mut contract, &alice(), &bob(), content);
+        }
+        assert!(contract.get_inbox_sort(bob()) == SortMode::NewestFirst);
+        assert_eq!(
+            ids(contract.get_mail_receive_sorted(bob(), None, 0, 10)),
+            vec![2, 1, 0]
+        );
+
+        set_context(&bob(), 1);
+        contract.set_inbox_sort(SortMode::OldestFirst);
+        assert_eq!(
+            ids(contract.get_mail_receive_sorted(bob(), None, 1, 10)),
+            vec![1, 2]
+        );
+        // An explicit order overrides the preference.
+        assert_eq!(
+            ids(contract.get_mail_receive_sorted(bob(), Some(SortMode::NewestFirst), 0, 1)),
+            vec![2]
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{Balance, Promise};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const SPAM_SCORE_SCALE: u64 = 10_000;
+pub const SPAM_SUSPEND_SCORE: u32 = 5_000;
+pub const SPAM_SUSPEND_MIN_REPORTS: u64 = 3;
+pub const BASE_SEND_COOLDOWN: u64 = 60_000_000_000;
+pub const MAX_SEND_COOLDOWN: u64 = 86_400_000_000_000;
+pub const COOLDOWN_BACKOFF_FACTOR: u64 = 2;
+pub const COOLDOWN_HALF_LIFE: u64 = 86_400_000_000_000;
+pub const SPAM_BOND_REFUND_WINDOW: u64 = 7 * 86_400_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SpamBond {
+    pub sender: AccountId,
+    pub amount: Balance,
+    pub posted_at: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn report_spam(&mut self, email_id: U128) {
+        assert_one_yocto();
+        let receiver = env::predecessor_account_id();
+        self.internal_report_spam(&receiver, email_id.0);
+    }
+
+    #[payable]
+    pub fn report_spam_many(&mut self, email_ids: Vec<U128>) -> Vec<AccountId> {
+        assert_one_yocto();
+        self.assert_batch_size(email_ids.len() as u64);
+        let receiver = env::predecessor_account_id();
+        let threshold = self.get_spam_block_threshold(receiver.clone());
+        let mut counts: BTreeMap<AccountId, u32> = BTreeMap::new();
+        let email_ids: BTreeSet<EmailID> =
+            email_ids.into_iter().map(|email_id| email_id.0).collect();
+        for email_id in email_ids {
+            // Repeats and earlier reports count once, rather than failing the batch.
+            if self.reported_emails.contains(&email_id) {
+                continue;
+            }
+            self.internal_report_spam(&receiver, email_id);
+            let sender = self.emails.get(&email_id).unwrap().sender;
+            *counts.entry(sender).or_insert(0) += 1;
+        }
+
+        let mut blocked = Vec::new();
+        if threshold == 0 {
+            return blocked;
+        }
+        for (sender, count) in counts {
+            if count >= threshold && !self.is_blocked_by(&receiver, &sender) {
+                self.internal_block(&receiver, &sender);
+                blocked.push(sender);
+            }
+        }
+        blocked
+    }
+
+    #[payable]
+    pub fn set_spam_block_threshold(&mut self, threshold: u32) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        vaccount.spam_block_threshold = threshold;
+        vaccount.last_active = env::block_timestamp();
+        self.accounts.insert(&account_id, &vaccount);
+    }
+
+    pub fn get_spam_block_threshold(&self, account: AccountId) -> u32 {
+        self.accounts
+            .get(&account)
+            .map(|vaccount| vaccount.spam_block_threshold)
+            .unwrap_or(0)
+    }
+
+    #[payable]
+    pub fn block_and_report(&mut self, email_id: U128, trash: bool) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let receiver = env::predecessor_account_id();
+        self.internal_report_spam(&receiver, real_email_id);
+        let email = self.emails.get(&real_email_id).unwrap();
+        if !self.is_blocked_by(&receiver, &email.sender) {
+            self.internal_block(&receiver, &email.sender);
+        }
+        if trash {
+            self.internal_trash(&receiver, real_email_id);
+        }
+    }
+
+    #[payable]
+    pub fn refund_bond(&mut self, email_id: U128) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let bond = self
+            .spam_bonds
+            .get(&real_email_id)
+            .expect("No spam bond for email");
+        assert!(
+            env::block_timestamp() >= bond.posted_at + SPAM_BOND_REFUND_WINDOW,
+            "Refund window not elapsed"
+        );
+        self.spam_bonds.remove(&real_email_id);
+        Promise::new(bond.sender).transfer(bond.amount);
+    }
+
+    pub fn get_email_bond(&self, email_id: U128) -> Option<U128> {
+        self.spam_bonds
+            .get(&email_id.0)
+            .map(|bond| U128(bond.amount))
+    }
+
+    pub fn is_reported(&self, email_id: U128) -> bool {
+        self.reported_emails.contains(&email_id.0)
+    }
+
+    pub fn get_current_cooldown(&self, account: AccountId) -> u64 {
+        if let Some(vaccount) = self.accounts.get(&account) {
+            return current_cooldown(&vaccount, env::block_timestamp());
+        }
+        0
+    }
+
+    pub fn get_spam_score(&self, account: AccountId) -> u32 {
+        if let Some(vaccount) = self.accounts.get(&account) {
+            if vaccount.sent_count == 0 {
+                return 0;
+            }
+            let score = vaccount.spam_reports * SPAM_SCORE_SCALE / vaccount.sent_count;
+            return score.min(SPAM_SCORE_SCALE) as u32;
+        }
+        0
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_report_spam(&mut self, receiver: &AccountId, real_email_id: EmailID) {
+        assert!(
+            self.holds_received(receiver, real_email_id),
+            "Caller is not receiver"
+        );
+        assert!(
+            self.reported_emails.insert(&real_email_id),
+            "Email already reported"
+        );
+
+        self.internal_touch(receiver);
+        let email = self.emails.get(&real_email_id).unwrap();
+        if let Some(mut vaccount) = self.accounts.get(&email.sender) {
+            let now = env::block_timestamp();
+            let current = current_cooldown(&vaccount, now);
+            vaccount.spam_reports += 1;
+            vaccount.min_send_interval = if current == 0 {
+                BASE_SEND_COOLDOWN
+            } else {
+                current
+                    .saturating_mul(COOLDOWN_BACKOFF_FACTOR)
+                    .min(MAX_SEND_COOLDOWN)
+            };
+            vaccount.min_send_interval_set_at = now;
+            self.accounts.insert(&email.sender, &vaccount);
+        }
+
+        if let Some(bond) = self.spam_bonds.remove(&real_email_id) {
+            self.internal_burn(bond.amount);
+        }
+        emit_event(
+            "spam_reported",
+            json!({
+                "email_id": U128(real_email_id),
+                "sender": email.sender,
+                "reporter": receiver,
+            }),
+        );
+    }
+
+    pub(crate) fn required_spam_bond(&self, sender: &AccountId, receiver: &AccountId) -> Balance {
+        if self.is_donation_contract(sender) || self.is_contact(receiver, sender) {
+            return 0;
+        }
+        self.spam_bond.0
+    }
+
+    pub(crate) fn internal_burn(&self, amount: Balance) {
+        if let Some(burn_account) = self.burn_account.clone() {
+            Promise::new(burn_account).transfer(amount);
+        }
+    }
+
+    pub(crate) fn assert_cooldown_elapsed(&self, account_id: &AccountId) {
+        require(
+            self.cooldown_elapsed(account_id),
+            ContractError::SendCooldownActive,
+        );
+    }
+
+    pub(crate) fn cooldown_elapsed(&self, account_id: &AccountId) -> bool {
+        match self.accounts.get(account_id) {
+            Some(vaccount) => {
+                let now = env::block_timestamp();
+                let cooldown = current_cooldown(&vaccount, now);
+                cooldown == 0 || now >= vaccount.last_sent_at + cooldown
+            }
+            None => true,
+        }
+    }
+
+    pub(crate) fn is_spam_suspended(&self, account_id: &AccountId) -> bool {
+        if let Some(vaccount) = self.accounts.get(account_id) {
+            return vaccount.spam_reports >= SPAM_SUSPEND_MIN_REPORTS
+                && self.get_spam_score(account_id.clone()) >= SPAM_SUSPEND_SCORE;
+        }
+        false
+    }
+}
+
+pub(crate) fn current_cooldown(vaccount: &VAccount, now: u64) -> u64 {
+    let half_lives = now.saturating_sub(vaccount.min_send_interval_set_at) / COOLDOWN_HALF_LIFE;
+    if half_lives >= u64::BITS as u64 {
+        return 0;
+    }
+    vaccount.min_send_interval >> half_lives
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::testing_env;
+
+    fn setup_reports(count: usize, threshold: u32) -> (Contract, Vec<U128>) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_ids = (0..count)
+            .map(|_| U128(send(&mut contract, &alice(), &bob(), "spam")))
+            .collect();
+        set_context(&bob(), 1);
+        contract.set_spam_block_threshold(threshold);
+        (contract, email_ids)
+    }
+
+    #[test]
+    fn batch_crossing_threshold_blocks_sender() {
+        let (mut contract, email_ids) = setup_reports(2, 2);
+        set_context(&bob(), 1);
+        assert_eq!(contract.report_spam_many(email_ids), vec![alice()]);
+        assert!(contract.is_blocked_by(&bob(), &alice()));
+    }
+
+    #[test]
+    fn batch_skips_duplicates_and_earlier_reports() {
+        let (mut contract, email_ids) = setup_reports(2, 2);
+        set_context(&bob(), 1);
+        contract.report_spam(email_ids[0]);
+
+        set_context(&bob(), 1);
+        let blocked = contract.report_spam_many(vec![email_ids[0], email_ids[1], email_ids[1]]);
+
+        assert!(blocked.is_empty());
+        assert!(contract.is_reported(email_ids[1]));
+        assert_eq!(contract.accounts.get(&alice()).unwrap().spam_reports, 2);
+    }
+
+    #[test]
+    fn batch_returns_only_newly_blocked() {
+        let (mut contract, email_ids) = setup_reports(2, 1);
+        set_context(&bob(), 1);
+        assert_eq!(contract.report_spam_many(vec![email_ids[0]]), vec![alice()]);
+        set_context(&bob(), 1);
+        assert!(contract.report_spam_many(vec![email_ids[1]]).is_empty());
+    }
+
+    #[test]
+    fn block_and_report_trashes_restorably() {
+        let (mut contract, email_ids) = setup_reports(1, 0);
+        set_context(&bob(), 1);
+        contract.block_and_report(email_ids[0], true);
+
+        assert!(contract.is_blocked_by(&bob(), &alice()));
+        assert!(contract.is_trashed_by(&bob(), email_ids[0].0));
+        set_context(&bob(), 1);
+        contract.restore_mail(email_ids[0]);
+        assert!(contract.is_receiver_of(&bob(), email_ids[0].0));
+    }
+
+    /// Reports alice once and moves past the resulting cooldown.
+    fn setup_cooled_down() -> Contract {
+        let (mut contract, email_ids) = setup_reports(1, 0);
+        register(&mut contract, &carol());
+        set_context(&bob(), 1);
+        contract.report_spam(email_ids[0]);
+        let now = env::block_timestamp() + BASE_SEND_COOLDOWN;
+        testing_env!(context(&alice(), 2).block_timestamp(now).build());
+        contract
+    }
+
+    #[test]
+    fn multi_send_checks_cooldown_once() {
+        let mut contract = setup_cooled_down();
+        let email_ids = contract.send_mail_multi(
+            vec![bob(), carol()],
+            "Hello".to_string(),
+            "content".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(email_ids.len(), 2);
+    }
+
+    #[test]
+    fn forward_thread_checks_cooldown_once() {
+        let mut contract = setup_cooled_down();
+        let now = env::block_timestamp();
+        testing_env!(context(&bob(), 1).block_timestamp(now).build());
+        contract.reply_mail(U128(0), "reply".to_string(), None, false);
+
+        testing_env!(context(&alice(), 2).block_timestamp(now).build());
+        assert_eq!(contract.forward_thread(U128(0), carol()).len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn refund_bond_requires_one_yocto() {
+        let (mut contract, email_ids) = setup_reports(1, 0);
+        set_context(&alice(), 0);
+        contract.refund_bond(email_ids[0]);
+    }
+
+    #[test]
+    fn spam_score_tracks_report_density() {
+        let (mut contract, email_ids) = setup_reports(4, 0);
+        assert_eq!(contract.get_spam_score(alice()), 0);
+        for email_id in &email_ids[..2] {
+            set_context(&bob(), 1);
+            contract.report_spam(*email_id);
+        }
+        assert_eq!(contract.get_spam_score(alice()), 5_000);
+        assert!(!contract.is_spam_suspended(&alice()));
+
+        set_context(&bob(), 1);
+        contract.report_spam(email_ids[2]);
+        assert_eq!(contract.get_spam_score(alice()), 7_500);
+        assert!(contract.is_spam_suspended(&alice()));
+        assert_eq!(contract.get_spam_score(carol()), 0);
+    }
+
+    #[test]
+    fn repeat_reports_back_off_and_decay() {
+        let (mut contract, email_ids) = setup_reports(3, 0);
+        set_context(&bob(), 1);
+        contract.report_spam(email_ids[0]);
+        assert_eq!(contract.get_current_cooldown(alice()), BASE_SEND_COOLDOWN);
+        set_context(&bob(), 1);
+        contract.report_spam(email_ids[1]);
+        assert_eq!(
+            contract.get_current_cooldown(alice()),
+            COOLDOWN_BACKOFF_FACTOR * BASE_SEND_COOLDOWN
+        );
+
+        testing_env!(context(&alice(), 0)
+            .block_timestamp(COOLDOWN_HALF_LIFE)
+            .build());
+        assert_eq!(contract.get_current_cooldown(alice()), BASE_SEND_COOLDOWN);
+    }
+
+    #[test]
+    #[should_panic(expected = "E006")]
+    fn send_inside_the_cooldown_is_rejected() {
+        let (mut contract, email_ids) = setup_reports(1, 0);
+        set_context(&bob(), 1);
+        contract.report_spam(email_ids[0]);
+        testing_env!(context(&alice(), 1)
+            .block_timestamp(BASE_SEND_COOLDOWN - 1)
+            .build());
+        contract.send_mail(bob(), "Hello".to_string(), "again".to_string(), None, None);
+    }
+
+    #[test]
+    fn confirmed_spam_burns_the_bond() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_spam_bond(U128(1_000));
+        set_context(&owner(), 1);
+        contract.set_burn_account(Some(carol()));
+        set_context(&alice(), 1_000 + 1);
+        let email_id =
+            contract.send_mail(bob(), "Hello".to_string(), "spam".to_string(), None, None);
+        assert_eq!(contract.get_email_bond(email_id), Some(U128(1_000)));
+
+        set_context(&bob(), 1);
+        contract.report_spam(email_id);
+        assert_eq!(contract.get_email_bond(email_id), None);
+        assert_eq!(transfers(), vec![(carol(), 1_000)]);
+    }
+
+    #[test]
+    fn burned_bond_stays_put_without_a_burn_account() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_spam_bond(U128(1_000));
+        set_context(&owner(), 1);
+        contract.add_donation_contract_account(carol());
+        assert_eq!(contract.get_burn_account(), None);
+        set_context(&alice(), 1_000 + 1);
+        let email_id =
+            contract.send_mail(bob(), "Hello".to_string(), "spam".to_string(), None, None);
+
+        set_context(&bob(), 1);
+        contract.report_spam(email_id);
+        assert_eq!(contract.get_email_bond(email_id), None);
+        assert!(transfers().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller is not owner")]
+    fn only_the_owner_sets_the_burn_account() {
+        let mut contract = setup();
+        set_context(&alice(), 1);
+        contract.set_burn_account(Some(alice()));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::*;
+
+pub const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+pub const MAX_STATS_DAYS: u16 = 90;
+pub const MAX_STATS_OUTBOX: u64 = 1000;
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_last_seen(&self, account: AccountId) -> Option<u64> {
+        self.accounts
+            .get(&account)
+            .map(|vaccount| vaccount.last_active)
+    }
+
+    pub fn get_send_stats_by_day(&self, account: AccountId, days: u16) -> Vec<(u64, u64)> {
+        assert!(days <= MAX_STATS_DAYS, "Too many days");
+        if days == 0 {
+            return Vec::new();
+        }
+
+        let today = env::block_timestamp() / NANOS_PER_DAY;
+        let first_day = today.saturating_sub(days as u64 - 1);
+        let mut buckets: Vec<(u64, u64)> = (first_day..=today).map(|day| (day, 0)).collect();
+
+        if let Some(sender_vec) = self.senders.get(&account) {
+            assert!(sender_vec.len() <= MAX_STATS_OUTBOX, "Outbox too large");
+            for index in sender_vec.iter() {
+                if let Some(mail) = self.emails.get(&index) {
+                    let day = mail.timestamp / NANOS_PER_DAY;
+                    if day >= first_day && day <= today {
+                        buckets[(day - first_day) as usize].1 += 1;
+                    }
+                }
+            }
+        }
+        buckets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_STATS_DAYS, NANOS_PER_DAY};
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::testing_env;
+
+    fn send_on_day(contract: &mut Contract, day: u64, content: &str) {
+        testing_env!(context(&alice(), 1)
+            .block_timestamp(day * NANOS_PER_DAY)
+            .build());
+        contract.send_mail(bob(), "Hello".to_string(), content.to_string(), None, None);
+    }
+
+    #[test]
+    fn sends_are_bucketed_by_day() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        send_on_day(&mut contract, 1, "a");
+        send_on_day(&mut contract, 3, "b");
+        send_on_day(&mut contract, 3, "c");
+
+        testing_env!(context(&alice(), 0)
+            .block_timestamp(3 * NANOS_PER_DAY + 1)
+            .build());
+        assert_eq!(
+            contract.get_send_stats_by_day(alice(), 3),
+            vec![(1, 1), (2, 0), (3, 2)]
+        );
+        assert_eq!(contract.get_send_stats_by_day(alice(), 1), vec![(3, 2)]);
+        assert!(contract.get_send_stats_by_day(alice(), 0).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many days")]
+    fn stats_window_is_bounded() {
+        let contract = setup();
+        contract.get_send_stats_by_day(alice(), MAX_STATS_DAYS + 1);
+    }
+
+    #[test]
+    fn last_seen_follows_the_latest_activity() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        assert_eq!(contract.get_last_seen(carol()), None);
+
+        send_on_day(&mut contract, 2, "a");
+        assert_eq!(contract.get_last_seen(alice()), Some(2 * NANOS_PER_DAY));
+        testing_env!(context(&bob(), 1)
+            .block_timestamp(5 * NANOS_PER_DAY)
+            .build());
+        contract.mark_as_read(U128(0));
+        assert_eq!(contract.get_last_seen(bob()), Some(5 * NANOS_PER_DAY));
+        assert_eq!(contract.get_last_seen(alice()), Some(2 * NANOS_PER_DAY));
+    }
+}
@@ -1,6 +1,10 @@
+use crate::label::label_key;
+use crate::ledger::{Charge, ChargeKind, StorageCharges};
+use crate::mail_index::OrderSide;
+use crate::sort::SortMode;
 use crate::*;
 use near_contract_standards::storage_management::StorageManagement;
-use near_sdk::{near_bindgen, Balance, Promise};
+use near_sdk::{near_bindgen, Balance, Promise, StorageUsage};
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
@@ -10,10 +14,76 @@ use near_sdk::serde::{Deserialize, Serialize};
 pub struct VAccount {
     pub deposit: Balance,
     pub used: Balance,
+    pub sent_count: u64,
+    pub spam_reports: u64,
+    pub min_send_interval: u64,
+    pub min_send_interval_set_at: u64,
+    pub last_sent_at: u64,
+    pub last_active: u64,
+    pub retention_days: Option<u32>,
+    pub inbox_price: Balance,
+    pub reject_duplicates: bool,
+    pub auto_archive_on_read: bool,
+    pub reserved_capacity: u64,
+    pub inbox_sort: SortMode,
+    pub allowlist_only: bool,
+    pub reject_paid_mail: bool,
+    pub webhook_tag: Option<String>,
+    pub bonus: Balance,
+    pub spam_block_threshold: u32,
+    pub fees_earned: Balance,
+    pub frozen: bool,
+    pub auto_purge_read_after_ns: Option<u64>,
+    pub charges: StorageCharges,
 }
 
-pub const STORAGE_PER_MAIL: Balance = 10;
+impl VAccount {
+    pub fn new(deposit: Balance, used: Balance) -> Self {
+        Self {
+            deposit,
+            used,
+            sent_count: 0,
+            spam_reports: 0,
+            min_send_interval: 0,
+            min_send_interval_set_at: 0,
+            last_sent_at: 0,
+            last_active: env::block_timestamp(),
+            retention_days: None,
+            inbox_price: 0,
+            reject_duplicates: false,
+            auto_archive_on_read: false,
+            reserved_capacity: 0,
+            inbox_sort: SortMode::NewestFirst,
+            allowlist_only: false,
+            reject_paid_mail: false,
+            webhook_tag: None,
+            bonus: 0,
+            spam_block_threshold: 0,
+            fees_earned: 0,
+            frozen: false,
+            auto_purge_read_after_ns: None,
+            charges: StorageCharges {
+                account: Charge {
+                    count: 1,
+                    amount: U128(used),
+                },
+                ..Default::default()
+            },
+        }
+    }
+
+    pub(crate) fn refundable(&self) -> Balance {
+        self.deposit.saturating_sub(self.bonus)
+    }
+}
+
+/// Upper bound on the bytes a send stores besides its title and content: the
+/// record's other fields and its entries in every per-mail index, including the
+/// first mail between two accounts with the longest ids. A send needs this much
+/// free up front and is then charged what it actually stored.
+pub const STORAGE_PER_MAIL: Balance = 8_000;
 pub const STORAGE_PER_ACCOUNT: Balance = 20;
+pub const MAX_CLEANUP_OPS: u64 = 200;
 
 #[near_bindgen]
 impl StorageManagement for Contract {
@@ -23,69 +93,71 @@ impl StorageManagement for Contract {
         account_id: Option<AccountId>,
         registration_only: Option<bool>,
     ) -> StorageBalance {
-        let account_id = account_id
-            .map(|a| a.into())
-            .unwrap_or_else(|| env::predecessor_account_id());
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
         let registration_only = registration_only.unwrap_or(false);
+        assert!(
+            !self.pending_cleanups.contains(&account_id),
+            "Account cleanup pending"
+        );
         let amount = env::attached_deposit();
         let is_registered = self.accounts.contains_key(&account_id);
 
-        if is_registered {
+        if is_registered && registration_only {
+            if amount > 0 {
+                Promise::new(env::predecessor_account_id()).transfer(amount);
+            }
+            self.storage_balance_of(account_id).unwrap()
+        } else if is_registered {
             let mut current_account = self.accounts.get(&account_id).unwrap();
             let new_amount = amount + current_account.deposit;
             current_account.deposit = new_amount;
             self.accounts.insert(&account_id, &current_account);
-            return StorageBalance {
+            StorageBalance {
                 total: U128(new_amount),
                 available: U128(new_amount - current_account.used),
-            };
+            }
         } else {
-            let used = min_deposit();
+            let used = self.storage_balance_bounds().min.0;
             assert!(
-                amount > used,
-                "Amount deposit must be greater than min deposit"
+                amount >= used,
+                "Amount deposit must be at least the min deposit"
             );
-            if registration_only {
+            let bonus = Balance::from(self.bonus_storage_bytes) * self.byte_cost();
+            let deposit = if registration_only {
                 let refund = amount - used;
-                Promise::new(env::predecessor_account_id()).transfer(refund);
-                let new_account = VAccount {
-                    deposit: used,
-                    used,
-                };
-                self.accounts.insert(&account_id, &new_account);
-                return StorageBalance {
-                    total: U128(used),
-                    available: U128(0),
-                };
+                if refund > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(refund);
+                }
+                used
             } else {
-                let new_account = VAccount {
-                    deposit: amount,
-                    used,
-                };
-                self.accounts.insert(&account_id, &new_account);
-                return StorageBalance {
-                    total: U128(amount),
-                    available: U128(amount - used),
-                };
+                amount
+            };
+            let mut new_account = VAccount::new(deposit + bonus, used);
+            new_account.bonus = bonus;
+            self.accounts.insert(&account_id, &new_account);
+            self.registered_count += 1;
+            self.account_ids.insert(&account_id);
+            StorageBalance {
+                total: U128(deposit + bonus),
+                available: U128(deposit + bonus - used),
             }
         }
     }
 
     #[payable]
     fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
         let account_id = env::predecessor_account_id();
-        let real_amount = amount.unwrap().0;
-        assert!(
-            self.accounts.contains_key(&account_id),
-            "account not registered"
-        );
-        let mut vaccount = self.accounts.get(&account_id).unwrap();
-        assert!(
-            (vaccount.deposit - vaccount.used) >= (real_amount),
-            "Withraw too much"
-        );
+        let mut vaccount = self
+            .accounts
+            .get(&account_id)
+            .unwrap_or_else(|| ContractError::AccountNotRegistered.panic());
+        let withdrawable = (vaccount.deposit - vaccount.used).saturating_sub(vaccount.bonus);
+        let real_amount = amount.map(|amount| amount.0).unwrap_or(withdrawable);
+        require(withdrawable >= real_amount, ContractError::NotDepositEnough);
         Promise::new(env::predecessor_account_id()).transfer(real_amount);
-        vaccount.deposit = vaccount.deposit - real_amount;
+        vaccount.deposit -= real_amount;
+        vaccount.last_active = env::block_timestamp();
         self.accounts.insert(&account_id, &vaccount);
         StorageBalance {
             total: U128(vaccount.deposit),
@@ -93,21 +165,27 @@ impl StorageManagement for Contract {
         }
     }
 
-    #[allow(unused_variables)]
+    /// Without `force` the account must hold nothing beyond its registration;
+    /// with it, the account is torn down as `close_account(true)` does.
     #[payable]
     fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
         let account_id = env::predecessor_account_id();
-        if let Some(vaccount) = self.accounts.get(&account_id) {
-            Promise::new(env::predecessor_account_id()).transfer(vaccount.deposit);
-            self.accounts.remove(&account_id);
-            return true;
-        } else {
-            return false;
-        }
+        let vaccount = match self.accounts.get(&account_id) {
+            Some(vaccount) => vaccount,
+            None => return false,
+        };
+        let force = force.unwrap_or(false);
+        assert!(
+            force || vaccount.used <= vaccount.charges.account.amount.0,
+            "Account still holds stored items"
+        );
+        self.internal_close_account(&account_id, vaccount, force);
+        true
     }
 
     fn storage_balance_bounds(&self) -> StorageBalanceBounds {
-        let min_deposit = min_deposit();
+        let min_deposit = self.min_deposit();
         StorageBalanceBounds {
             min: U128(min_deposit),
             max: None,
@@ -121,10 +199,661 @@ impl StorageManagement for Contract {
                 available: U128(vaccount.deposit - vaccount.used),
             });
         }
-        return None;
+        None
+    }
+}
+
+impl Contract {
+    pub(crate) fn byte_cost(&self) -> Balance {
+        self.storage_cost_override
+            .map(|cost| cost.0)
+            .unwrap_or_else(env::storage_byte_cost)
+    }
+
+    pub(crate) fn min_deposit(&self) -> Balance {
+        self.byte_cost() * STORAGE_PER_ACCOUNT
+    }
+
+    pub(crate) fn mail_storage_cost(&self, content_len: u64) -> Balance {
+        self.byte_cost() * (STORAGE_PER_MAIL + Balance::from(content_len))
+    }
+
+    /// What the bytes written since `storage_usage` was `before` cost.
+    pub(crate) fn stored_cost_since(&self, before: StorageUsage) -> Balance {
+        self.byte_cost() * Balance::from(env::storage_usage().saturating_sub(before))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn close_account(&mut self, force: bool) -> U128 {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let vaccount = self
+            .accounts
+            .get(&account_id)
+            .expect("Account not registered");
+        U128(self.internal_close_account(&account_id, vaccount, force))
+    }
+
+    pub fn continue_cleanup(&mut self, account: AccountId) -> bool {
+        assert!(
+            self.pending_cleanups.contains(&account),
+            "No cleanup pending"
+        );
+        self.internal_cleanup(&account)
+    }
+
+    pub fn get_registered_count(&self) -> u64 {
+        self.registered_count
+    }
+
+    pub fn is_cleanup_pending(&self, account: AccountId) -> bool {
+        self.pending_cleanups.contains(&account)
+    }
+}
+
+impl Contract {
+    /// Removes the account, refunds its deposit and starts the teardown of
+    /// everything it stored. Returns the refund.
+    pub(crate) fn internal_close_account(
+        &mut self,
+        account_id: &AccountId,
+        vaccount: VAccount,
+        force: bool,
+    ) -> Balance {
+        let sent_count = self
+            .senders
+            .get(account_id)
+            .map(|sender_vec| sender_vec.len())
+            .unwrap_or(0);
+        assert!(force || sent_count == 0, "Account still has sent mail");
+
+        self.notification_prefs.remove(account_id);
+        self.recent_allowed.remove(account_id);
+        if self.pending_broadcasts.remove(account_id).is_some() {
+            self.internal_forget_entry(account_id, ChargeKind::Broadcast, account_id.to_string());
+        }
+        self.send_request_ids.remove(account_id);
+        self.recent_mail_hashes.remove(account_id);
+        self.accounts.remove(account_id);
+        self.registered_count -= 1;
+        self.account_ids.remove(account_id);
+        self.pending_cleanups.insert(account_id);
+        self.internal_cleanup(account_id);
+
+        Promise::new(account_id.clone()).transfer(vaccount.refundable());
+        vaccount.refundable()
+    }
+
+    pub(crate) fn internal_cleanup(&mut self, account_id: &AccountId) -> bool {
+        let mut budget = MAX_CLEANUP_OPS;
+
+        let (sent, done) = drain_set(&mut self.senders, account_id, &mut budget);
+        for email_id in sent {
+            // Spam bonds stay posted and are returned by `refund_bond` once
+            // the refund window has passed, as for deleted mail.
+            if let Some(email) = self.emails.get(&email_id) {
+                self.internal_purge(email_id, &email);
+            }
+        }
+        if !done {
+            return false;
+        }
+        let (received, done) = drain_set(&mut self.receivers, account_id, &mut budget);
+        self.internal_purge_orphans(received);
+        if !done {
+            return false;
+        }
+        let (archived, done) = drain_set(&mut self.archived, account_id, &mut budget);
+        self.internal_purge_orphans(archived);
+        if !done {
+            return false;
+        }
+        let (trashed, done) = drain_set(&mut self.trash, account_id, &mut budget);
+        self.internal_purge_orphans(trashed);
+        if !done {
+            return false;
+        }
+        let (contacts, done) = drain_set(&mut self.contacts, account_id, &mut budget);
+        for contact in contacts.iter() {
+            self.internal_forget_entry(account_id, ChargeKind::Contact, contact.to_string());
+        }
+        if !done {
+            return false;
+        }
+        let (muted, done) = drain_set(&mut self.muted_threads, account_id, &mut budget);
+        for root_id in muted.iter() {
+            self.internal_forget_entry(account_id, ChargeKind::MutedThread, root_id.to_string());
+        }
+        if !done {
+            return false;
+        }
+        let (blocked, done) = drain_set(&mut self.blocklists, account_id, &mut budget);
+        for sender in blocked.iter() {
+            self.internal_remove_blocked_by(sender, account_id);
+            self.internal_forget_entry(account_id, ChargeKind::BlockEntry, sender.to_string());
+        }
+        if !done {
+            return false;
+        }
+        let (allowed, done) = drain_set(&mut self.allowlists, account_id, &mut budget);
+        for sender in allowed.iter() {
+            self.internal_forget_entry(account_id, ChargeKind::AllowEntry, sender.to_string());
+        }
+        if !done {
+            return false;
+        }
+        let (overridden, done) = drain_map(&mut self.fee_overrides, account_id, &mut budget);
+        for sender in overridden.iter() {
+            self.internal_forget_entry(account_id, ChargeKind::FeeOverride, sender.to_string());
+        }
+        if !done {
+            return false;
+        }
+        if !drain_set(&mut self.account_threads, account_id, &mut budget).1 {
+            return false;
+        }
+        if !drain_set(&mut self.conversation_partners, account_id, &mut budget).1 {
+            return false;
+        }
+        for label in self.get_labels(account_id.clone()) {
+            let key = label_key(account_id, &label);
+            let (labeled, done) = drain_set(&mut self.label_mail, &key, &mut budget);
+            for email_id in labeled.iter() {
+                self.labeled.remove(email_id);
+            }
+            self.internal_purge_orphans(labeled);
+            if !done {
+                return false;
+            }
+        }
+        let (labels, done) = drain_set(&mut self.label_names, account_id, &mut budget);
+        for label in labels {
+            self.internal_forget_entry(account_id, ChargeKind::Label, label);
+        }
+        if !done {
+            return false;
+        }
+        if !self.internal_drop_scheduled(account_id, &mut budget) {
+            return false;
+        }
+        let (requests, done) = drain_set(&mut self.incoming_requests, account_id, &mut budget);
+        for request_id in requests {
+            if let Some(request) = self.internal_remove_request(request_id) {
+                self.internal_refund_request(&request);
+            }
+        }
+        if !done {
+            return false;
+        }
+        let (links, done) = drain_set(&mut self.account_links, account_id, &mut budget);
+        for (kind, item) in links {
+            self.internal_drop_link(account_id, kind, item);
+        }
+        if !done {
+            return false;
+        }
+        let (subscribers, done) = drain_set(&mut self.subscribers, account_id, &mut budget);
+        for subscriber in subscribers.iter() {
+            let item = account_id.to_string();
+            match self.accounts.get(subscriber) {
+                Some(mut vaccount) => {
+                    self.internal_release_entry(
+                        &mut vaccount,
+                        subscriber,
+                        ChargeKind::Subscription,
+                        item,
+                    );
+                    self.accounts.insert(subscriber, &vaccount);
+                }
+                None => {
+                    self.internal_forget_entry(subscriber, ChargeKind::Subscription, item);
+                }
+            }
+        }
+        if !done {
+            return false;
+        }
+        if !self.internal_compact_order(OrderSide::Sent, account_id, &mut budget) {
+            return false;
+        }
+        if !self.internal_compact_order(OrderSide::Received, account_id, &mut budget) {
+            return false;
+        }
+
+        self.pending_cleanups.remove(account_id);
+        true
+    }
+
+    fn internal_purge_orphans(&mut self, email_ids: Vec<EmailID>) {
+        for email_id in email_ids {
+            if let Some(email) = self.emails.get(&email_id) {
+                self.internal_purge_if_orphaned(email_id, &email);
+            }
+        }
+    }
+
+    /// Detaches a closed account from state keyed by someone else, so nothing
+    /// there stays charged to (or resolves to) the closed account.
+    fn internal_drop_link(&mut self, account_id: &AccountId, kind: ChargeKind, item: String) {
+        self.entry_charges
+            .remove(&(kind, account_id.clone(), item.clone()));
+        match kind {
+            ChargeKind::Mail => {
+                // Mail the account paid for but someone else still holds is carried
+                // by the contract from now on.
+                if let Ok(email_id) = item.parse::<EmailID>() {
+                    if let Some(mut email) = self.emails.get(&email_id) {
+                        email.storage_payer = Some(env::current_account_id());
+                        self.emails.insert(&email_id, &email);
+                    }
+                }
+            }
+            ChargeKind::Reaction => {
+                if let Ok(email_id) = item.parse::<EmailID>() {
+                    if let Some(mut reactions) = self.reactions.get(&email_id) {
+                        reactions.remove(account_id);
+                        self.reactions.insert(&email_id, &reactions);
+                    }
+                }
+            }
+            ChargeKind::Subscription => {
+                if let Ok(publisher) = item.parse::<AccountId>() {
+                    if let Some(mut subscriber_set) = self.subscribers.get(&publisher) {
+                        subscriber_set.remove(account_id);
+                        self.subscribers.insert(&publisher, &subscriber_set);
+                    }
+                }
+            }
+            ChargeKind::Alias => {
+                self.alias_to_account.remove(&item);
+            }
+            ChargeKind::Tombstone => {
+                self.order_tombstones.remove(&item);
+            }
+            ChargeKind::DeletedMail => {
+                if let Ok(email_id) = item.parse::<EmailID>() {
+                    self.deleted_mail.remove(&email_id);
+                }
+            }
+            ChargeKind::Request => {
+                if let Ok(request_id) = item.parse::<RequestID>() {
+                    if let Some(request) = self.send_requests.remove(&request_id) {
+                        if let Some(mut incoming) = self.incoming_requests.get(&request.receiver) {
+                            incoming.remove(&request_id);
+                            self.incoming_requests.insert(&request.receiver, &incoming);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn drain_set<K: BorshSerialize, T: BorshSerialize + BorshDeserialize>(
+    map: &mut LookupMap<K, UnorderedSet<T>>,
+    account_id: &K,
+    budget: &mut u64,
+) -> (Vec<T>, bool) {
+    let mut set = match map.get(account_id) {
+        Some(set) => set,
+        None => return (Vec::new(), true),
+    };
+
+    let removed: Vec<T> = set.iter().take(*budget as usize).collect();
+    for item in removed.iter() {
+        set.remove(item);
+    }
+    *budget -= removed.len() as u64;
+
+    if set.is_empty() {
+        map.remove(account_id);
+        (removed, true)
+    } else {
+        map.insert(account_id, &set);
+        (removed, false)
     }
 }
 
-pub(crate) fn min_deposit() -> Balance {
-    return env::storage_byte_cost() * STORAGE_PER_ACCOUNT;
+fn drain_map<V: BorshSerialize + BorshDeserialize>(
+    map: &mut LookupMap<AccountId, UnorderedMap<AccountId, V>>,
+    account_id: &AccountId,
+    budget: &mut u64,
+) -> (Vec<AccountId>, bool) {
+    let mut inner = match map.get(account_id) {
+        Some(inner) => inner,
+        None => return (Vec::new(), true),
+    };
+
+    let keys: Vec<AccountId> = inner.keys().take(*budget as usize).collect();
+    for key in keys.iter() {
+        inner.remove(key);
+    }
+    *budget -= keys.len() as u64;
+
+    if inner.is_empty() {
+        map.remove(account_id);
+        (keys, true)
+    } else {
+        map.insert(account_id, &inner);
+        (keys, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MAX_CLEANUP_OPS;
+    use crate::test_utils::*;
+    use crate::*;
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::testing_env;
+
+    #[test]
+    #[should_panic(expected = "Account still has sent mail")]
+    fn close_account_rejects_live_sent_mail() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        send(&mut contract, &alice(), &bob(), "hi");
+
+        set_context(&alice(), 1);
+        contract.close_account(false);
+    }
+
+    #[test]
+    fn close_account_refunds_deposit_after_teardown() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        send(&mut contract, &bob(), &alice(), "hi");
+        set_context(&alice(), 1);
+        contract.add_contact(bob());
+        let balance = contract.storage_balance_of(alice()).unwrap();
+        assert!(balance.available.0 < balance.total.0);
+
+        set_context(&alice(), 1);
+        let refund = contract.close_account(false);
+
+        assert_eq!(refund, U128(DEPOSIT));
+        assert!(contract.storage_balance_of(alice()).is_none());
+        assert!(contract.get_contacts(alice()).is_empty());
+        assert_eq!(contract.get_mail_receive_num(alice()), 0);
+        assert_eq!(contract.get_registered_count(), 1);
+        assert!(!contract.is_cleanup_pending(alice()));
+    }
+
+    #[test]
+    fn close_account_force_removes_sent_mail() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let email_id = send(&mut contract, &alice(), &bob(), "hi");
+
+        set_context(&alice(), 1);
+        assert_eq!(contract.close_account(true), U128(DEPOSIT));
+        assert!(contract.emails.get(&email_id).is_none());
+        assert_eq!(contract.get_mail_send_num(alice()), 0);
+    }
+
+    #[test]
+    fn close_account_force_purges_everywhere_and_leaves_bond_to_its_window() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&owner(), 1);
+        contract.set_spam_bond(U128(100));
+        set_context(&alice(), 101);
+        let email_id = contract
+            .send_mail(bob(), "Hello".to_string(), "hi".to_string(), None, None)
+            .0;
+        let bob_used = contract.accounts.get(&bob()).unwrap().used;
+        set_context(&bob(), 1);
+        contract.react(U128(email_id), "+1".to_string());
+
+        set_context(&alice(), 1);
+        contract.close_account(true);
+
+        assert_eq!(contract.get_mail_receive_num(bob()), 0);
+        assert!(!contract.holds_received(&bob(), email_id));
+        assert_eq!(contract.accounts.get(&bob()).unwrap().used, bob_used);
+        assert_eq!(transfers(), vec![(alice(), DEPOSIT)]);
+        assert_eq!(contract.get_email_bond(U128(email_id)), Some(U128(100)));
+
+        testing_env!(context(&alice(), 1)
+            .block_timestamp(SPAM_BOND_REFUND_WINDOW)
+            .build());
+        contract.refund_bond(U128(email_id));
+        assert_eq!(transfers(), vec![(alice(), 100)]);
+    }
+
+    #[test]
+    fn close_account_releases_alias_and_subscriptions() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        register(&mut contract, &carol());
+        set_context(&alice(), 1);
+        contract.register_alias("al".to_string());
+        set_context(&alice(), 1);
+        contract.subscribe(bob());
+        let carol_used = contract.accounts.get(&carol()).unwrap().used;
+        set_context(&carol(), 1);
+        contract.subscribe(alice());
+
+        set_context(&alice(), 1);
+        contract.close_account(false);
+
+        assert!(contract.get_alias_account("al".to_string()).is_none());
+        assert!(contract.get_subscribers(bob()).is_empty());
+        assert!(contract.get_subscribers(alice()).is_empty());
+        assert_eq!(contract.accounts.get(&carol()).unwrap().used, carol_used);
+        assert!(contract.account_links.get(&alice()).is_none());
+    }
+
+    #[test]
+    fn close_account_hands_receiver_paid_mail_to_contract() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&alice(), contract.reserved_mail_cost());
+        contract.reserve_inbox(1);
+        let mut sender = contract.accounts.get(&bob()).unwrap();
+        sender.deposit = sender.used;
+        contract.accounts.insert(&bob(), &sender);
+        let email_id = send(&mut contract, &bob(), &alice(), "hi");
+        assert_eq!(
+            contract.emails.get(&email_id).unwrap().storage_payer,
+            Some(alice())
+        );
+
+        set_context(&alice(), 1);
+        contract.close_account(false);
+
+        let email = contract.emails.get(&email_id).unwrap();
+        assert_eq!(email.storage_payer, Some(contract_account()));
+        assert_eq!(contract.get_mail_send_num(bob()), 1);
+    }
+
+    #[test]
+    fn withdraw_defaults_to_everything_available() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        let available = contract.storage_balance_of(alice()).unwrap().available;
+
+        set_context(&alice(), 1);
+        let balance = contract.storage_withdraw(None);
+        assert_eq!(balance.available, U128(0));
+        assert_eq!(transfers(), vec![(alice(), available.0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E002")]
+    fn withdraw_rejects_more_than_available() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        set_context(&alice(), 1);
+        contract.storage_withdraw(Some(U128(DEPOSIT)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn withdraw_requires_one_yocto() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        set_context(&alice(), 0);
+        contract.storage_withdraw(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn unregister_requires_one_yocto() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        set_context(&alice(), 0);
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account still holds stored items")]
+    fn unregister_refuses_while_items_are_stored() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&alice(), 1);
+        contract.add_contact(bob());
+
+        set_context(&alice(), 1);
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn unregister_refunds_deposit_and_removes_account() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+
+        set_context(&alice(), 1);
+        assert!(contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(alice()).is_none());
+        assert_eq!(contract.get_registered_count(), 0);
+        assert_eq!(transfers(), vec![(alice(), DEPOSIT)]);
+
+        set_context(&alice(), 1);
+        assert!(!contract.storage_unregister(None));
+    }
+
+    #[test]
+    fn unregister_force_cleans_outstanding_mail_and_counts_down() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let sent_id = send(&mut contract, &alice(), &bob(), "hi");
+        let received_id = send(&mut contract, &bob(), &alice(), "hey");
+        assert_eq!(contract.get_registered_count(), 2);
+
+        set_context(&alice(), 1);
+        assert!(contract.storage_unregister(Some(true)));
+
+        assert_eq!(contract.get_registered_count(), 1);
+        assert!(contract.storage_balance_of(alice()).is_none());
+        assert!(contract.emails.get(&sent_id).is_none());
+        assert!(contract.senders.get(&alice()).is_none());
+        assert!(contract.receivers.get(&alice()).is_none());
+        assert!(!contract.holds_received(&bob(), sent_id));
+        assert!(contract.is_sender_of(&bob(), received_id));
+        assert!(!contract.is_cleanup_pending(alice()));
+        assert_eq!(transfers(), vec![(alice(), DEPOSIT)]);
+
+        // Registering again starts a fresh account and counts it once.
+        register(&mut contract, &alice());
+        register(&mut contract, &alice());
+        assert_eq!(contract.get_registered_count(), 2);
+    }
+
+    #[test]
+    fn cleanup_resumes_across_calls() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        set_context(&bob(), 100 * DEPOSIT);
+        contract.storage_deposit(None, None);
+        for _ in 0..=MAX_CLEANUP_OPS {
+            send(&mut contract, &bob(), &alice(), "hi");
+        }
+
+        set_context(&alice(), 1);
+        contract.close_account(false);
+        assert!(contract.is_cleanup_pending(alice()));
+
+        set_context(&bob(), 0);
+        while !contract.continue_cleanup(alice()) {}
+        assert!(!contract.is_cleanup_pending(alice()));
+        assert_eq!(contract.get_mail_receive_num(alice()), 0);
+        assert_eq!(contract.get_mail_send_num(bob()), MAX_CLEANUP_OPS + 1);
+    }
+
+    #[test]
+    fn registration_only_keeps_exactly_the_minimum() {
+        let mut contract = setup();
+        let min = contract.storage_balance_bounds().min.0;
+        set_context(&alice(), min);
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!((balance.total, balance.available), (U128(min), U128(0)));
+        assert!(transfers().is_empty());
+
+        set_context(&bob(), min + 7);
+        contract.storage_deposit(None, Some(true));
+        assert_eq!(transfers(), vec![(bob(), 7)]);
+        assert_eq!(contract.storage_balance_of(bob()).unwrap().total, U128(min));
+    }
+
+    #[test]
+    fn registration_only_refunds_a_registered_account_in_full() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        set_context(&alice(), 5);
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(balance.total, U128(DEPOSIT));
+        assert_eq!(transfers(), vec![(alice(), 5)]);
+    }
+
+    #[test]
+    fn minimum_follows_the_storage_cost_override() {
+        let mut contract = setup();
+        set_context(&owner(), 1);
+        contract.set_storage_cost_override(Some(U128(3)));
+        assert_eq!(
+            contract.storage_balance_bounds().min,
+            U128(3 * STORAGE_PER_ACCOUNT)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount deposit must be at least the min deposit")]
+    fn registration_below_the_minimum_is_rejected() {
+        let mut contract = setup();
+        let min = contract.storage_balance_bounds().min.0;
+        set_context(&alice(), min - 1);
+        contract.storage_deposit(None, Some(true));
+    }
+
+    #[test]
+    fn registration_bonus_is_usable_but_not_withdrawable() {
+        let mut contract = setup();
+        set_context(&owner(), 1);
+        contract.set_bonus_storage_bytes(500);
+        let bonus = 500 * contract.byte_cost();
+        register(&mut contract, &alice());
+        let balance = contract.storage_balance_of(alice()).unwrap();
+        assert_eq!(balance.total, U128(DEPOSIT + bonus));
+
+        set_context(&alice(), 1);
+        contract.storage_withdraw(None);
+        assert_eq!(transfers(), vec![(alice(), balance.available.0 - bonus)]);
+        assert_eq!(
+            contract.storage_balance_of(alice()).unwrap().available,
+            U128(bonus)
+        );
+    }
 }
@@ -0,0 +1,141 @@
+use crate::*;
+use near_contract_standards::storage_management::StorageManagement;
+use near_sdk::mock::VmAction;
+use near_sdk::serde_json::Value;
+use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+use near_sdk::{testing_env, ONE_NEAR};
+
+pub(crate) const DEPOSIT: Balance = ONE_NEAR;
+
+pub(crate) fn owner() -> AccountId {
+    accounts(0)
+}
+
+pub(crate) fn alice() -> AccountId {
+    accounts(1)
+}
+
+pub(crate) fn bob() -> AccountId {
+    accounts(2)
+}
+
+pub(crate) fn carol() -> AccountId {
+    accounts(3)
+}
+
+pub(crate) fn contract_account() -> AccountId {
+    accounts(5)
+}
+
+pub(crate) fn context(predecessor: &AccountId, deposit: Balance) -> VMContextBuilder {
+    let mut builder = VMContextBuilder::new();
+    builder
+        .current_account_id(contract_account())
+        .predecessor_account_id(predecessor.clone())
+        .attached_deposit(deposit);
+    builder
+}
+
+pub(crate) fn set_context(predecessor: &AccountId, deposit: Balance) {
+    testing_env!(context(predecessor, deposit).build());
+}
+
+pub(crate) fn setup() -> Contract {
+    set_context(&owner(), 0);
+    Contract::new(owner())
+}
+
+pub(crate) fn register(contract: &mut Contract, account_id: &AccountId) {
+    set_context(account_id, DEPOSIT);
+    contract.storage_deposit(None, None);
+}
+
+pub(crate) fn send(
+    contract: &mut Contract,
+    sender: &AccountId,
+    receiver: &AccountId,
+    content: &str,
+) -> EmailID {
+    set_context(sender, 1);
+    contract
+        .send_mail(
+            receiver.clone(),
+            "Hello".to_string(),
+            content.to_string(),
+            None,
+            None,
+        )
+        .0
+}
+
+pub(crate) fn transfers() -> Vec<(AccountId, Balance)> {
+    get_created_receipts()
+        .into_iter()
+        .flat_map(|receipt| {
+            let receiver_id = receipt.receiver_id;
+            receipt
+                .actions
+                .into_iter()
+                .filter_map(move |action| match action {
+                    VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                    _ => None,
+                })
+        })
+        .collect()
+}
+
+/// The data of every `event` logged in the current context.
+pub(crate) fn events(event: &str) -> Vec<Value> {
+    get_logs()
+        .iter()
+        .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+        .map(|payload| near_sdk::serde_json::from_str::<Value>(payload).unwrap())
+        .filter(|payload| payload["event"] == event)
+        .map(|payload| payload["data"][0].clone())
+        .collect()
+}
+
+/// Writes contract state in the baseline layout: alice and bob registered,
+/// and one mail from alice to bob per entry of `contents`. A `None` entry is a
+/// mail the baseline deleted, which left its id in both indexes.
+pub(crate) fn write_baseline_state(contents: &[Option<&str>]) {
+    use crate::migration::{OldContract, OldEmail, OldVAccount};
+
+    let byte_cost = env::storage_byte_cost();
+    let mut old = OldContract {
+        senders: LookupMap::new(StorageKeys::Sender),
+        receivers: LookupMap::new(StorageKeys::Receiver),
+        emails: UnorderedMap::new(StorageKeys::Email),
+        email_count: contents.len() as u128,
+        accounts: LookupMap::new(StorageKeys::Account),
+        donation_contract_account: None,
+    };
+    let mut sent = UnorderedSet::new(b"baseline-sent".to_vec());
+    let mut received = UnorderedSet::new(b"baseline-received".to_vec());
+    for (email_id, content) in contents.iter().enumerate() {
+        let email_id = email_id as EmailID;
+        sent.insert(&email_id);
+        received.insert(&email_id);
+        if let Some(content) = content {
+            let email = OldEmail {
+                title: "Hello".to_string(),
+                content: content.to_string(),
+                timestamp: 7,
+                fee: None,
+            };
+            old.emails.insert(&email_id, &email);
+        }
+    }
+    old.senders.insert(&alice(), &sent);
+    old.receivers.insert(&bob(), &received);
+    let account_cost = STORAGE_PER_ACCOUNT * byte_cost;
+    let mail_cost = BASELINE_STORAGE_PER_MAIL * byte_cost * contents.len() as Balance;
+    for (account_id, used) in [(alice(), account_cost + mail_cost), (bob(), account_cost)] {
+        let record = OldVAccount {
+            deposit: DEPOSIT,
+            used,
+        };
+        old.accounts.insert(&account_id, &record);
+    }
+    env::state_write(&old);
+}
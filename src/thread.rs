@@ -0,0 +1,108 @@
+use crate::*;
+use near_sdk::serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ThreadSummary {
+    pub root_id: U128,
+    pub last_email: EmailView,
+    pub message_count: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_threads(
+        &self,
+        account: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<ThreadSummary> {
+        let thread_set = match self.account_threads.get(&account) {
+            Some(thread_set) => thread_set,
+            None => return Vec::new(),
+        };
+        thread_set
+            .as_vector()
+            .iter()
+            .skip(from_index as usize)
+            .filter_map(|root_id| self.thread_summary(root_id))
+            .take(limit as usize)
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_index_thread(&mut self, email: &Email, email_id: EmailID) {
+        let root_id = match email.thread_root {
+            Some(root_id) => {
+                let mut replies = self.thread_replies.get(&root_id.0).unwrap_or_else(|| {
+                    Vector::new(StorageKeys::ThreadReplyList { root_id: root_id.0 })
+                });
+                replies.push(&email_id);
+                self.thread_replies.insert(&root_id.0, &replies);
+                root_id.0
+            }
+            None => email_id,
+        };
+        for account_id in [&email.sender, &email.receiver] {
+            let mut thread_set = self.account_threads.get(account_id).unwrap_or_else(|| {
+                UnorderedSet::new(StorageKeys::AccountThreadSet {
+                    account_id: account_id.clone(),
+                })
+            });
+            if thread_set.insert(&root_id) {
+                self.account_threads.insert(account_id, &thread_set);
+            }
+        }
+    }
+
+    fn thread_summary(&self, root_id: EmailID) -> Option<ThreadSummary> {
+        let mut email_ids = vec![root_id];
+        if let Some(replies) = self.thread_replies.get(&root_id) {
+            email_ids.extend(replies.iter());
+        }
+
+        let mut message_count = 0;
+        let mut last_email = None;
+        for email_id in email_ids {
+            if let Some(email) = self.emails.get(&email_id) {
+                message_count += 1;
+                last_email = Some(EmailView::new(email_id, email));
+            }
+        }
+        last_email.map(|last_email| ThreadSummary {
+            root_id: U128(root_id),
+            last_email,
+            message_count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    #[test]
+    fn threads_summarise_their_replies_for_both_parties() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let root = U128(send(&mut contract, &alice(), &bob(), "root"));
+        set_context(&bob(), 1);
+        let reply = contract.reply_mail(root, "reply".to_string(), None, false);
+        send(&mut contract, &bob(), &alice(), "separate");
+
+        let threads = contract.get_threads(alice(), 0, 10);
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].root_id, root);
+        assert_eq!(threads[0].message_count, 2);
+        assert_eq!(threads[0].last_email.email_id, reply);
+        assert_eq!(threads[1].message_count, 1);
+
+        let page = contract.get_threads(bob(), 1, 10);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].root_id, U128(2));
+        assert!(contract.get_threads(carol(), 0, 10).is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn trash_mail(&mut self, email_id: U128) {
+        assert_one_yocto();
+        let real_email_id: EmailID = email_id.0;
+        let receiver = env::predecessor_account_id();
+        require(
+            self.holds_received(&receiver, real_email_id),
+            ContractError::CallerNotReceiver,
+        );
+        self.internal_trash(&receiver, real_email_id);
+        self.internal_touch(&receiver);
+    }
+
+    #[payable]
+    pub fn restore_mail(&mut self, email_id: U128) {
+        assert_one_yocto();
+        let receiver = env::predecessor_account_id();
+        assert!(
+            self.internal_restore(&receiver, email_id.0),
+            "Email not in trash"
+        );
+        self.internal_touch(&receiver);
+    }
+
+    #[payable]
+    pub fn restore_all(&mut self, limit: u64) -> u64 {
+        assert_one_yocto();
+        self.assert_batch_size(limit);
+        let receiver = env::predecessor_account_id();
+        let email_ids: Vec<EmailID> = self
+            .trash
+            .get(&receiver)
+            .map(|trash| trash.iter().take(limit as usize).collect())
+            .unwrap_or_default();
+        for email_id in email_ids.iter() {
+            self.internal_restore(&receiver, *email_id);
+        }
+        self.internal_touch(&receiver);
+        email_ids.len() as u64
+    }
+
+    #[payable]
+    pub fn empty_trash(&mut self, limit: u64) -> u64 {
+        assert_one_yocto();
+        self.assert_batch_size(limit);
+        let receiver = env::predecessor_account_id();
+        let email_ids: Vec<EmailID> = match self.trash.get(&receiver) {
+            Some(trash) => trash.iter().take(limit as usize).collect(),
+            None => return 0,
+        };
+        for email_id in email_ids.iter() {
+            match self.emails.get(email_id) {
+                Some(email) => self.internal_drop_received(&receiver, *email_id, &email),
+                None => self.internal_remove_from_trash(&receiver, *email_id),
+            }
+        }
+        self.internal_touch(&receiver);
+        email_ids.len() as u64
+    }
+
+    pub fn get_trash(&self, account: AccountId, from_index: u64, limit: u64) -> Vec<EmailView> {
+        self.trash
+            .get(&account)
+            .map(|trash| trash.to_vec())
+            .unwrap_or_default()
+            .into_iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|email_id| {
+                self.emails
+                    .get(&email_id)
+                    .map(|email| EmailView::new(email_id, email))
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn is_trashed_by(&self, account_id: &AccountId, email_id: EmailID) -> bool {
+        self.trash
+            .get(account_id)
+            .map(|trash| trash.contains(&email_id))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn internal_trash(&mut self, receiver: &AccountId, email_id: EmailID) {
+        self.internal_remove_received(receiver, email_id);
+        let mut trash = self.trash.get(receiver).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKeys::AccountTrash {
+                account_id: receiver.clone(),
+            })
+        });
+        trash.insert(&email_id);
+        self.trash.insert(receiver, &trash);
+    }
+
+    pub(crate) fn internal_remove_from_trash(&mut self, account_id: &AccountId, email_id: EmailID) {
+        if let Some(mut trash) = self.trash.get(account_id) {
+            if trash.remove(&email_id) {
+                self.internal_save_trash(account_id, &trash);
+            }
+        }
+    }
+
+    fn internal_restore(&mut self, receiver: &AccountId, email_id: EmailID) -> bool {
+        if !self.is_trashed_by(receiver, email_id) {
+            return false;
+        }
+        self.internal_remove_from_trash(receiver, email_id);
+        self.internal_add_to_inbox(receiver, email_id);
+        true
+    }
+
+    fn internal_save_trash(&mut self, account_id: &AccountId, trash: &UnorderedSet<EmailID>) {
+        if trash.is_empty() {
+            self.trash.remove(account_id);
+        } else {
+            self.trash.insert(account_id, trash);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+
+    fn mail_charged(contract: &Contract, account_id: &AccountId) -> Balance {
+        contract
+            .accounts
+            .get(account_id)
+            .unwrap()
+            .charges
+            .mail
+            .amount
+            .0
+    }
+
+    fn setup_trashed(count: usize) -> (Contract, Vec<EmailID>, Balance) {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        let before = mail_charged(&contract, &alice());
+        let ids: Vec<EmailID> = (0..count)
+            .map(|_| send(&mut contract, &alice(), &bob(), "hi"))
+            .collect();
+        for email_id in ids.iter() {
+            set_context(&bob(), 1);
+            contract.trash_mail(U128(*email_id));
+        }
+        (contract, ids, before)
+    }
+
+    #[test]
+    fn trash_pages_and_restores_in_batches() {
+        let (mut contract, ids, _) = setup_trashed(3);
+        assert!(contract.get_mail_receive(bob()).is_empty());
+        assert_eq!(contract.get_trash(bob(), 0, 2).len(), 2);
+        assert_eq!(contract.get_trash(bob(), 2, 10).len(), 1);
+        assert_eq!(contract.get_trash(bob(), 0, 10)[0].email.content, "");
+
+        set_context(&bob(), 1);
+        assert_eq!(contract.restore_all(2), 2);
+        assert_eq!(contract.get_trash(bob(), 0, 10).len(), 1);
+        set_context(&bob(), 1);
+        assert_eq!(contract.restore_all(10), 1);
+        assert!(contract.get_trash(bob(), 0, 10).is_empty());
+        for email_id in ids {
+            assert!(contract.is_receiver_of(&bob(), email_id));
+        }
+    }
+
+    #[test]
+    fn emptying_the_trash_refunds_the_payer() {
+        let (mut contract, ids, before) = setup_trashed(3);
+        for email_id in ids.iter() {
+            set_context(&alice(), 1);
+            contract.hide_mail(U128(*email_id));
+        }
+        let charged: Vec<Balance> = ids
+            .iter()
+            .map(|email_id| contract.emails.get(email_id).unwrap().storage_charged.0)
+            .collect();
+        let held = mail_charged(&contract, &alice());
+        assert_eq!(held, before + charged.iter().sum::<Balance>());
+
+        set_context(&bob(), 1);
+        assert_eq!(contract.empty_trash(2), 2);
+        assert_eq!(mail_charged(&contract, &alice()), before + charged[2]);
+        set_context(&bob(), 1);
+        assert_eq!(contract.empty_trash(10), 1);
+        assert_eq!(mail_charged(&contract, &alice()), before);
+        assert_eq!(contract.mail_exist(), 0);
+        set_context(&bob(), 1);
+        assert_eq!(contract.empty_trash(10), 0);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::*;
+use near_sdk::{ext_contract, Gas, PromiseResult};
+
+const GAS_FOR_MSG_METADATA: Gas = Gas(5_000_000_000_000);
+const GAS_FOR_ON_RECEIVER_VERIFIED: Gas = Gas(30_000_000_000_000);
+
+#[allow(dead_code)]
+#[ext_contract(ext_messaging)]
+pub trait Messaging {
+    fn msg_metadata(&self);
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn send_mail_verified(
+        &mut self,
+        receiver: AccountId,
+        title: String,
+        content: String,
+        fee: Option<U128>,
+        options: Option<SendOptions>,
+    ) -> Promise {
+        let sender = env::predecessor_account_id();
+        let attached = env::attached_deposit();
+        let mut email = Email::new(sender, receiver.clone(), title, content, fee);
+        options.unwrap_or_default().apply(&mut email);
+        self.prepare_mail(&mut email);
+        if let Err(error) = self.check_send(&email, attached) {
+            error.panic();
+        }
+        ext_messaging::ext(receiver)
+            .with_static_gas(GAS_FOR_MSG_METADATA)
+            .msg_metadata()
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ON_RECEIVER_VERIFIED)
+                    .on_receiver_verified(email, U128(attached)),
+            )
+    }
+
+    #[private]
+    pub fn on_receiver_verified(&mut self, email: Email, attached: U128) -> Option<U128> {
+        let plan = match env::promise_result(0) {
+            PromiseResult::Successful(_) => self.check_send(&email, attached.0),
+            _ => Err(ContractError::ReceiverNotVerified),
+        };
+        match plan {
+            Ok(plan) => Some(U128(self.internal_deliver(email, plan))),
+            Err(error) => {
+                env::log_str(&error.describe());
+                if attached.0 > 0 {
+                    Promise::new(email.sender).transfer(attached.0);
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use crate::*;
+    use near_sdk::{testing_env, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+    fn pending_email() -> Email {
+        Email::new(alice(), bob(), "Hello".to_string(), "hi".to_string(), None)
+    }
+
+    fn set_callback_context(result: PromiseResult) {
+        testing_env!(
+            context(&contract_account(), 0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E014")]
+    fn verified_send_checks_preconditions_before_dispatch() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&bob(), 1);
+        contract.block_sender(alice());
+
+        set_context(&alice(), 1);
+        contract.send_mail_verified(bob(), "Hello".to_string(), "hi".to_string(), None, None);
+    }
+
+    #[test]
+    fn callback_delivers_when_receiver_responds() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+
+        set_callback_context(PromiseResult::Successful(vec![]));
+        let email_id = contract.on_receiver_verified(pending_email(), U128(1));
+
+        assert_eq!(email_id, Some(U128(0)));
+        assert_eq!(contract.get_mail_receive_num(bob()), 1);
+        assert!(transfers().is_empty());
+    }
+
+    #[test]
+    fn callback_refunds_when_receiver_fails() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+
+        set_callback_context(PromiseResult::Failed);
+        assert_eq!(
+            contract.on_receiver_verified(pending_email(), U128(1)),
+            None
+        );
+        assert_eq!(contract.get_mail_receive_num(bob()), 0);
+        assert_eq!(transfers(), vec![(alice(), 1)]);
+    }
+
+    #[test]
+    fn callback_refunds_paid_mail_after_receiver_opts_out() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&bob(), 1);
+        contract.set_reject_paid_mail(true);
+
+        let mut email = pending_email();
+        email.fee = Some(FeeKind::Near(U128(500)));
+        set_callback_context(PromiseResult::Successful(vec![]));
+        assert_eq!(contract.on_receiver_verified(email, U128(501)), None);
+        assert_eq!(contract.get_mail_receive_num(bob()), 0);
+        assert_eq!(transfers(), vec![(alice(), 501)]);
+    }
+
+    #[test]
+    fn callback_refunds_when_precondition_fails() {
+        let mut contract = setup();
+        register(&mut contract, &alice());
+        register(&mut contract, &bob());
+        set_context(&bob(), 1);
+        contract.block_sender(alice());
+
+        set_callback_context(PromiseResult::Successful(vec![]));
+        assert_eq!(
+            contract.on_receiver_verified(pending_email(), U128(1)),
+            None
+        );
+        assert_eq!(contract.get_email_count(), U128(0));
+        assert_eq!(transfers(), vec![(alice(), 1)]);
+    }
+}
@@ -0,0 +1,93 @@
+//! Gas ceilings for the inbox paths, measured with the mocked runtime's host
+//! gas accounting (storage reads and writes, logs, promises). Wasm execution
+//! itself is not metered here, so the ceilings bound how much state a call
+//! touches rather than its full on-chain cost.
+
+use near_contract_standards::storage_management::StorageManagement;
+use near_message::Contract;
+use near_sdk::json_types::U128;
+use near_sdk::test_utils::{accounts, VMContextBuilder};
+use near_sdk::{env, testing_env, Gas, ONE_NEAR};
+
+const SEND_CEILING: Gas = Gas(6_000_000_000_000);
+const PAGE_CEILING: Gas = Gas(4_000_000_000_000);
+const DELETE_CEILING: Gas = Gas(6_000_000_000_000);
+const PAGE_SIZE: u64 = 10;
+
+fn call_as(account: usize, deposit: u128) {
+    testing_env!(VMContextBuilder::new()
+        .current_account_id(accounts(5))
+        .predecessor_account_id(accounts(account))
+        .attached_deposit(deposit)
+        .build());
+}
+
+/// A fresh contract whose receiver holds `size` mails.
+fn inbox_of(size: u64) -> Contract {
+    near_sdk::mock::with_mocked_blockchain(|blockchain| blockchain.take_storage());
+    call_as(0, 0);
+    let mut contract = Contract::new(accounts(0));
+    for account in [1, 2] {
+        call_as(account, 100 * ONE_NEAR);
+        contract.storage_deposit(None, None);
+    }
+    for _ in 0..size {
+        call_as(1, 1);
+        send_once(&mut contract);
+    }
+    contract
+}
+
+/// Gas used by one call, each in its own context.
+fn measure(account: usize, deposit: u128, call: impl FnOnce()) -> Gas {
+    call_as(account, deposit);
+    call();
+    env::used_gas()
+}
+
+#[test]
+fn bounded_paths_stay_under_ceilings_at_every_inbox_size() {
+    for size in [10, 100, 1000] {
+        let mut contract = inbox_of(size);
+        let send = measure(1, 1, || send_once(&mut contract));
+        let page = measure(2, 0, || {
+            contract.get_mail_receive_paginated(accounts(2), size - PAGE_SIZE, PAGE_SIZE);
+        });
+        let delete = measure(1, 1, || contract.delete_mail(U128(0)));
+
+        assert!(send <= SEND_CEILING, "send_mail at {size}: {send:?}");
+        assert!(page <= PAGE_CEILING, "paginated read at {size}: {page:?}");
+        assert!(
+            delete <= DELETE_CEILING,
+            "delete_mail at {size}: {delete:?}"
+        );
+    }
+}
+
+#[test]
+fn full_inbox_read_scales_at_most_linearly() {
+    let small = inbox_of(10);
+    let small_gas = measure(2, 0, || {
+        small.get_mail_receive(accounts(2));
+    });
+    let large = inbox_of(100);
+    let large_gas = measure(2, 0, || {
+        large.get_mail_receive(accounts(2));
+    });
+
+    // Ten times the mail, with a fixed part on top, costs under ten times the gas.
+    assert!(
+        large_gas.0 <= 10 * small_gas.0,
+        "get_mail_receive: {small_gas:?} at 10, {large_gas:?} at 100"
+    );
+}
+
+fn send_once(contract: &mut Contract) {
+    contract.send_mail(
+        accounts(2),
+        "Hello".to_string(),
+        "hi".to_string(),
+        None,
+        None,
+    );
+}